@@ -0,0 +1,107 @@
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::framebuffer::{FramebufferAbstract, RenderPassAbstract};
+use vulkano::image::AttachmentImage;
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::WindowId;
+
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::renderer::Renderer;
+use crate::vulkan::initialization::VulkanContext;
+
+// Whatever gets drawn. run owns the windows, their swapchains and the event loop and calls into
+// this, so a different demo only has to implement these on top of the same render pass. Everything
+// that's per window gets the id of the window it's about
+pub trait App {
+    // Once per frame before the windows get recorded, dt is the time since the last frame in
+    // seconds. renderers has every window that's still open
+    fn update(&mut self, dt: f32, renderers: &mut [Renderer]);
+
+    // Gets every event after the renderers have seen it. Closing the last window already quits,
+    // anything else that should quit has to set control_flow to Exit
+    fn handle_event(&mut self, event: &Event<'_, ()>, renderers: &mut [Renderer], control_flow: &mut ControlFlow);
+
+    // Begins and ends the render pass on framebuffer. dynamic_state has the viewport for it
+    fn record_draw(&mut self, window: WindowId, builder: AutoCommandBufferBuilder,
+                   framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>, image_index: usize,
+                   dynamic_state: &DynamicState) -> AutoCommandBufferBuilder;
+
+    // When the swapchain had to change its format (e.g. HDR got turned on). Every pipeline has to be
+    // rebuilt for the new render pass, framebuffers_recreated follows right after. On error the
+    // window stops drawing until its render pass changes again
+    fn render_pass_recreated(&mut self, _window: WindowId, _render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>)
+                             -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    // Before the first frame and whenever the swapchain got recreated. Anything recorded ahead of
+    // time for the old framebuffers or viewport has to be recorded again. depth_image is the one
+    // they all share, for anything that reads it like the decals
+    fn framebuffers_recreated(&mut self, _window: WindowId, _image_count: usize, _dynamic_state: &DynamicState,
+                              _depth_image: &Arc<AttachmentImage>) {}
+}
+
+// The renderer for the window an event is about
+pub fn find_renderer(renderers: &mut [Renderer], window: WindowId) -> Option<&mut Renderer> {
+    renderers.iter_mut()
+        .find(|renderer| renderer.window().id() == window)
+}
+
+// renderers has one renderer per window, all on the context's device
+pub fn run<A: App + 'static>(context: VulkanContext, event_loop: EventLoop<()>, mut renderers: Vec<Renderer>,
+                             mut app: A) -> ! {
+    let VulkanContext {
+        // event_loop.run never returns so this stays alive for the whole run
+        debug_callback: _debug_callback,
+        ..
+    } = context;
+
+    let mut last_frame = Instant::now();
+
+    event_loop.run(move |event, _, control_flow| {
+        match &event {
+            // Just that window goes away, the last one takes the app with it
+            Event::WindowEvent { window_id, event: WindowEvent::CloseRequested } => {
+                if let Some(index) = renderers.iter().position(|renderer| renderer.window().id() == *window_id) {
+                    renderers[index].wait_idle();
+                    renderers.remove(index);
+                }
+                if renderers.is_empty() {
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+            Event::WindowEvent { window_id, event: WindowEvent::Resized(_) } => {
+                if let Some(renderer) = find_renderer(&mut renderers, *window_id) {
+                    renderer.window_resized();
+                }
+            }
+            Event::WindowEvent { window_id, event: WindowEvent::ScaleFactorChanged { new_inner_size, .. } } => {
+                if let Some(renderer) = find_renderer(&mut renderers, *window_id) {
+                    renderer.scale_factor_changed((**new_inner_size).into());
+                }
+            }
+            // Every way out (close button, the app quitting) ends up here before the closure and
+            // with it the renderers get dropped
+            Event::LoopDestroyed => {
+                for renderer in &renderers {
+                    renderer.wait_idle();
+                }
+            }
+            Event::RedrawEventsCleared => {
+                let now = Instant::now();
+                app.update((now - last_frame).as_secs_f32(), &mut renderers);
+                last_frame = now;
+
+                for renderer in &mut renderers {
+                    renderer.render_frame(&mut app);
+                }
+            }
+            _ => ()
+        }
+
+        app.handle_event(&event, &mut renderers, control_flow);
+    })
+}
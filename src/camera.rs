@@ -0,0 +1,374 @@
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Matrix3, Matrix4, Point3, Quaternion, Rad, Rotation, Vector3, VectorSpace};
+
+use std::collections::HashSet;
+use std::f32::consts::FRAC_PI_2;
+
+// Physical key positions so WASD stays in the same place on AZERTY or Dvorak. Linux (evdev) and
+// Windows (set 1) happen to share these, macOS has its own numbering
+#[cfg(not(target_os = "macos"))]
+mod scancodes {
+    pub const W: u32 = 17;
+    pub const A: u32 = 30;
+    pub const S: u32 = 31;
+    pub const D: u32 = 32;
+    pub const Q: u32 = 16;
+    pub const E: u32 = 18;
+}
+
+#[cfg(target_os = "macos")]
+mod scancodes {
+    pub const W: u32 = 13;
+    pub const A: u32 = 0;
+    pub const S: u32 = 1;
+    pub const D: u32 = 2;
+    pub const Q: u32 = 12;
+    pub const E: u32 = 14;
+}
+
+// Stay a bit away from straight up/down, look_at breaks down when forward and up line up
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+pub struct Camera {
+    pub position: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    pub fov: Rad<f32>,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera {
+            position: Point3::new(0.0, 0.0, 2.0),
+            target: Point3::new(0.0, 0.0, 0.0),
+            up: Vector3::unit_y(),
+            fov: Deg(60.0).into(),
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+}
+
+impl Camera {
+    pub fn view(&self) -> Matrix4<f32> {
+        Matrix4::look_at(self.position, self.target, self.up)
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        (self.target - self.position).normalize()
+    }
+
+    // Yaw 0 looks down -z, positive yaw turns right and positive pitch looks up
+    pub fn rotate(&mut self, yaw: Rad<f32>, pitch: Rad<f32>) {
+        let forward = self.forward();
+        let yaw = forward.x.atan2(-forward.z) + yaw.0;
+        let pitch = (forward.y.asin() + pitch.0).clamp(-MAX_PITCH, MAX_PITCH);
+
+        let forward = Vector3::new(
+            pitch.cos() * yaw.sin(),
+            pitch.sin(),
+            -pitch.cos() * yaw.cos(),
+        );
+        self.target = self.position + forward;
+    }
+
+    // direction is relative to the camera: x is right, y is up and z is forward
+    pub fn fly(&mut self, direction: Vector3<f32>, distance: f32) {
+        let forward = self.forward();
+        let right = forward.cross(self.up).normalize();
+
+        let offset = right * direction.x + self.up * direction.y + forward * direction.z;
+        if offset.magnitude2() == 0.0 {
+            return;
+        }
+
+        let offset = offset.normalize() * distance;
+        self.position += offset;
+        self.target += offset;
+    }
+
+    pub fn projection(&self, aspect_ratio: f32) -> Matrix4<f32> {
+        // cgmath does OpenGL clip space, Vulkan has y pointing down and depth in 0..1
+        let opengl_to_vulkan = Matrix4::new(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, -1.0, 0.0, 0.0,
+            0.0, 0.0, 0.5, 0.0,
+            0.0, 0.0, 0.5, 1.0,
+        );
+
+        opengl_to_vulkan * cgmath::perspective(self.fov, aspect_ratio, self.near, self.far)
+    }
+
+    // The ray from the camera through a point on screen, in Vulkan's device coordinates (-1..1 with
+    // y pointing down). The direction is normalized
+    pub fn ray(&self, ndc: [f32; 2], aspect_ratio: f32) -> (Point3<f32>, Vector3<f32>) {
+        let forward = self.forward();
+        let right = forward.cross(self.up).normalize();
+        let up = right.cross(forward);
+        let half_height = (self.fov.0 / 2.0).tan();
+
+        let direction = forward + right * (ndc[0] * half_height * aspect_ratio) - up * (ndc[1] * half_height);
+        (self.position, direction.normalize())
+    }
+}
+
+// --camera fly|orbit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraMode {
+    Fly,
+    Orbit,
+}
+
+impl CameraMode {
+    pub fn from_arg(arg: &str) -> Option<CameraMode> {
+        match arg {
+            "fly" => Some(CameraMode::Fly),
+            "orbit" => Some(CameraMode::Orbit),
+            _ => None,
+        }
+    }
+}
+
+// How close and how far the orbit camera can zoom
+const MIN_ORBIT_DISTANCE: f32 = 0.5;
+const MAX_ORBIT_DISTANCE: f32 = 20.0;
+
+// Circles around target, always looking at it. This only keeps the angles and the distance, apply
+// turns them into a Camera so everything after that doesn't care which mode is used
+pub struct OrbitCamera {
+    pub target: Point3<f32>,
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+}
+
+impl OrbitCamera {
+    // Starts wherever camera is, looking at its target
+    pub fn from_camera(camera: &Camera) -> OrbitCamera {
+        let offset = camera.position - camera.target;
+        let distance = offset.magnitude().clamp(MIN_ORBIT_DISTANCE, MAX_ORBIT_DISTANCE);
+        let offset = offset.normalize();
+
+        OrbitCamera {
+            target: camera.target,
+            yaw: offset.x.atan2(offset.z),
+            pitch: offset.y.asin().clamp(-MAX_PITCH, MAX_PITCH),
+            distance,
+        }
+    }
+
+    // Positive yaw moves the camera to the right around the target and positive pitch moves it up
+    pub fn rotate(&mut self, yaw: Rad<f32>, pitch: Rad<f32>) {
+        self.yaw += yaw.0;
+        self.pitch = (self.pitch + pitch.0).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    // Multiplies the distance, below 1 moves closer
+    pub fn zoom(&mut self, factor: f32) {
+        self.distance = (self.distance * factor).clamp(MIN_ORBIT_DISTANCE, MAX_ORBIT_DISTANCE);
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        let offset = Vector3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        );
+        camera.target = self.target;
+        camera.position = self.target + offset * self.distance;
+    }
+}
+
+// Where the camera is and which way it's turned. The orientation turns -z (forward) and y (up) of
+// the camera into world space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    pub position: Point3<f32>,
+    pub orientation: Quaternion<f32>,
+}
+
+impl Keyframe {
+    pub fn from_camera(camera: &Camera) -> Keyframe {
+        let forward = camera.forward();
+        let right = forward.cross(camera.up).normalize();
+        let up = right.cross(forward);
+
+        Keyframe {
+            position: camera.position,
+            orientation: Quaternion::from(Matrix3::from_cols(right, up, -forward)),
+        }
+    }
+
+    // The camera looks one unit ahead, like after Camera::rotate
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.position = self.position;
+        camera.target = self.position + self.orientation.rotate_vector(-Vector3::unit_z());
+        camera.up = self.orientation.rotate_vector(Vector3::unit_y());
+    }
+
+    // The position in a straight line, the orientation along the shorter way around
+    pub fn interpolate(&self, other: &Keyframe, amount: f32) -> Keyframe {
+        // q and -q are the same rotation, slerp would take the long way to the other one
+        let other_orientation = if self.orientation.dot(other.orientation) < 0.0 {
+            -other.orientation
+        } else {
+            other.orientation
+        };
+
+        Keyframe {
+            position: Point3::from_vec(self.position.to_vec().lerp(other.position.to_vec(), amount)),
+            orientation: self.orientation
+                .slerp(other_orientation, amount)
+                .normalize(),
+        }
+    }
+}
+
+// Flies through the keyframes in order and back to the first, segment_duration seconds from one
+// to the next. Eases in and out of every keyframe so it doesn't jerk when the direction changes
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+    pub segment_duration: f32,
+}
+
+impl CameraPath {
+    pub fn new(segment_duration: f32) -> CameraPath {
+        CameraPath {
+            keyframes: vec!(),
+            segment_duration,
+        }
+    }
+
+    // Once a full loop is done time starts over. None with fewer than two keyframes, there's
+    // nothing to move between
+    pub fn keyframe_at(&self, time: f32) -> Option<Keyframe> {
+        let count = self.keyframes.len();
+        if count < 2 {
+            return None;
+        }
+
+        let position = (time / self.segment_duration).rem_euclid(count as f32);
+        let segment = (position as usize).min(count - 1);
+        let amount = position - segment as f32;
+        let smoothed = amount * amount * (3.0 - 2.0 * amount);
+
+        let from = &self.keyframes[segment];
+        let to = &self.keyframes[(segment + 1) % count];
+        Some(from.interpolate(to, smoothed))
+    }
+}
+
+// WASD moves, E and Q go up and down
+pub fn movement_direction(held_scancodes: &HashSet<u32>) -> Vector3<f32> {
+    let axis = |positive, negative| {
+        held_scancodes.contains(&positive) as i32 as f32 - held_scancodes.contains(&negative) as i32 as f32
+    };
+
+    Vector3::new(
+        axis(scancodes::D, scancodes::A),
+        axis(scancodes::E, scancodes::Q),
+        axis(scancodes::W, scancodes::S),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn looking_at(position: [f32; 3], target: [f32; 3]) -> Camera {
+        Camera {
+            position: Point3::from(position),
+            target: Point3::from(target),
+            ..Camera::default()
+        }
+    }
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn ray_goes_through_the_screen() {
+        let camera = looking_at([0.0, 0.0, 2.0], [0.0, 0.0, 0.0]);
+        let (origin, direction) = camera.ray([0.0, 0.0], 1.5);
+        assert_eq!(origin, camera.position);
+        assert_close(direction, -Vector3::unit_z());
+
+        // Wherever the ray goes it has to land back on the same spot once projected
+        let view_proj = camera.projection(1.5) * camera.view();
+        for &ndc in &[[0.5, -0.25], [-1.0, 1.0]] {
+            let (origin, direction) = camera.ray(ndc, 1.5);
+            let clip = view_proj * (origin + direction).to_homogeneous();
+            assert_close(Vector3::new(clip.x / clip.w, clip.y / clip.w, 0.0), Vector3::new(ndc[0], ndc[1], 0.0));
+        }
+    }
+
+    #[test]
+    fn keyframe_keeps_the_camera() {
+        let camera = looking_at([1.0, 2.0, 3.0], [0.0, 0.0, 0.0]);
+        let mut applied = Camera::default();
+        Keyframe::from_camera(&camera).apply(&mut applied);
+
+        assert_eq!(applied.position, camera.position);
+        assert_close(applied.forward(), camera.forward());
+        // Up stays about y, only bent towards the direction the camera looks in
+        assert!(applied.up.dot(Vector3::unit_y()) > 0.0);
+        assert!(applied.up.dot(applied.forward()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn interpolation_goes_halfway() {
+        let from = Keyframe::from_camera(&looking_at([0.0, 0.0, 0.0], [0.0, 0.0, -1.0]));
+        let to = Keyframe::from_camera(&looking_at([2.0, 0.0, 0.0], [3.0, 0.0, 0.0]));
+        let mut camera = Camera::default();
+        from.interpolate(&to, 0.5).apply(&mut camera);
+
+        assert_eq!(camera.position, Point3::new(1.0, 0.0, 0.0));
+        // Halfway between looking down -z and looking down +x
+        assert_close(camera.forward(), Vector3::new(1.0, 0.0, -1.0).normalize());
+    }
+
+    #[test]
+    fn interpolation_takes_the_short_way() {
+        let from = Keyframe::from_camera(&looking_at([0.0, 0.0, 0.0], [0.0, 0.0, -1.0]));
+        let mut to = Keyframe::from_camera(&looking_at([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]));
+        to.orientation = -to.orientation;
+        let mut camera = Camera::default();
+        from.interpolate(&to, 0.5).apply(&mut camera);
+
+        assert_close(camera.forward(), Vector3::new(1.0, 0.0, -1.0).normalize());
+    }
+
+    #[test]
+    fn movement_goes_by_scancode() {
+        let held = |keys: &[u32]| keys.iter().cloned().collect::<HashSet<u32>>();
+        assert_eq!(movement_direction(&held(&[])), Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(movement_direction(&held(&[scancodes::W])), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(movement_direction(&held(&[scancodes::A, scancodes::Q])), Vector3::new(-1.0, -1.0, 0.0));
+        // Opposite keys cancel out
+        assert_eq!(movement_direction(&held(&[scancodes::D, scancodes::A, scancodes::E])), Vector3::new(0.0, 1.0, 0.0));
+        // Anything else doesn't move
+        assert_eq!(movement_direction(&held(&[57, 100])), Vector3::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn path_loops_through_the_keyframes() {
+        let mut path = CameraPath::new(2.0);
+        assert!(path.keyframe_at(0.0).is_none());
+
+        path.keyframes.push(Keyframe::from_camera(&looking_at([0.0, 0.0, 0.0], [0.0, 0.0, -1.0])));
+        assert!(path.keyframe_at(0.0).is_none());
+        path.keyframes.push(Keyframe::from_camera(&looking_at([4.0, 0.0, 0.0], [4.0, 0.0, -1.0])));
+
+        let x = |time| path.keyframe_at(time).unwrap().position.x;
+        assert_eq!(x(0.0), 0.0);
+        // Eased, but halfway is still halfway
+        assert!((x(1.0) - 2.0).abs() < 1e-4);
+        assert!(x(0.5) < 1.0);
+        assert!((x(2.0) - 4.0).abs() < 1e-4);
+        // And back to the first one
+        assert!((x(3.0) - 2.0).abs() < 1e-4);
+        assert!(x(4.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,94 @@
+use vulkano::format::Format;
+
+// Hue in 0..1 (wraps around), saturation and value in 0..1
+pub fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> [f32; 3] {
+    let hue = hue.rem_euclid(1.0) * 6.0;
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    let m = value - chroma;
+    [r + m, g + m, b + m]
+}
+
+// sRGB attachments encode whatever gets written (clear values included) as if it were linear, so
+// a color picked in sRGB (like from a color picker) has to be decoded first to come out the same.
+// UNORM attachments store it as is. Alpha is always linear
+pub fn clear_color_for(format: Format, srgb: [f32; 4]) -> [f32; 4] {
+    if !is_srgb(format) {
+        return srgb;
+    }
+
+    let [r, g, b, a] = srgb;
+    [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Only the formats a color attachment can realistically have
+pub fn is_srgb(format: Format) -> bool {
+    matches!(
+        format,
+        Format::R8Srgb | Format::R8G8Srgb | Format::R8G8B8Srgb | Format::B8G8R8Srgb | Format::R8G8B8A8Srgb
+            | Format::B8G8R8A8Srgb | Format::A8B8G8R8SrgbPack32
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: [f32; 3], b: [f32; 3]) -> bool {
+        a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-5)
+    }
+
+    #[test]
+    fn hues_go_around() {
+        assert!(close(hsv_to_rgb(0.0, 1.0, 1.0), [1.0, 0.0, 0.0]));
+        assert!(close(hsv_to_rgb(1.0 / 3.0, 1.0, 1.0), [0.0, 1.0, 0.0]));
+        assert!(close(hsv_to_rgb(2.0 / 3.0, 1.0, 1.0), [0.0, 0.0, 1.0]));
+        // Wraps around both ways
+        assert!(close(hsv_to_rgb(1.5, 1.0, 1.0), hsv_to_rgb(0.5, 1.0, 1.0)));
+        assert!(close(hsv_to_rgb(-0.25, 1.0, 1.0), hsv_to_rgb(0.75, 1.0, 1.0)));
+        // No saturation is gray
+        assert!(close(hsv_to_rgb(0.3, 0.0, 0.5), [0.5, 0.5, 0.5]));
+    }
+
+    #[test]
+    fn srgb_decodes_at_the_ends_and_the_knee() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        // Both halves meet there
+        let knee = 0.04045;
+        assert!((srgb_to_linear(knee) - knee / 12.92).abs() < 1e-6);
+        assert!((((knee + 0.055) / 1.055).powf(2.4) - knee / 12.92).abs() < 1e-6);
+    }
+
+    #[test]
+    fn clear_color_only_decodes_for_srgb() {
+        let color = [0.5, 0.04045, 1.0, 0.5];
+        assert_eq!(clear_color_for(Format::B8G8R8A8Unorm, color), color);
+
+        let [r, g, b, a] = clear_color_for(Format::B8G8R8A8Srgb, color);
+        assert!((r - 0.214041).abs() < 1e-5);
+        assert!((g - 0.04045 / 12.92).abs() < 1e-6);
+        assert_eq!(b, srgb_to_linear(1.0));
+        // Alpha always is linear
+        assert_eq!(a, 0.5);
+        assert_eq!(clear_color_for(Format::B8G8R8A8Srgb, [0.0, 0.0, 0.0, 1.0]), [0.0, 0.0, 0.0, 1.0]);
+    }
+}
@@ -0,0 +1,78 @@
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Queue;
+use vulkano::pipeline::ComputePipeline;
+use vulkano::sync::{self, GpuFuture};
+
+use std::sync::Arc;
+
+use crate::vulkan::shader::{load_shader, ShaderLoadError, ShaderRequirements};
+
+// Has to match local_size_x in fill.comp
+const WORKGROUP_SIZE: u32 = 64;
+
+// The compute example: fills a storage buffer with the square of each index on the GPU and reads
+// it back. Doesn't need a window, just a queue that can do compute
+pub fn compute_squares(queue: &Arc<Queue>, count: u32) -> Result<Vec<u32>, ShaderLoadError> {
+    let device = queue.device();
+    // A plain storage buffer, nothing past the Shader capability
+    let shader = load_shader(device, "fill.comp", ShaderRequirements::none(), || cs::Shader::load(device.clone()))?;
+
+    let pipeline = Arc::new(
+        ComputePipeline::new(device.clone(), &shader.main_entry_point(), &())
+            .unwrap()
+    );
+
+    let buffer = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::all(),
+        false,
+        (0..count).map(|_| 0u32),
+    )
+        .unwrap();
+
+    let layout = pipeline.descriptor_set_layout(0).unwrap();
+    let set = Arc::new(
+        PersistentDescriptorSet::start(
+            layout.clone()
+        )
+            .add_buffer(buffer.clone())
+            .unwrap()
+            .build()
+            .unwrap()
+    );
+
+    // Rounded up, the shader skips the invocations past the end of the buffer
+    let workgroups = count.div_ceil(WORKGROUP_SIZE);
+
+    let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
+        device.clone(),
+        queue.family(),
+    )
+        .unwrap()
+        .dispatch([workgroups, 1, 1], pipeline.clone(), set, ())
+        .unwrap()
+        .build()
+        .unwrap();
+
+    sync::now(device.clone())
+        .then_execute(queue.clone(), command_buffer)
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap()
+        .wait(None)
+        .unwrap();
+
+    let values = buffer.read()
+        .unwrap();
+    Ok(values.to_vec())
+}
+
+mod cs {
+    vulkano_shaders::shader!{
+        ty: "compute",
+        path: "src/fill.comp"
+    }
+}
@@ -0,0 +1,84 @@
+use vulkano::device::Queue;
+use vulkano::sampler::{Filter, MipmapMode, SamplerAddressMode};
+use cgmath::{EuclideanSpace, InnerSpace, Matrix3, Matrix4, Point3, SquareMatrix, Vector3};
+use image::{Rgba, RgbaImage};
+
+use std::sync::Arc;
+
+use crate::texture::{texture_from_pixels, SamplerConfig, Texture, TextureError};
+
+const DEFAULT_TEXTURE_SIZE: u32 = 64;
+
+// A texture projected onto everything inside a box, see frag_decal.glsl. Lives in the space of
+// the meshes before they spin, so it stays on the surface it was put on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decal {
+    pub position: Point3<f32>,
+    // Out of the surface, the texture gets projected along it
+    pub normal: Vector3<f32>,
+    // Of every side of the box, it's as deep as it is wide
+    pub size: f32,
+}
+
+impl Decal {
+    // Into the box frag_decal.glsl tests against, -0.5..0.5 on every axis with z along the normal
+    pub fn box_matrix(&self) -> Matrix4<f32> {
+        let z = self.normal.normalize();
+        // Anything not parallel to the normal works for the texture's up, the world's y keeps it
+        // upright on walls
+        let helper = if z.y.abs() < 0.99 { Vector3::unit_y() } else { Vector3::unit_z() };
+        let x = helper.cross(z).normalize();
+        let y = z.cross(x);
+
+        let rotation = Matrix4::from(Matrix3::from_cols(x, y, z));
+        let from_decal = Matrix4::from_translation(self.position.to_vec()) * rotation * Matrix4::from_scale(self.size);
+        from_decal.invert()
+            .unwrap()
+    }
+}
+
+// A ring with a dot in the middle, for when --decal doesn't give one
+pub fn default_decal_texture(queue: &Arc<Queue>) -> Result<Texture, TextureError> {
+    let center = DEFAULT_TEXTURE_SIZE as f32 / 2.0;
+    let pixels = RgbaImage::from_fn(DEFAULT_TEXTURE_SIZE, DEFAULT_TEXTURE_SIZE, |x, y| {
+        let distance = ((x as f32 + 0.5 - center).powi(2) + (y as f32 + 0.5 - center).powi(2)).sqrt() / center;
+        let inside = distance < 0.15 || (0.6..0.85).contains(&distance);
+        if inside { Rgba([255, 64, 32, 255]) } else { Rgba([0, 0, 0, 0]) }
+    });
+
+    let sampler_config = SamplerConfig {
+        mag_filter: Filter::Linear,
+        min_filter: Filter::Linear,
+        mipmap_mode: MipmapMode::Linear,
+        address_mode: SamplerAddressMode::ClampToEdge,
+    };
+    texture_from_pixels(pixels, queue, true, true, &sampler_config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Transform;
+
+    fn assert_close(a: Point3<f32>, b: Point3<f32>) {
+        assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn box_is_around_the_position() {
+        let decal = Decal { position: Point3::new(1.0, 2.0, 3.0), normal: Vector3::new(0.0, 0.0, 2.0), size: 0.5 };
+        let box_matrix = decal.box_matrix();
+
+        assert_close(box_matrix.transform_point(decal.position), Point3::new(0.0, 0.0, 0.0));
+        assert_close(box_matrix.transform_point(Point3::new(1.0, 2.0, 3.25)), Point3::new(0.0, 0.0, 0.5));
+        // Upright, the world's y is the texture's
+        assert_close(box_matrix.transform_point(Point3::new(1.0, 2.25, 3.0)), Point3::new(0.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn straight_up_normal() {
+        let decal = Decal { position: Point3::new(0.0, 0.0, 0.0), normal: Vector3::unit_y(), size: 1.0 };
+        let local = decal.box_matrix().transform_point(Point3::new(0.0, 0.5, 0.0));
+        assert_close(local, Point3::new(0.0, 0.0, 0.5));
+    }
+}
@@ -0,0 +1,820 @@
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::device::Device;
+use vulkano::framebuffer::{FramebufferAbstract, RenderPassAbstract};
+use vulkano::image::AttachmentImage;
+use vulkano::swapchain::PresentMode;
+use winit::dpi::PhysicalPosition;
+use winit::event::{Event, WindowEvent, DeviceEvent, ElementState, KeyboardInput, VirtualKeyCode, MouseButton,
+                   MouseScrollDelta};
+use winit::event_loop::ControlFlow;
+use winit::monitor::MonitorHandle;
+use winit::window::{Window, WindowId};
+use cgmath::{Point3, Rad, Vector3};
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::app::{find_renderer, App};
+use crate::camera::{Camera, CameraPath, Keyframe, OrbitCamera, movement_direction};
+use crate::color::hsv_to_rgb;
+use crate::decal::Decal;
+use crate::frame_timer::{refresh_rate, FrameLimiter, FrameTimer};
+use crate::hot_reload::ShaderWatcher;
+use crate::post::{ColorMode, DepthComplexityView, COLOR_MODES, DEPTH_COMPLEXITY_TILE_SIZE};
+use crate::reel::DemoReel;
+use crate::renderer::{Renderer, Scene, SceneError};
+use crate::session::{save_session, Assets, CameraState, DecalState, KeyframeState, Session};
+use crate::shapes::Shape;
+use crate::streaming::{Asset, AssetStreamer};
+use crate::text::text_size;
+use crate::vulkan::initialization::VulkanContext;
+use crate::vulkan::memory::log_memory_heaps;
+#[cfg(feature = "renderdoc")]
+use crate::vulkan::capture::FrameCapture;
+
+// F toggles the fps counter, which goes to the text overlay or the window title depending on this
+const FPS_IN_WINDOW_TITLE: bool = false;
+// Pixels from the top left corner of every window
+const OVERLAY_TEXT_POSITION: [f32; 2] = [8.0, 8.0];
+// The panel behind the overlay reaches this far past the text, in pixels
+const OVERLAY_PADDING: f32 = 6.0;
+const OVERLAY_CORNER_RADIUS: f32 = 6.0;
+const OVERLAY_BORDER: [f32; 4] = [1.0, 1.0, 1.0, 0.3];
+// After the camera path line while it plays
+const PLAYING_DOT_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+
+// Where the axis labels go, a bit past the ends of the unit length axes
+const AXIS_LABELS: [([f32; 3], &str); 3] = [
+    ([1.1, 0.0, 0.0], "X"),
+    ([0.0, 1.1, 0.0], "Y"),
+    ([0.0, 0.0, 1.1], "Z"),
+];
+
+// V cycles through these
+const PRESENT_MODES: [PresentMode; 3] = [
+    PresentMode::Fifo,
+    PresentMode::Mailbox,
+    PresentMode::Immediate,
+];
+
+// Units per second and radians per pixel of mouse motion
+const CAMERA_SPEED: f32 = 1.5;
+const MOUSE_SENSITIVITY: f32 = 0.003;
+
+// Each line the scroll wheel turns moves the orbit camera this much closer. Touchpads scroll in
+// pixels, those get counted in lines of this many pixels
+const ZOOM_PER_LINE: f32 = 0.9;
+const PIXELS_PER_LINE: f64 = 20.0;
+
+// F12 saves the next frame here, overwriting the last screenshot
+const SCREENSHOT_PATH: &str = "screenshot.png";
+
+// C cycles through these and then a background that slowly goes through all hues
+pub const CLEAR_COLORS: [[f32; 4]; 4] = [
+    [0.0, 0.0, 1.0, 1.0],
+    [0.0, 0.0, 0.0, 1.0],
+    [0.2, 0.2, 0.2, 1.0],
+    [1.0, 1.0, 1.0, 1.0],
+];
+const HUE_CYCLE_SECONDS: f32 = 10.0;
+
+// O cycles through these, None draws the scene as usual
+const DEPTH_COMPLEXITY_VIEWS: [Option<DepthComplexityView>; 4] = [
+    None,
+    Some(DepthComplexityView::Pixels),
+    Some(DepthComplexityView::TileAverage),
+    Some(DepthComplexityView::TileMax),
+];
+
+// Seconds from one camera keyframe to the next until --keyframe-duration says otherwise
+pub const DEFAULT_KEYFRAME_DURATION: f32 = 3.0;
+
+// What G dithers with until --dither says otherwise, in 8 bit steps
+const DEFAULT_DITHER_STRENGTH: f32 = 1.0;
+
+// How big the box of a decal placed with middle click is, the mesh is about 1 across
+const DECAL_SIZE: f32 = 0.3;
+
+// For --fps-cap refresh on monitors that don't say what their refresh rate is
+const FALLBACK_REFRESH_RATE: u32 = 60;
+
+// The spinning triangles with the fly camera and all the debugging keys. Every window shows the
+// same scene from the same camera, the keys that change how it's presented only affect the
+// window they were pressed in
+pub struct Demo {
+    device: Arc<Device>,
+    // Every window's own, the fps get added to the end of them
+    titles: HashMap<WindowId, String>,
+    scene: Scene,
+    // The scene target each window draws into
+    targets: HashMap<WindowId, usize>,
+    camera: Camera,
+    // Time the animation has been running for, this doesn't advance while paused
+    animation_time: f32,
+    // For the hue cycle, which keeps going while paused since Space is only meant to stop the rotation
+    hue_time: f32,
+    paused: bool,
+    show_fps: bool,
+    // The last stats the frame timer handed out, shown until the next ones
+    fps_text: Option<String>,
+    frame_timer: FrameTimer,
+    frame_limiter: Option<FrameLimiter>,
+    // Set by cap_at_refresh_rate, the monitor the cap was last taken from
+    refresh_monitor: Option<MonitorHandle>,
+    present_mode_index: usize,
+    clear_color_index: usize,
+    color_mode_index: usize,
+    depth_complexity_index: usize,
+    // G toggles between this and no dithering at all
+    dither_strength: f32,
+    dither: bool,
+    // winit sends repeated Pressed events while a key is held, toggles should only fire once
+    held_keys: HashSet<VirtualKeyCode>,
+    held_scancodes: HashSet<u32>,
+    mouse_look: bool,
+    // Replaces the fly camera when set, camera then just follows it
+    orbit: Option<OrbitCamera>,
+    orbit_drag: bool,
+    // N adds the camera as it is, T flies through them
+    camera_path: CameraPath,
+    // Seconds into the path while it's playing, in real time so pausing doesn't stop it
+    path_time: Option<f32>,
+    // The last CursorMoved, to get how far it moved since
+    cursor_position: Option<PhysicalPosition<f64>>,
+    shader_watcher: Option<ShaderWatcher>,
+    // Where F5 and quitting save the session to along with the assets it was started with, see
+    // set_session_file
+    session_file: Option<(PathBuf, Assets)>,
+    // Tab streams in the next of stream_paths, over and over
+    streamer: Option<AssetStreamer>,
+    stream_paths: Vec<PathBuf>,
+    stream_index: usize,
+    // Switches the first mesh by itself, see set_demo_reel
+    reel: Option<DemoReel>,
+    #[cfg(feature = "renderdoc")]
+    frame_capture: FrameCapture,
+}
+
+impl Demo {
+    // frame_limiter caps the frame rate no matter the present mode, shader_watcher hot reloads the
+    // first triangle's shaders. C cycles on from whatever clear color the scene already has
+    pub fn new(context: &VulkanContext, title: String, scene: Scene, shader_watcher: Option<ShaderWatcher>,
+               frame_limiter: Option<FrameLimiter>, #[cfg(feature = "renderdoc")] frame_capture: FrameCapture) -> Demo {
+        // The scene was created with the first window's render pass
+        let mut targets = HashMap::new();
+        targets.insert(context.window.surface.window().id(), 0);
+        let mut titles = HashMap::new();
+        titles.insert(context.window.surface.window().id(), title);
+
+        Demo {
+            device: context.device.clone(),
+            titles,
+            scene,
+            targets,
+            camera: Camera::default(),
+            animation_time: 0.0,
+            hue_time: 0.0,
+            paused: false,
+            show_fps: false,
+            fps_text: None,
+            frame_timer: FrameTimer::new(),
+            frame_limiter,
+            refresh_monitor: None,
+            present_mode_index: 0,
+            clear_color_index: 0,
+            color_mode_index: 0,
+            depth_complexity_index: 0,
+            dither_strength: DEFAULT_DITHER_STRENGTH,
+            dither: false,
+            held_keys: HashSet::new(),
+            held_scancodes: HashSet::new(),
+            mouse_look: false,
+            orbit: None,
+            orbit_drag: false,
+            camera_path: CameraPath::new(DEFAULT_KEYFRAME_DURATION),
+            path_time: None,
+            cursor_position: None,
+            shader_watcher,
+            session_file: None,
+            streamer: None,
+            stream_paths: vec!(),
+            stream_index: 0,
+            reel: None,
+            #[cfg(feature = "renderdoc")]
+            frame_capture,
+        }
+    }
+
+    // Left drag rotates around the origin and scrolling zooms, instead of flying around
+    pub fn use_orbit_camera(&mut self) {
+        self.orbit = Some(OrbitCamera::from_camera(&self.camera));
+    }
+
+    // Seconds the camera path takes from one keyframe to the next
+    pub fn set_keyframe_duration(&mut self, seconds: f32) {
+        self.camera_path.segment_duration = seconds;
+    }
+
+    // Turns the dithering on in every window, G goes back and forth between this strength and off
+    pub fn set_dither(&mut self, strength: f32, renderers: &mut [Renderer]) {
+        self.dither_strength = strength;
+        self.dither = true;
+        for renderer in renderers.iter_mut() {
+            renderer.set_dither(strength);
+        }
+    }
+
+    // Everything the session has that isn't the scene's, Session::apply_to_scene already went
+    // into that. Has to come before the flags that could say otherwise
+    pub fn restore_session(&mut self, session: &Session, renderers: &mut [Renderer]) {
+        self.animation_time = session.animation_time;
+        self.paused = session.paused;
+        if session.show_fps {
+            self.show_fps = true;
+            for renderer in renderers.iter_mut() {
+                renderer.set_gpu_timing(true);
+            }
+        }
+        if let Some(clear_color) = session.clear_color {
+            self.clear_color_index = session.clear_color_index.min(CLEAR_COLORS.len());
+            self.scene.clear_color = clear_color;
+        }
+        match COLOR_MODES.iter().position(|mode| mode.name() == session.color_mode) {
+            Some(index) => {
+                self.color_mode_index = index;
+                for renderer in renderers.iter_mut() {
+                    renderer.set_color_mode(COLOR_MODES[index]);
+                }
+            }
+            None => println!("Unknown color mode {} in the session, using normal", session.color_mode),
+        }
+        if let Some(strength) = session.dither {
+            self.set_dither(strength, renderers);
+        }
+
+        self.camera = Camera::from(&session.camera);
+        if session.orbit_camera {
+            self.use_orbit_camera();
+        }
+        self.camera_path.keyframes = session.keyframes.iter()
+            .map(Keyframe::from)
+            .collect();
+        self.camera_path.segment_duration = session.keyframe_duration;
+    }
+
+    // Saves the session to path with F5 and when the demo quits. assets is what the scene was
+    // created from, the scene doesn't remember
+    pub fn set_session_file(&mut self, path: PathBuf, assets: Assets) {
+        self.session_file = Some((path, assets));
+    }
+
+    fn session(&self, assets: &Assets) -> Session {
+        Session {
+            animation_time: self.animation_time,
+            paused: self.paused,
+            show_fps: self.show_fps,
+            clear_color_index: self.clear_color_index,
+            clear_color: Some(self.scene.clear_color),
+            color_mode: COLOR_MODES[self.color_mode_index].name().to_string(),
+            dither: Some(self.dither_strength).filter(|_| self.dither),
+            wireframe: self.scene.wireframe,
+            show_axes: self.scene.show_axes,
+            show_bounds: self.scene.show_bounds,
+            show_transparent: self.scene.show_transparent,
+            stencil_mask: self.scene.stencil_mask,
+            uv_grid: self.scene.uv_grid,
+            affine_uvs: self.scene.affine_uvs,
+            label_depth_test: self.scene.label_depth_test,
+            rotation_speed: self.scene.rotation_speed,
+            orbit_camera: self.orbit.is_some(),
+            keyframe_duration: self.camera_path.segment_duration,
+            assets: assets.clone(),
+            camera: CameraState::from(&self.camera),
+            keyframes: self.camera_path.keyframes.iter()
+                .map(KeyframeState::from)
+                .collect(),
+            decals: self.scene.decals().iter()
+                .map(DecalState::from)
+                .collect(),
+        }
+    }
+
+    fn write_session(&self) {
+        let (path, assets) = match &self.session_file {
+            Some(session_file) => session_file,
+            None => {
+                println!("Saving the session needs --session");
+                return;
+            }
+        };
+        match save_session(path, &self.session(assets)) {
+            Ok(()) => println!("Saved the session to {}", path.display()),
+            Err(e) => println!("Failed to save the session to {}: {}", path.display(), e),
+        }
+    }
+
+    // Tab hands the next of paths to streamer, starting from the first
+    pub fn set_streamer(&mut self, streamer: AssetStreamer, paths: Vec<PathBuf>) {
+        self.streamer = Some(streamer);
+        self.stream_paths = paths;
+        self.stream_index = 0;
+    }
+
+    // Every window gets a post pass for the crossfade, for as long as the demo runs
+    pub fn set_demo_reel(&mut self, reel: DemoReel, renderers: &mut [Renderer]) {
+        for renderer in renderers.iter_mut() {
+            renderer.set_crossfade(Some(0.0));
+        }
+        self.reel = Some(reel);
+    }
+
+    fn stream_next(&mut self) {
+        let streamer = match &mut self.streamer {
+            Some(streamer) => streamer,
+            None => {
+                println!("Streaming needs --stream");
+                return;
+            }
+        };
+
+        let path = self.stream_paths[self.stream_index % self.stream_paths.len()].clone();
+        let name = path.display().to_string();
+        let requested = streamer.request(path, move |scene, asset| match asset {
+            Ok(Asset::Mesh(mesh)) => {
+                scene.replace_mesh(mesh);
+                println!("Streamed in {}", name);
+            }
+            Ok(Asset::Texture(texture)) => match scene.replace_texture(texture) {
+                Ok(()) => println!("Streamed in {}", name),
+                Err(_) => println!("Streamed in {} but there's no --texture quad to put it on", name),
+            },
+            Err(e) => println!("Failed to stream in {}: {}", name, e),
+        });
+        match requested {
+            Ok(()) => self.stream_index += 1,
+            Err(path) => println!("The streaming queue is full, try {} again later", path.display()),
+        }
+    }
+
+    // Caps the frame rate at the refresh rate of the monitor window is on, and follows any window
+    // to the monitor it moves to. Replaces whatever cap there was
+    pub fn cap_at_refresh_rate(&mut self, window: &Window) {
+        self.refresh_monitor = None;
+        self.follow_refresh_rate(window.current_monitor());
+    }
+
+    fn follow_refresh_rate(&mut self, monitor: MonitorHandle) {
+        // Moved fires all the time while dragging, the video modes are only worth a look on another monitor
+        if self.refresh_monitor.as_ref() == Some(&monitor) {
+            return;
+        }
+
+        let monitor_name = monitor.name().unwrap_or_else(|| "the monitor".to_string());
+        let target_fps = match refresh_rate(&monitor) {
+            Some(refresh_rate) => {
+                println!("Capping the frame rate at {} fps, the refresh rate of {}", refresh_rate, monitor_name);
+                refresh_rate
+            }
+            None => {
+                println!(
+                    "Couldn't find the refresh rate of {}, capping the frame rate at {} fps",
+                    monitor_name,
+                    FALLBACK_REFRESH_RATE,
+                );
+                FALLBACK_REFRESH_RATE
+            }
+        };
+        match &mut self.frame_limiter {
+            Some(frame_limiter) => frame_limiter.set_target_fps(target_fps),
+            None => self.frame_limiter = Some(FrameLimiter::new(target_fps)),
+        }
+        self.refresh_monitor = Some(monitor);
+    }
+
+    // What the windows were created with, so V carries on from there
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        if let Some(index) = PRESENT_MODES.iter().position(|mode| *mode == present_mode) {
+            self.present_mode_index = index;
+        }
+    }
+
+    // For every window at once. The text overlay isn't drawn while it's on (it would count as
+    // overdraw too), so the legend goes to stdout instead
+    pub fn set_depth_complexity(&mut self, view: Option<DepthComplexityView>, renderers: &mut [Renderer]) {
+        if let Some(index) = DEPTH_COMPLEXITY_VIEWS.iter().position(|v| *v == view) {
+            self.depth_complexity_index = index;
+        }
+        self.scene.overdraw = view.is_some();
+        for renderer in renderers.iter_mut() {
+            renderer.set_depth_complexity(view);
+        }
+
+        match view {
+            Some(DepthComplexityView::Pixels) => {
+                println!("Showing the depth complexity per pixel, from blue for 1 fragment to red for 16 or more");
+            }
+            Some(view) => println!(
+                "Showing the depth complexity as the {} of every {}x{} tile, from blue for 1 fragment to red for 16 or more",
+                view.name(),
+                DEPTH_COMPLEXITY_TILE_SIZE,
+                DEPTH_COMPLEXITY_TILE_SIZE,
+            ),
+            None => println!("Depth complexity view off"),
+        }
+    }
+
+    fn toggle_camera_path(&mut self) {
+        if self.path_time.is_some() {
+            // Carries on from wherever the path was, the other cameras expect y to be up
+            self.path_time = None;
+            self.camera.up = Vector3::unit_y();
+            if self.orbit.is_some() {
+                self.orbit = Some(OrbitCamera::from_camera(&self.camera));
+            }
+        } else if self.camera_path.keyframes.len() < 2 {
+            println!("The camera path needs at least 2 keyframes, add them with N");
+        } else {
+            self.path_time = Some(0.0);
+        }
+    }
+
+    // On the first mesh under the cursor, facing out of the surface
+    fn place_decal(&mut self, window: WindowId) {
+        let target = self.targets[&window];
+        if !self.scene.decals_supported(target) {
+            println!("Decals need --decals");
+            return;
+        }
+        let cursor = match self.cursor_position {
+            Some(cursor) => [cursor.x as f32, cursor.y as f32],
+            None => return,
+        };
+
+        match self.scene.pick(target, cursor, self.animation_time, &self.camera) {
+            Some((position, normal)) => self.scene.add_decal(Decal { position, normal, size: DECAL_SIZE }),
+            None => println!("Nothing to put a decal on under the cursor"),
+        }
+    }
+
+    // For every window after the first
+    pub fn add_window(&mut self, renderer: &Renderer, title: String) -> Result<(), SceneError> {
+        let target = self.scene.add_target(renderer.render_pass())?;
+        self.scene.set_letterbox_color(target, renderer.letterbox_color());
+        self.targets.insert(renderer.window().id(), target);
+        self.titles.insert(renderer.window().id(), title);
+        Ok(())
+    }
+
+    fn key_pressed(&mut self, key: VirtualKeyCode, window: WindowId, renderers: &mut [Renderer],
+                   control_flow: &mut ControlFlow) {
+        match key {
+            VirtualKeyCode::Escape => *control_flow = ControlFlow::Exit,
+            VirtualKeyCode::Space => self.paused = !self.paused,
+            VirtualKeyCode::F => {
+                self.show_fps = !self.show_fps;
+                self.fps_text = None;
+                self.frame_timer = FrameTimer::new();
+                for renderer in renderers.iter_mut() {
+                    renderer.set_gpu_timing(self.show_fps);
+                    if !self.show_fps && FPS_IN_WINDOW_TITLE {
+                        if let Some(title) = self.titles.get(&renderer.window().id()) {
+                            renderer.window().set_title(title);
+                        }
+                    }
+                }
+            }
+            VirtualKeyCode::V => {
+                self.present_mode_index = (self.present_mode_index + 1) % PRESENT_MODES.len();
+                for renderer in renderers.iter_mut() {
+                    renderer.set_present_mode(PRESENT_MODES[self.present_mode_index]);
+                }
+            }
+            VirtualKeyCode::C => {
+                // One past the end is the hue cycle, which is set every frame
+                self.clear_color_index = (self.clear_color_index + 1) % (CLEAR_COLORS.len() + 1);
+                if let Some(&clear_color) = CLEAR_COLORS.get(self.clear_color_index) {
+                    self.scene.clear_color = clear_color;
+                }
+            }
+            VirtualKeyCode::P => {
+                self.color_mode_index = (self.color_mode_index + 1) % COLOR_MODES.len();
+                for renderer in renderers.iter_mut() {
+                    renderer.set_color_mode(COLOR_MODES[self.color_mode_index]);
+                }
+            }
+            VirtualKeyCode::O => {
+                let index = (self.depth_complexity_index + 1) % DEPTH_COMPLEXITY_VIEWS.len();
+                self.set_depth_complexity(DEPTH_COMPLEXITY_VIEWS[index], renderers);
+            }
+            VirtualKeyCode::N => {
+                self.camera_path.keyframes.push(Keyframe::from_camera(&self.camera));
+                println!("Added camera keyframe {}", self.camera_path.keyframes.len());
+            }
+            VirtualKeyCode::G => {
+                self.dither = !self.dither;
+                let strength = if self.dither { self.dither_strength } else { 0.0 };
+                for renderer in renderers.iter_mut() {
+                    renderer.set_dither(strength);
+                }
+            }
+            VirtualKeyCode::T => self.toggle_camera_path(),
+            VirtualKeyCode::F5 => self.write_session(),
+            VirtualKeyCode::Tab => self.stream_next(),
+            VirtualKeyCode::Delete => self.scene.clear_decals(),
+            VirtualKeyCode::L => self.scene.wireframe = !self.scene.wireframe,
+            VirtualKeyCode::X => self.scene.show_axes = !self.scene.show_axes,
+            VirtualKeyCode::K => self.scene.stencil_mask = !self.scene.stencil_mask,
+            VirtualKeyCode::U => self.scene.uv_grid = !self.scene.uv_grid,
+            VirtualKeyCode::R => self.scene.affine_uvs = !self.scene.affine_uvs,
+            VirtualKeyCode::B => self.scene.show_bounds = !self.scene.show_bounds,
+            VirtualKeyCode::M => log_memory_heaps(self.device.physical_device()),
+            VirtualKeyCode::F11 => {
+                if let Some(renderer) = find_renderer(renderers, window) {
+                    renderer.toggle_fullscreen();
+                }
+            }
+            VirtualKeyCode::F12 => {
+                if let Some(renderer) = find_renderer(renderers, window) {
+                    renderer.take_screenshot(PathBuf::from(SCREENSHOT_PATH));
+                }
+            }
+            #[cfg(feature = "renderdoc")]
+            VirtualKeyCode::F9 => self.frame_capture.trigger(),
+            _ => ()
+        }
+    }
+}
+
+impl App for Demo {
+    fn update(&mut self, dt: f32, renderers: &mut [Renderer]) {
+        // Waiting here, right before the next frame gets recorded, works out the same as waiting
+        // right after the last one was presented
+        if let Some(frame_limiter) = &mut self.frame_limiter {
+            frame_limiter.wait();
+        }
+
+        if !self.paused {
+            self.animation_time += dt;
+        }
+        self.hue_time += dt;
+
+        // Before anything gets recorded, the frames in flight keep whatever they used alive
+        if let Some(streamer) = &mut self.streamer {
+            streamer.finish_loaded(&mut self.scene);
+        }
+
+        if let Some(reel) = &mut self.reel {
+            // The copy goes in before this frame's scene, so it still has the old mesh
+            if reel.advance(dt, &mut self.scene) {
+                println!("Demo reel: {}", reel.current());
+                for renderer in renderers.iter_mut() {
+                    renderer.capture_previous();
+                }
+            }
+            for renderer in renderers.iter_mut() {
+                renderer.set_crossfade(Some(reel.crossfade()));
+            }
+        }
+
+        if self.clear_color_index == CLEAR_COLORS.len() {
+            let [r, g, b] = hsv_to_rgb(self.hue_time / HUE_CYCLE_SECONDS, 0.6, 0.8);
+            self.scene.clear_color = [r, g, b, 1.0];
+        }
+
+        match (&mut self.path_time, &self.orbit) {
+            (Some(time), _) => {
+                *time += dt;
+                if let Some(keyframe) = self.camera_path.keyframe_at(*time) {
+                    keyframe.apply(&mut self.camera);
+                }
+            }
+            (None, Some(orbit)) => orbit.apply(&mut self.camera),
+            (None, None) => self.camera.fly(movement_direction(&self.held_scancodes), CAMERA_SPEED * dt),
+        }
+
+        self.scene.clear_text();
+        if self.scene.show_axes {
+            for (position, label) in AXIS_LABELS.iter() {
+                self.scene.draw_label(Point3::from(*position), label);
+            }
+        }
+        // Everything on its own line, in the top left corner
+        let mut overlay = vec!();
+        if self.show_fps {
+            if let Some(stats) = self.frame_timer.tick(Duration::from_secs_f32(dt)) {
+                // Of the last window's frame only, but they all record the same scene
+                let stats = format!(
+                    "{}, recording {:.3} ms, {} descriptor sets built",
+                    stats,
+                    self.scene.record_time().as_secs_f64() * 1000.0,
+                    self.scene.take_descriptor_sets_built(),
+                );
+                if FPS_IN_WINDOW_TITLE {
+                    for renderer in renderers.iter() {
+                        if let Some(title) = self.titles.get(&renderer.window().id()) {
+                            renderer.window().set_title(&format!("{} - {}", title, stats));
+                        }
+                    }
+                } else {
+                    self.fps_text = Some(stats);
+                }
+            }
+            if let Some(text) = &self.fps_text {
+                overlay.push(text.clone());
+            }
+        }
+        // The line the dot goes after, on for half of every second like a recording light
+        let mut path_line = None;
+        if let Some(path_time) = self.path_time {
+            overlay.push(format!("Camera path: {} keyframes", self.camera_path.keyframes.len()));
+            if path_time.fract() < 0.5 {
+                path_line = Some(overlay.len() - 1);
+            }
+        }
+        let color_mode = COLOR_MODES[self.color_mode_index];
+        if color_mode != ColorMode::Normal {
+            overlay.push(format!("Color mode: {}", color_mode.name()));
+        }
+        if let Some(streamer) = self.streamer.as_ref().filter(|streamer| streamer.pending() > 0) {
+            overlay.push(format!("Streaming: {} pending", streamer.pending()));
+        }
+        if self.dither {
+            overlay.push(format!("Dither: strength {}", self.dither_strength));
+        }
+        if let Some(reel) = &self.reel {
+            overlay.push(format!("Demo reel: {}", reel.current()));
+        }
+        // Perspective-correct is the normal one, same as the color modes
+        if self.scene.affine_uvs {
+            overlay.push("Texture mapping: affine".to_string());
+        }
+        if !overlay.is_empty() {
+            let [x, y] = OVERLAY_TEXT_POSITION;
+            let text = overlay.join("\n");
+            let [width, height] = text_size(&text);
+            self.scene.draw_shape(
+                Shape::rounded_rect(
+                    x - OVERLAY_PADDING,
+                    y - OVERLAY_PADDING,
+                    width + OVERLAY_PADDING * 2.0,
+                    height + OVERLAY_PADDING * 2.0,
+                    OVERLAY_CORNER_RADIUS,
+                )
+                    .border(OVERLAY_BORDER, 1.0)
+            );
+            if let Some(line) = path_line {
+                let line_height = height / overlay.len() as f32;
+                let radius = line_height / 4.0;
+                self.scene.draw_shape(
+                    Shape::circle(
+                        x + text_size(&overlay[line])[0] + radius * 2.0,
+                        y + (line as f32 + 0.5) * line_height,
+                        radius,
+                    )
+                        .fill(PLAYING_DOT_COLOR)
+                );
+            }
+            self.scene.draw_text(x, y, &text);
+        }
+
+        if let Some(watcher) = &mut self.shader_watcher {
+            match watcher.poll(&self.device) {
+                Some(Ok((vertex, fragment))) => match self.scene.replace_shaders(&vertex, &fragment) {
+                    Ok(()) => println!("Reloaded the shaders"),
+                    Err(e) => println!("Failed to rebuild the pipeline, keeping the old one: {}", e),
+                },
+                Some(Err(e)) => println!("Keeping the old shaders: {}", e),
+                None => (),
+            }
+        }
+    }
+
+    fn handle_event(&mut self, event: &Event<'_, ()>, renderers: &mut [Renderer], control_flow: &mut ControlFlow) {
+        match event {
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        state,
+                        scancode,
+                        virtual_keycode,
+                        ..
+                    },
+                    ..
+                },
+                ..
+            } => {
+                // Movement goes by physical key position, everything else by what's printed on the key
+                match state {
+                    ElementState::Pressed => self.held_scancodes.insert(*scancode),
+                    ElementState::Released => self.held_scancodes.remove(scancode),
+                };
+
+                let key = match virtual_keycode {
+                    Some(key) => *key,
+                    None => return,
+                };
+
+                if *state == ElementState::Released {
+                    self.held_keys.remove(&key);
+                    return;
+                }
+
+                if self.held_keys.insert(key) {
+                    self.key_pressed(key, *window_id, renderers, control_flow);
+                }
+            }
+            // Whatever gets released while another window has the focus never arrives, the camera would
+            // keep flying and the toggles wouldn't fire again
+            Event::WindowEvent { event: WindowEvent::Focused(false), .. } => {
+                self.held_scancodes.clear();
+                self.held_keys.clear();
+            }
+            // Holding the right mouse button grabs and hides the cursor and turns mouse motion into looking around
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::MouseInput { state, button: MouseButton::Right, .. },
+            } if self.orbit.is_none() => {
+                self.mouse_look = *state == ElementState::Pressed;
+
+                let window = match find_renderer(renderers, *window_id) {
+                    Some(renderer) => renderer.window(),
+                    None => return,
+                };
+                if let Err(e) = window.set_cursor_grab(self.mouse_look) {
+                    println!("Failed to grab the cursor: {}", e);
+                }
+                window.set_cursor_visible(!self.mouse_look);
+            }
+            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta: (x, y) }, .. } if self.mouse_look => {
+                self.camera.rotate(
+                    Rad(*x as f32 * MOUSE_SENSITIVITY),
+                    Rad(-*y as f32 * MOUSE_SENSITIVITY),
+                );
+            }
+            // The orbit camera goes by the cursor instead, it doesn't get grabbed
+            Event::WindowEvent { event: WindowEvent::MouseInput { state, button: MouseButton::Left, .. }, .. } => {
+                self.orbit_drag = *state == ElementState::Pressed;
+            }
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Middle, .. },
+            } => self.place_decal(*window_id),
+            Event::WindowEvent { event: WindowEvent::CursorMoved { position, .. }, .. } => {
+                if let (Some(orbit), Some(last)) = (&mut self.orbit, self.cursor_position) {
+                    if self.orbit_drag {
+                        orbit.rotate(
+                            Rad(-(position.x - last.x) as f32 * MOUSE_SENSITIVITY),
+                            Rad((position.y - last.y) as f32 * MOUSE_SENSITIVITY),
+                        );
+                    }
+                }
+                self.cursor_position = Some(*position);
+            }
+            // Either can mean the window is on another monitor now
+            Event::WindowEvent { window_id, event: WindowEvent::Moved(_) }
+            | Event::WindowEvent { window_id, event: WindowEvent::ScaleFactorChanged { .. } } if self.refresh_monitor.is_some() => {
+                if let Some(renderer) = find_renderer(renderers, *window_id) {
+                    let monitor = renderer.window().current_monitor();
+                    self.follow_refresh_rate(monitor);
+                }
+            }
+            // Quitting saves the session without asking, every way out ends up here
+            Event::LoopDestroyed if self.session_file.is_some() => self.write_session(),
+            Event::WindowEvent { event: WindowEvent::MouseWheel { delta, .. }, .. } => {
+                if let Some(orbit) = &mut self.orbit {
+                    let lines = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => *y,
+                        MouseScrollDelta::PixelDelta(position) => (position.y / PIXELS_PER_LINE) as f32,
+                    };
+                    orbit.zoom(ZOOM_PER_LINE.powf(lines));
+                }
+            }
+            _ => ()
+        }
+    }
+
+    fn record_draw(&mut self, window: WindowId, builder: AutoCommandBufferBuilder,
+                   framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>, image_index: usize,
+                   _dynamic_state: &DynamicState) -> AutoCommandBufferBuilder {
+        // The scene already has the viewport from framebuffers_recreated
+        let target = self.targets[&window];
+        self.scene.record(target, builder, framebuffer, image_index, self.animation_time, &self.camera)
+    }
+
+    fn render_pass_recreated(&mut self, window: WindowId, render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>)
+                             -> Result<(), Box<dyn Error>> {
+        self.scene.set_render_pass(self.targets[&window], render_pass)?;
+        Ok(())
+    }
+
+    fn framebuffers_recreated(&mut self, window: WindowId, image_count: usize, dynamic_state: &DynamicState,
+                              depth_image: &Arc<AttachmentImage>) {
+        let target = self.targets[&window];
+        self.scene.record_static_draws(target, image_count, dynamic_state);
+        self.scene.set_depth_image(target, depth_image);
+    }
+}
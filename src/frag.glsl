@@ -1,7 +1,21 @@
 #version 460
 
+layout(location = 1) in vec3 v_color;
+layout(location = 2) in vec3 v_normal;
 layout(location = 0) out vec4 f_color;
+// Same block as in vert.glsl
+layout(set = 0, binding = 0) uniform Data {
+    mat4 view;
+    mat4 proj;
+    vec3 light_direction;
+    float time;
+    vec3 light_color;
+    float ambient;
+} uni;
 
 void main() {
-    f_color = vec4(1.0, 0.0, 0.0, 1.0);
-}
\ No newline at end of file
+    // Interpolation shortens the normals between vertices
+    vec3 normal = normalize(v_normal);
+    float diffuse = max(dot(normal, normalize(uni.light_direction)), 0.0);
+    f_color = vec4(v_color * (uni.ambient + uni.light_color * diffuse), 1.0);
+}
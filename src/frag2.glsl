@@ -1,7 +1,18 @@
 #version 460
 
 layout(location = 0) out vec4 f_color;
+// Same block as in vert.glsl, only time is used here
+layout(set = 0, binding = 0) uniform Data {
+    mat4 view;
+    mat4 proj;
+    vec3 light_direction;
+    float time;
+    vec3 light_color;
+    float ambient;
+} uni;
 
 void main() {
-    f_color = vec4(1.0, 1.0, 0.0, 1.0);
-}
\ No newline at end of file
+    // Pulses between dark and full yellow about once a second
+    float brightness = 0.6 + 0.4 * sin(uni.time * 6.0);
+    f_color = vec4(vec3(1.0, 1.0, 0.0) * brightness, 1.0);
+}
@@ -0,0 +1,50 @@
+#version 460
+
+// Drawn once per decal as a fullscreen triangle in the subpass after the color pass. Whatever is in
+// the depth buffer gets moved back into world space and then into the decal's box, which is
+// -0.5..0.5 on every axis. Only the parts inside get the texture, projected along z
+layout(location = 0) in vec2 v_uv;
+layout(location = 0) out vec4 f_color;
+
+// With MSAA the depth buffer is multisampled too, the first sample stands in for the pixel
+#ifdef MULTISAMPLED
+layout(input_attachment_index = 0, set = 0, binding = 0) uniform subpassInputMS depth;
+#else
+layout(input_attachment_index = 0, set = 0, binding = 0) uniform subpassInput depth;
+#endif
+layout(set = 0, binding = 1) uniform sampler2D decal;
+
+layout(push_constant) uniform PushConstants {
+    mat4 inverse_view_proj;
+    mat4 world_to_decal;
+} push;
+
+// Surfaces turned further away from the decal than this don't get any of it, so it doesn't
+// smear along the sides of whatever it was put on
+const float MIN_FACING = 0.2;
+
+void main() {
+#ifdef MULTISAMPLED
+    float z = subpassLoad(depth, 0).r;
+#else
+    float z = subpassLoad(depth).r;
+#endif
+    vec4 world = push.inverse_view_proj * vec4(v_uv * 2.0 - 1.0, z, 1.0);
+    vec3 position = world.xyz / world.w;
+    vec3 local = (push.world_to_decal * vec4(position, 1.0)).xyz;
+
+    // The depth buffer has no normals, the slope of the positions between neighbouring pixels
+    // gives the face's. The decal's z axis is its normal, in the same space as local. Before any
+    // discard, the neighbours are needed for the derivatives
+    vec3 normal = normalize(cross(dFdx(local), dFdy(local)));
+    float facing = abs(normal.z);
+
+    // Nothing was drawn here, or it's outside the box
+    if (z == 1.0 || any(greaterThan(abs(local), vec3(0.5))) || facing < MIN_FACING) {
+        discard;
+    }
+
+    // The texture's top is the decal's +y
+    vec4 color = texture(decal, vec2(local.x + 0.5, 0.5 - local.y));
+    f_color = vec4(color.rgb, color.a * smoothstep(MIN_FACING, 1.0, facing));
+}
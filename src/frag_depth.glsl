@@ -0,0 +1,5 @@
+#version 460
+
+// The prepass only writes depth, there's no color attachment to write to
+void main() {
+}
@@ -0,0 +1,9 @@
+#version 460
+
+layout(location = 0) out vec4 f_color;
+
+// Blended additively with no depth test, so after the frame every pixel holds how many fragments
+// landed on it times this. Has to match LAYER_STEP in post_depth_complexity.glsl
+void main() {
+    f_color = vec4(vec3(1.0 / 32.0), 1.0);
+}
@@ -0,0 +1,29 @@
+#version 460
+
+// See shapes.rs. local is in pixels from the center, so the distances below are in pixels too
+layout(location = 0) in vec2 v_local;
+layout(location = 1) flat in vec2 v_half_size;
+layout(location = 2) flat in float v_corner_radius;
+layout(location = 3) flat in float v_border_width;
+layout(location = 4) flat in vec4 v_fill;
+layout(location = 5) flat in vec4 v_border;
+
+layout(location = 0) out vec4 f_color;
+
+// Negative inside the rectangle with rounded corners, positive outside
+float rounded_rect_distance(vec2 p, vec2 half_size, float radius) {
+    vec2 q = abs(p) - half_size + radius;
+    return length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+void main() {
+    float distance = rounded_rect_distance(v_local, v_half_size, v_corner_radius);
+    // How much the distance changes over one pixel, 1 unless something scales the viewport. Half a
+    // pixel on either side of the edge blends from one color to the other
+    float width = fwidth(distance) * 0.5;
+    float coverage = 1.0 - smoothstep(-width, width, distance);
+    float in_border = v_border_width > 0.0 ? smoothstep(-width, width, distance + v_border_width) : 0.0;
+
+    vec4 color = mix(v_fill, v_border, in_border);
+    f_color = vec4(color.rgb, color.a * coverage);
+}
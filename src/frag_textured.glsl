@@ -0,0 +1,14 @@
+#version 460
+
+// See vert.glsl
+#ifdef AFFINE
+layout(location = 0) noperspective in vec2 v_uv;
+#else
+layout(location = 0) in vec2 v_uv;
+#endif
+layout(location = 0) out vec4 f_color;
+layout(set = 0, binding = 1) uniform sampler2D tex;
+
+void main() {
+    f_color = texture(tex, v_uv);
+}
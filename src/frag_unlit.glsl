@@ -0,0 +1,8 @@
+#version 460
+
+layout(location = 1) in vec3 v_color;
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    f_color = vec4(v_color, 1.0);
+}
@@ -0,0 +1,24 @@
+#version 460
+
+// Checkers over the 0..1 UV range with lines on the cell borders, tinted by the UVs themselves so
+// u (red) and v (green) show which way they run. Stretched or sheared checkers mean stretched textures
+#ifdef AFFINE
+layout(location = 0) noperspective in vec2 v_uv;
+#else
+layout(location = 0) in vec2 v_uv;
+#endif
+layout(location = 0) out vec4 f_color;
+
+const float CELLS = 8.0;
+// In pixels, fwidth keeps them that wide no matter how big the cells end up on screen
+const float LINE_WIDTH = 1.0;
+
+void main() {
+    vec2 cell = floor(v_uv * CELLS);
+    float checker = mod(cell.x + cell.y, 2.0);
+    vec3 color = vec3(fract(v_uv), 0.25) * mix(0.5, 1.0, checker);
+
+    vec2 distance = abs(fract(v_uv * CELLS - 0.5) - 0.5) / fwidth(v_uv * CELLS);
+    float line = 1.0 - min(min(distance.x, distance.y) / LINE_WIDTH, 1.0);
+    f_color = vec4(mix(color, vec3(1.0), line), 1.0);
+}
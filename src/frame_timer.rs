@@ -0,0 +1,139 @@
+use winit::monitor::MonitorHandle;
+
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// thread::sleep tends to oversleep by a millisecond or so, the last bit of every frame is spent
+// spinning instead
+const SPIN_TIME: Duration = Duration::from_millis(2);
+
+// Collects frame times and hands out a summary once per second
+pub struct FrameTimer {
+    window_start: Instant,
+    frames: u32,
+    min_frame_time: Duration,
+    max_frame_time: Duration,
+}
+
+pub struct FrameStats {
+    pub fps: f32,
+    pub min_frame_time: Duration,
+    pub max_frame_time: Duration,
+}
+
+impl FrameTimer {
+    pub fn new() -> FrameTimer {
+        FrameTimer {
+            window_start: Instant::now(),
+            frames: 0,
+            min_frame_time: Duration::from_secs(u64::MAX),
+            max_frame_time: Duration::from_secs(0),
+        }
+    }
+
+    pub fn tick(&mut self, frame_time: Duration) -> Option<FrameStats> {
+        self.frames += 1;
+        self.min_frame_time = self.min_frame_time.min(frame_time);
+        self.max_frame_time = self.max_frame_time.max(frame_time);
+
+        let elapsed = self.window_start.elapsed();
+        if elapsed < Duration::from_secs(1) {
+            return None;
+        }
+
+        let stats = FrameStats {
+            fps: self.frames as f32 / elapsed.as_secs_f32(),
+            min_frame_time: self.min_frame_time,
+            max_frame_time: self.max_frame_time,
+        };
+        *self = FrameTimer::new();
+
+        Some(stats)
+    }
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} fps (min {:.2} ms, max {:.2} ms)",
+            self.fps,
+            self.min_frame_time.as_secs_f64() * 1000.0,
+            self.max_frame_time.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+// Keeps the frame rate at or below a target by sleeping off whatever is left of each frame
+pub struct FrameLimiter {
+    frame_time: Duration,
+    next_frame: Instant,
+}
+
+impl FrameLimiter {
+    pub fn new(target_fps: u32) -> FrameLimiter {
+        FrameLimiter {
+            frame_time: Duration::from_secs(1) / target_fps,
+            next_frame: Instant::now(),
+        }
+    }
+
+    // Takes effect from the next frame on
+    pub fn set_target_fps(&mut self, target_fps: u32) {
+        self.frame_time = Duration::from_secs(1) / target_fps;
+    }
+
+    pub fn wait(&mut self) {
+        self.next_frame += self.frame_time;
+
+        let now = Instant::now();
+        if self.next_frame <= now {
+            // Running behind, don't try to catch up with a burst of short frames
+            self.next_frame = now;
+            return;
+        }
+
+        let remaining = self.next_frame - now;
+        if remaining > SPIN_TIME {
+            thread::sleep(remaining - SPIN_TIME);
+        }
+
+        while Instant::now() < self.next_frame {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+// winit doesn't say which video mode a monitor is in right now. The fastest one at the monitor's
+// current size is what it's most likely running, None if it doesn't list any (e.g. on Wayland)
+pub fn refresh_rate(monitor: &MonitorHandle) -> Option<u32> {
+    let size = monitor.size();
+    fastest_refresh_rate(
+        monitor.video_modes().map(|mode| ([mode.size().width, mode.size().height], mode.refresh_rate())),
+        [size.width, size.height],
+    )
+}
+
+fn fastest_refresh_rate(modes: impl Iterator<Item = ([u32; 2], u16)>, size: [u32; 2]) -> Option<u32> {
+    modes.filter(|(mode_size, refresh_rate)| *mode_size == size && *refresh_rate > 0)
+        .map(|(_, refresh_rate)| refresh_rate as u32)
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fastest_refresh_rate_at_the_current_size() {
+        let modes = vec!(([1920, 1080], 60), ([1920, 1080], 144), ([1280, 720], 240), ([1920, 1080], 120));
+        assert_eq!(fastest_refresh_rate(modes.into_iter(), [1920, 1080]), Some(144));
+    }
+
+    #[test]
+    fn no_refresh_rate_without_a_matching_mode() {
+        let modes = vec!(([1280, 720], 60), ([1920, 1080], 0));
+        assert_eq!(fastest_refresh_rate(modes.into_iter(), [1920, 1080]), None);
+    }
+}
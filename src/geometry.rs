@@ -0,0 +1,157 @@
+use cgmath::{InnerSpace, Vector3};
+
+use std::f32::consts::PI;
+
+use crate::mesh::Vertex;
+
+// Built-in shapes for testing without any asset files. All of them are centered on the origin and
+// one unit across, with triangles going counter-clockwise when seen from the outside. UVs have v
+// going down, the same way image rows do, so textures come out upright
+const SHAPE_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+// In the XY plane, facing +z
+pub fn make_quad() -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = vec!();
+    let mut indices = vec!();
+    push_face(&mut vertices, &mut indices, Vector3::new(0.0, 0.0, 0.0), Vector3::unit_x(), Vector3::unit_y());
+    (vertices, indices)
+}
+
+// Every face has vertices of its own so the normals stay flat
+pub fn make_cube() -> (Vec<Vertex>, Vec<u32>) {
+    let x = Vector3::unit_x();
+    let y = Vector3::unit_y();
+    let z = Vector3::unit_z();
+
+    // Right and up as seen from outside each face
+    let faces = [
+        (x, -z, y),
+        (-x, z, y),
+        (y, x, -z),
+        (-y, x, z),
+        (z, x, y),
+        (-z, -x, y),
+    ];
+
+    let mut vertices = vec!();
+    let mut indices = vec!();
+    for &(normal, right, up) in faces.iter() {
+        push_face(&mut vertices, &mut indices, normal * 0.5, right, up);
+    }
+    (vertices, indices)
+}
+
+// segments go around the equator and rings from pole to pole. There's a seam at +z where the first
+// and last column of vertices share positions but not UVs
+pub fn make_uv_sphere(segments: u32, rings: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let segments = segments.max(3);
+    let rings = rings.max(2);
+
+    let mut vertices = vec!();
+    for ring in 0..=rings {
+        let theta = ring as f32 / rings as f32 * PI;
+        for segment in 0..=segments {
+            let phi = segment as f32 / segments as f32 * 2.0 * PI;
+            let normal = [theta.sin() * phi.sin(), theta.cos(), theta.sin() * phi.cos()];
+
+            vertices.push(Vertex {
+                position: [normal[0] * 0.5, normal[1] * 0.5, normal[2] * 0.5],
+                normal,
+                uv: [segment as f32 / segments as f32, ring as f32 / rings as f32],
+                color: SHAPE_COLOR,
+            });
+        }
+    }
+
+    let columns = segments + 1;
+    let mut indices = vec!();
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let top_left = ring * columns + segment;
+            let bottom_left = top_left + columns;
+
+            // The triangle touching a pole would have two corners on it
+            if ring != rings - 1 {
+                indices.extend_from_slice(&[top_left, bottom_left, bottom_left + 1]);
+            }
+            if ring != 0 {
+                indices.extend_from_slice(&[bottom_left + 1, top_left + 1, top_left]);
+            }
+        }
+    }
+
+    (vertices, indices)
+}
+
+// A unit square around center, spanned by right and up
+fn push_face(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, center: Vector3<f32>, right: Vector3<f32>,
+             up: Vector3<f32>) {
+    let normal = right.cross(up).normalize();
+    let first = vertices.len() as u32;
+
+    // Top left, bottom left, bottom right, top right
+    let corners = [(-0.5, 0.5, [0.0, 0.0]), (-0.5, -0.5, [0.0, 1.0]), (0.5, -0.5, [1.0, 1.0]), (0.5, 0.5, [1.0, 0.0])];
+    for &(x, y, uv) in corners.iter() {
+        vertices.push(Vertex {
+            position: (center + right * x + up * y).into(),
+            normal: normal.into(),
+            uv,
+            color: SHAPE_COLOR,
+        });
+    }
+
+    indices.extend_from_slice(&[first, first + 1, first + 2, first + 2, first + 3, first]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Unit normals, UVs inside the texture, indices that point at vertices and triangles that go
+    // counter-clockwise around their normals
+    fn check_shape(vertices: &[Vertex], indices: &[u32]) {
+        for vertex in vertices {
+            assert!((Vector3::from(vertex.normal).magnitude() - 1.0).abs() < 1e-5);
+            assert!(vertex.uv.iter().all(|uv| (0.0..=1.0).contains(uv)));
+        }
+        assert!(indices.len().is_multiple_of(3));
+        assert!(indices.iter().all(|&index| (index as usize) < vertices.len()));
+        for triangle in indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(vertices[triangle[i] as usize].position));
+            let normal = Vector3::from(vertices[triangle[0] as usize].normal);
+            assert!((b - a).cross(c - a).dot(normal) > 0.0);
+        }
+    }
+
+    #[test]
+    fn quad_faces_z() {
+        let (vertices, indices) = make_quad();
+        check_shape(&vertices, &indices);
+        assert_eq!((vertices.len(), indices.len()), (4, 6));
+        assert!(vertices.iter().all(|vertex| vertex.normal == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn cube_normals_point_out() {
+        let (vertices, indices) = make_cube();
+        check_shape(&vertices, &indices);
+        assert_eq!((vertices.len(), indices.len()), (24, 36));
+        for vertex in &vertices {
+            // The center of each face is half a unit out along its normal
+            assert!(Vector3::from(vertex.position).dot(Vector3::from(vertex.normal)) > 0.49);
+        }
+    }
+
+    #[test]
+    fn sphere_normals_point_out() {
+        let (vertices, indices) = make_uv_sphere(32, 16);
+        check_shape(&vertices, &indices);
+        // One triangle per segment in the rings at the poles, two everywhere else
+        assert_eq!((vertices.len(), indices.len()), (33 * 17, 32 * (16 - 1) * 2 * 3));
+        for vertex in &vertices {
+            let position = Vector3::from(vertex.position);
+            assert!((position.magnitude() - 0.5).abs() < 1e-5);
+            assert!((position * 2.0 - Vector3::from(vertex.normal)).magnitude() < 1e-5);
+        }
+    }
+}
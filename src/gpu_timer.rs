@@ -0,0 +1,300 @@
+use vulkano::VulkanObject;
+use vulkano::buffer::{BufferAccess, BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{CommandBuffer, CommandBufferExecError};
+use vulkano::command_buffer::pool::standard::{StandardCommandPoolAlloc, StandardCommandPoolBuilder};
+use vulkano::command_buffer::sys::{Flags, Kind, UnsafeCommandBuffer, UnsafeCommandBufferBuilder, UnsafeCommandBufferBuilderPipelineBarrier};
+use vulkano::device::{Device, DeviceOwned, Queue};
+use vulkano::image::{ImageAccess, ImageLayout};
+use vulkano::query::{QueryType, UnsafeQueryPool};
+use vulkano::sync::{AccessCheckError, AccessFlagBits, GpuFuture, PipelineStages};
+
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::vulkan::loader::instance_function;
+
+// Printed once per interval, the same as the fps counter
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+// VK_QUERY_RESULT_64_BIT | VK_QUERY_RESULT_WAIT_BIT
+const QUERY_RESULT_FLAGS: u32 = 0x1 | 0x2;
+
+// Command buffer, query pool, first query, query count, buffer, offset, stride, flags
+type CmdCopyQueryPoolResults = extern "system" fn(usize, u64, u32, u32, u64, u64, u64, u32);
+
+// vulkano 0.18 has no safe way to use queries, so the timestamps go into tiny command buffers of
+// their own that are submitted around the frame's one, in the same batch. Since they're written at
+// the top and bottom of the pipe that still measures everything in between.
+//
+// Query results only get written back by a command buffer (vkGetQueryPoolResults isn't exposed),
+// and only once the queries are available, so each swapchain image's pair is copied into that
+// image's buffer at the start of the next frame for it and read after that frame's fence. vulkano's
+// own copy_query_pool_results can only copy 32 bits, which wraps every few seconds on some GPUs,
+// so the copy is a raw vkCmdCopyQueryPoolResults with the 64 bit flag
+pub struct GpuTimer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    pool: Arc<UnsafeQueryPool>,
+    copy_query_pool_results: CmdCopyQueryPoolResults,
+    // One per swapchain image, the GPU locks the whole buffer while a copy into it is in flight
+    results: Vec<Arc<CpuAccessibleBuffer<[u64]>>>,
+    slots: Vec<Slot>,
+    // Nanoseconds per tick
+    timestamp_period: f32,
+    report_start: Instant,
+    frame_times: Vec<f32>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Slot {
+    // A frame wrote both timestamps, so they can be copied
+    written: bool,
+    // The last frame copied the previous one's timestamps into results
+    copied: bool,
+}
+
+impl GpuTimer {
+    // None if the queue can't do timestamps
+    pub fn new(queue: &Arc<Queue>, image_count: usize) -> Option<GpuTimer> {
+        queue.family().timestamp_valid_bits()?;
+        let device = queue.device().clone();
+
+        let function = instance_function(device.instance(), "vkCmdCopyQueryPoolResults")?;
+        // Core since 1.0, the signature is the one from the spec
+        let copy_query_pool_results = unsafe {
+            mem::transmute::<extern "system" fn(), CmdCopyQueryPoolResults>(function)
+        };
+
+        // queries_range wants one slot more than it should
+        let pool = UnsafeQueryPool::new(device.clone(), QueryType::Timestamp, image_count as u32 * 2 + 1)
+            .unwrap();
+
+        let results = (0..image_count)
+            .map(|_| CpuAccessibleBuffer::from_iter(
+                device.clone(),
+                BufferUsage { transfer_destination: true, ..BufferUsage::none() },
+                false,
+                vec!(0u64; 2).into_iter(),
+            )
+                .unwrap())
+            .collect();
+
+        Some(GpuTimer {
+            timestamp_period: device.physical_device().limits().timestamp_period(),
+            device,
+            queue: queue.clone(),
+            pool: Arc::new(pool),
+            copy_query_pool_results,
+            results,
+            slots: vec!(Slot::default(); image_count),
+            report_start: Instant::now(),
+            frame_times: vec!(),
+        })
+    }
+
+    // For the start of the frame rendering into image_index. The caller has to have waited for the
+    // last frame that used it
+    pub fn start_commands(&mut self, image_index: usize) -> QueryCommands {
+        let first = image_index as u32 * 2;
+        let written = self.slots[image_index].written;
+
+        let results = self.results[image_index].clone();
+        let copy_query_pool_results = self.copy_query_pool_results;
+        let copied = if written { Some(results.clone()) } else { None };
+
+        let commands = self.commands(copied, |builder, pool| unsafe {
+            if written {
+                let destination = results.inner();
+                copy_query_pool_results(
+                    builder.internal_object(),
+                    pool.internal_object(),
+                    first,
+                    2,
+                    destination.buffer.internal_object(),
+                    destination.offset as u64,
+                    mem::size_of::<u64>() as u64,
+                    QUERY_RESULT_FLAGS,
+                );
+
+                // The fence alone doesn't make the copy visible to the CPU
+                let mut barrier = UnsafeCommandBufferBuilderPipelineBarrier::new();
+                barrier.add_memory_barrier(
+                    PipelineStages { transfer: true, ..PipelineStages::none() },
+                    AccessFlagBits { transfer_write: true, ..AccessFlagBits::none() },
+                    PipelineStages { host: true, ..PipelineStages::none() },
+                    AccessFlagBits { host_read: true, ..AccessFlagBits::none() },
+                    false,
+                );
+                builder.pipeline_barrier(&barrier);
+            }
+            builder.reset_query_pool(pool.queries_range(first, 2).unwrap());
+            builder.write_timestamp(
+                pool.query(first).unwrap(),
+                PipelineStages { top_of_pipe: true, ..PipelineStages::none() },
+            );
+        });
+
+        self.slots[image_index] = Slot { written: true, copied: written };
+        commands
+    }
+
+    pub fn end_commands(&mut self, image_index: usize) -> QueryCommands {
+        let second = image_index as u32 * 2 + 1;
+        self.commands(None, |builder, pool| unsafe {
+            builder.write_timestamp(
+                pool.query(second).unwrap(),
+                PipelineStages { bottom_of_pipe: true, ..PipelineStages::none() },
+            );
+        })
+    }
+
+    // If the frame never got submitted its timestamps never get written either
+    pub fn frame_dropped(&mut self, image_index: usize) {
+        self.slots[image_index] = Slot::default();
+    }
+
+    // Once the last frame for image_index has finished. Returns the average, min and max frame
+    // time in milliseconds once per REPORT_INTERVAL
+    pub fn frame_finished(&mut self, image_index: usize) -> Option<GpuFrameStats> {
+        if self.slots[image_index].copied {
+            // The frame's fence has been waited on, which releases the GPU lock on the buffer
+            match self.results[image_index].read() {
+                Ok(results) => {
+                    let ticks = results[1].saturating_sub(results[0]);
+                    self.frame_times.push(ticks as f32 * self.timestamp_period / 1_000_000.0);
+                }
+                Err(e) => println!("Failed to read the GPU timestamps: {}", e),
+            }
+        }
+
+        if self.report_start.elapsed() < REPORT_INTERVAL || self.frame_times.is_empty() {
+            return None;
+        }
+
+        let stats = GpuFrameStats {
+            average: self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32,
+            min: self.frame_times.iter().cloned().fold(f32::MAX, f32::min),
+            max: self.frame_times.iter().cloned().fold(0.0, f32::max),
+        };
+        self.frame_times.clear();
+        self.report_start = Instant::now();
+        Some(stats)
+    }
+
+    fn commands<F>(&self, results: Option<Arc<CpuAccessibleBuffer<[u64]>>>, record: F) -> QueryCommands
+        where F: FnOnce(&mut UnsafeCommandBufferBuilder<StandardCommandPoolBuilder>, &UnsafeQueryPool)
+    {
+        let command_pool = Device::standard_command_pool(&self.device, self.queue.family());
+        let mut builder = unsafe {
+            UnsafeCommandBufferBuilder::new(&command_pool, Kind::primary(), Flags::OneTimeSubmit)
+                .unwrap()
+        };
+        record(&mut builder, &self.pool);
+
+        QueryCommands {
+            inner: builder.build().unwrap(),
+            _pool: self.pool.clone(),
+            results,
+            submitted: AtomicBool::new(false),
+        }
+    }
+}
+
+pub struct GpuFrameStats {
+    pub average: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+// Submittable like any other command buffer. vulkano doesn't know what's in it, so the buffer the
+// timestamps get copied into is locked by hand like AutoCommandBuffer does for its resources. The
+// query pool isn't something vulkano tracks at all
+pub struct QueryCommands {
+    inner: UnsafeCommandBuffer<StandardCommandPoolAlloc>,
+    _pool: Arc<UnsafeQueryPool>,
+    // Written by the copy, if there is one
+    results: Option<Arc<CpuAccessibleBuffer<[u64]>>>,
+    // Built with OneTimeSubmit
+    submitted: AtomicBool,
+}
+
+unsafe impl DeviceOwned for QueryCommands {
+    fn device(&self) -> &Arc<Device> {
+        self.inner.device()
+    }
+}
+
+// SAFETY: inner is only ever recorded by GpuTimer, and the only resources it uses are the query
+// pool and the results buffer, both kept alive by the fields. The buffer is locked and checked
+// below. The pool can't be: each image's pair of queries is only touched by the frames rendering
+// into that image, and the renderer waits for the last one's fence before building the next one's
+// commands, so no two submissions use the same queries at once
+unsafe impl CommandBuffer for QueryCommands {
+    type PoolAlloc = StandardCommandPoolAlloc;
+
+    fn inner(&self) -> &UnsafeCommandBuffer<StandardCommandPoolAlloc> {
+        &self.inner
+    }
+
+    fn lock_submit(&self, future: &dyn GpuFuture, queue: &Queue) -> Result<(), CommandBufferExecError> {
+        if self.submitted.swap(true, Ordering::SeqCst) {
+            return Err(CommandBufferExecError::OneTimeSubmitAlreadySubmitted);
+        }
+
+        let results = match &self.results {
+            Some(results) => results,
+            None => return Ok(()),
+        };
+
+        // Same as vulkano's own command buffers: if the previous future already has the buffer
+        // locked for us, add to its lock, otherwise take one
+        let locked = match future.check_buffer_access(results, true, queue) {
+            Ok(_) => {
+                // SAFETY: the future just said it holds an exclusive lock on the buffer
+                unsafe { results.increase_gpu_lock(); }
+                Ok(())
+            }
+            Err(AccessCheckError::Unknown) => results.try_gpu_lock(true, queue),
+            Err(AccessCheckError::Denied(error)) => Err(error),
+        };
+
+        locked.map_err(|error| {
+            // The next submission isn't going to unlock this one
+            self.submitted.store(false, Ordering::SeqCst);
+            CommandBufferExecError::AccessError {
+                error,
+                command_name: "vkCmdCopyQueryPoolResults".into(),
+                command_param: "dstBuffer".into(),
+                command_offset: 0,
+            }
+        })
+    }
+
+    // SAFETY: vulkano only calls this once the submission has finished, after a successful
+    // lock_submit, which is the only thing that locks the buffer
+    unsafe fn unlock(&self) {
+        if let Some(results) = &self.results {
+            results.unlock();
+        }
+    }
+
+    fn check_buffer_access(&self, buffer: &dyn BufferAccess, _exclusive: bool, _queue: &Queue)
+                           -> Result<Option<(PipelineStages, AccessFlagBits)>, AccessCheckError> {
+        match &self.results {
+            // Exclusively locked by lock_submit, so any access after this is fine
+            Some(results) if results.conflicts_buffer(buffer) => Ok(Some((
+                PipelineStages { transfer: true, ..PipelineStages::none() },
+                AccessFlagBits { transfer_write: true, ..AccessFlagBits::none() },
+            ))),
+            _ => Err(AccessCheckError::Unknown),
+        }
+    }
+
+    // No images in here
+    fn check_image_access(&self, _image: &dyn ImageAccess, _layout: ImageLayout, _exclusive: bool, _queue: &Queue)
+                          -> Result<Option<(PipelineStages, AccessFlagBits)>, AccessCheckError> {
+        Err(AccessCheckError::Unknown)
+    }
+}
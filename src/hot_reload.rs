@@ -0,0 +1,188 @@
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use shaderc::{Compiler, ShaderKind};
+use vulkano::OomError;
+use vulkano::device::Device;
+use vulkano::pipeline::shader::ShaderModule;
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+use crate::vulkan::shader::{ShaderLoadError, ShaderRequirements};
+
+// Vertex and fragment shader
+pub type ShaderModules = (Arc<ShaderModule>, Arc<ShaderModule>);
+
+// Editors tend to save in a few steps (truncate, write, rename), the events for them get collected
+// for this long and come out as one
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub enum ShaderReloadError {
+    NoCompiler,
+    Watch(notify::Error),
+    Io(io::Error),
+    Compile(shaderc::Error),
+    // Compiled fine but uses something the device doesn't have enabled
+    Requirements(ShaderLoadError),
+    ModuleCreation(OomError),
+}
+
+impl fmt::Display for ShaderReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderReloadError::NoCompiler => write!(f, "failed to initialize the shader compiler"),
+            ShaderReloadError::Watch(e) => write!(f, "failed to watch the shaders: {}", e),
+            ShaderReloadError::Io(e) => write!(f, "failed to read the shader: {}", e),
+            ShaderReloadError::Compile(e) => write!(f, "failed to compile the shader: {}", e),
+            ShaderReloadError::Requirements(e) => write!(f, "{}", e),
+            ShaderReloadError::ModuleCreation(e) => write!(f, "failed to create the shader module: {}", e),
+        }
+    }
+}
+
+impl Error for ShaderReloadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ShaderReloadError::NoCompiler => None,
+            ShaderReloadError::Watch(e) => Some(e),
+            ShaderReloadError::Io(e) => Some(e),
+            ShaderReloadError::Compile(e) => Some(e),
+            ShaderReloadError::Requirements(e) => Some(e),
+            ShaderReloadError::ModuleCreation(e) => Some(e),
+        }
+    }
+}
+
+impl From<notify::Error> for ShaderReloadError {
+    fn from(e: notify::Error) -> ShaderReloadError {
+        ShaderReloadError::Watch(e)
+    }
+}
+
+impl From<io::Error> for ShaderReloadError {
+    fn from(e: io::Error) -> ShaderReloadError {
+        ShaderReloadError::Io(e)
+    }
+}
+
+impl From<shaderc::Error> for ShaderReloadError {
+    fn from(e: shaderc::Error) -> ShaderReloadError {
+        ShaderReloadError::Compile(e)
+    }
+}
+
+impl From<ShaderLoadError> for ShaderReloadError {
+    fn from(e: ShaderLoadError) -> ShaderReloadError {
+        ShaderReloadError::Requirements(e)
+    }
+}
+
+impl From<OomError> for ShaderReloadError {
+    fn from(e: OomError) -> ShaderReloadError {
+        ShaderReloadError::ModuleCreation(e)
+    }
+}
+
+struct WatchedFile {
+    path: PathBuf,
+    kind: ShaderKind,
+}
+
+impl WatchedFile {
+    // The watcher reports paths the way the platform sees them, which isn't always how they were
+    // given to it (symlinks, /private on macOS)
+    fn is(&self, path: &Path) -> bool {
+        path == self.path || match (fs::canonicalize(path), fs::canonicalize(&self.path)) {
+            (Ok(path), Ok(own_path)) => path == own_path,
+            _ => false,
+        }
+    }
+}
+
+// Watches the GLSL sources of a vertex and a fragment shader. Their directories are watched
+// instead of the files themselves, an editor that saves by renaming a new file over the old one
+// would take the watch with it otherwise
+pub struct ShaderWatcher {
+    compiler: Compiler,
+    vertex: WatchedFile,
+    fragment: WatchedFile,
+    // Stops watching once it's dropped
+    _watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+}
+
+impl ShaderWatcher {
+    pub fn new(vertex_path: PathBuf, fragment_path: PathBuf) -> Result<ShaderWatcher, ShaderReloadError> {
+        let compiler = Compiler::new()
+            .ok_or(ShaderReloadError::NoCompiler)?;
+
+        let (sender, events) = channel();
+        let mut watcher: RecommendedWatcher = Watcher::new(sender, DEBOUNCE)?;
+        for path in &[&vertex_path, &fragment_path] {
+            let directory = path.parent()
+                .unwrap_or_else(|| Path::new("."));
+            watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        }
+
+        Ok(ShaderWatcher {
+            compiler,
+            vertex: WatchedFile { path: vertex_path, kind: ShaderKind::Vertex },
+            fragment: WatchedFile { path: fragment_path, kind: ShaderKind::Fragment },
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    // Some once either file changed. Both get compiled so the pipeline can be rebuilt in one go,
+    // an error in either is returned instead and the next save tries again
+    pub fn poll(&mut self, device: &Arc<Device>) -> Option<Result<ShaderModules, ShaderReloadError>> {
+        let mut changed = false;
+        // Empty, or the watcher thread is gone which only happens when it's dropped
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                DebouncedEvent::Create(path) | DebouncedEvent::Write(path) | DebouncedEvent::Rename(_, path) => {
+                    changed |= self.vertex.is(&path) || self.fragment.is(&path);
+                }
+                DebouncedEvent::Error(e, _) => return Some(Err(e.into())),
+                _ => (),
+            }
+        }
+        if !changed {
+            return None;
+        }
+
+        let vertex = match compile(&mut self.compiler, device, &self.vertex) {
+            Ok(module) => module,
+            Err(e) => return Some(Err(e)),
+        };
+        let fragment = match compile(&mut self.compiler, device, &self.fragment) {
+            Ok(module) => module,
+            Err(e) => return Some(Err(e)),
+        };
+
+        Some(Ok((vertex, fragment)))
+    }
+}
+
+fn compile(compiler: &mut Compiler, device: &Arc<Device>, file: &WatchedFile) -> Result<Arc<ShaderModule>, ShaderReloadError> {
+    let source = fs::read_to_string(&file.path)?;
+    let file_name = file.path.display().to_string();
+
+    let artifact = compiler.compile_into_spirv(&source, file.kind, &file_name, "main", None)?;
+    if artifact.get_num_warnings() > 0 {
+        println!("{}", artifact.get_warning_messages());
+    }
+
+    ShaderRequirements::from_spirv(artifact.as_binary())
+        .check(device, &file_name)?;
+
+    // shaderc only hands out valid SPIR-V
+    let module = unsafe { ShaderModule::new(device.clone(), artifact.as_binary_u8())? };
+    Ok(module)
+}
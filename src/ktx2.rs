@@ -0,0 +1,186 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+// Identifier, the 9 header fields and the offsets of the data format descriptor, the key/value
+// data and the supercompression data
+const HEADER_LEN: usize = 80;
+// Byte offset, byte length and uncompressed byte length, all u64
+const LEVEL_INDEX_ENTRY_LEN: usize = 24;
+
+// Only what's needed to upload a plain 2D texture. The data format descriptor and the key/value
+// pairs get skipped, vkFormat already says everything about the layout
+pub struct Ktx2 {
+    // A VkFormat value, 0 for formats Vulkan doesn't know
+    pub vk_format: u32,
+    pub width: u32,
+    pub height: u32,
+    // The full size image first, each one half the size of the one before
+    pub levels: Vec<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub enum Ktx2Error {
+    Io(io::Error),
+    NotKtx2,
+    Truncated,
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for Ktx2Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ktx2Error::Io(e) => write!(f, "failed to read the file: {}", e),
+            Ktx2Error::NotKtx2 => write!(f, "not a KTX2 file"),
+            Ktx2Error::Truncated => write!(f, "the file ends before its data does"),
+            Ktx2Error::Unsupported(what) => write!(f, "{} is not supported", what),
+        }
+    }
+}
+
+impl Error for Ktx2Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Ktx2Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Ktx2Error {
+    fn from(e: io::Error) -> Ktx2Error {
+        Ktx2Error::Io(e)
+    }
+}
+
+pub fn load_ktx2(path: &Path) -> Result<Ktx2, Ktx2Error> {
+    parse_ktx2(&fs::read(path)?)
+}
+
+// Everything in KTX2 is little endian
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Ktx2Error> {
+    bytes.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(Ktx2Error::Truncated)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, Ktx2Error> {
+    let low = read_u32(bytes, offset)? as u64;
+    let high = read_u32(bytes, offset + 4)? as u64;
+    Ok(low | high << 32)
+}
+
+pub fn parse_ktx2(bytes: &[u8]) -> Result<Ktx2, Ktx2Error> {
+    if bytes.len() < IDENTIFIER.len() || bytes[..IDENTIFIER.len()] != IDENTIFIER {
+        return Err(Ktx2Error::NotKtx2);
+    }
+    if bytes.len() < HEADER_LEN {
+        return Err(Ktx2Error::Truncated);
+    }
+
+    let vk_format = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?;
+    let depth = read_u32(bytes, 28)?;
+    let layer_count = read_u32(bytes, 32)?;
+    let face_count = read_u32(bytes, 36)?;
+    // 0 asks the loader to generate the mipmaps, we just take the one level that's there
+    let level_count = read_u32(bytes, 40)?.max(1);
+    let supercompression = read_u32(bytes, 44)?;
+
+    if supercompression != 0 {
+        return Err(Ktx2Error::Unsupported("supercompression (Basis Universal or zstd)"));
+    }
+    if depth > 1 {
+        return Err(Ktx2Error::Unsupported("a 3D texture"));
+    }
+    if layer_count > 1 {
+        return Err(Ktx2Error::Unsupported("an array texture"));
+    }
+    if face_count > 1 {
+        return Err(Ktx2Error::Unsupported("a cube map"));
+    }
+    if width == 0 {
+        return Err(Ktx2Error::NotKtx2);
+    }
+    if height == 0 {
+        return Err(Ktx2Error::Unsupported("a 1D texture"));
+    }
+
+    let levels = (0..level_count as usize)
+        .map(|level| {
+            let entry = HEADER_LEN + level * LEVEL_INDEX_ENTRY_LEN;
+            let offset = read_u64(bytes, entry)? as usize;
+            let length = read_u64(bytes, entry + 8)? as usize;
+            bytes.get(offset..offset.checked_add(length).ok_or(Ktx2Error::Truncated)?)
+                .map(|level| level.to_vec())
+                .ok_or(Ktx2Error::Truncated)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Ktx2 { vk_format, width, height, levels })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A header for a 2D texture, followed by the level index and the levels in the order given
+    fn ktx2_bytes(vk_format: u32, width: u32, height: u32, levels: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = IDENTIFIER.to_vec();
+        for field in &[vk_format, 1, width, height, 0, 0, 1, levels.len() as u32, 0] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        bytes.resize(HEADER_LEN, 0);
+
+        let mut offset = HEADER_LEN + levels.len() * LEVEL_INDEX_ENTRY_LEN;
+        for level in levels {
+            bytes.extend_from_slice(&(offset as u64).to_le_bytes());
+            bytes.extend_from_slice(&(level.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(&(level.len() as u64).to_le_bytes());
+            offset += level.len();
+        }
+        for level in levels {
+            bytes.extend_from_slice(level);
+        }
+        bytes
+    }
+
+    #[test]
+    fn reads_the_header_and_levels() {
+        let levels = vec!(vec!(1; 64), vec!(2; 16), vec!(3; 16));
+        let ktx2 = parse_ktx2(&ktx2_bytes(145, 8, 8, &levels))
+            .unwrap();
+
+        assert_eq!(ktx2.vk_format, 145);
+        assert_eq!((ktx2.width, ktx2.height), (8, 8));
+        assert_eq!(ktx2.levels, levels);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(matches!(parse_ktx2(b"\x89PNG\r\n\x1a\n"), Err(Ktx2Error::NotKtx2)));
+        assert!(matches!(parse_ktx2(&IDENTIFIER), Err(Ktx2Error::Truncated)));
+    }
+
+    #[test]
+    fn rejects_truncated_levels() {
+        let mut bytes = ktx2_bytes(37, 2, 2, &[vec!(0; 16)]);
+        bytes.truncate(bytes.len() - 1);
+        assert!(matches!(parse_ktx2(&bytes), Err(Ktx2Error::Truncated)));
+    }
+
+    #[test]
+    fn rejects_supercompression_and_cube_maps() {
+        let mut supercompressed = ktx2_bytes(37, 2, 2, &[vec!(0; 16)]);
+        supercompressed[44] = 1;
+        assert!(matches!(parse_ktx2(&supercompressed), Err(Ktx2Error::Unsupported(_))));
+
+        let mut cube = ktx2_bytes(37, 2, 2, &[vec!(0; 16)]);
+        cube[36] = 6;
+        assert!(matches!(parse_ktx2(&cube), Err(Ktx2Error::Unsupported(_))));
+    }
+}
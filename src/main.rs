@@ -1,290 +1,682 @@
 mod vulkan;
+mod mesh;
+mod frame_timer;
+mod camera;
+mod renderer;
+mod texture;
+mod spirv;
+mod screenshot;
+mod compute;
+mod color;
+mod hot_reload;
+mod post;
+mod app;
+mod demo;
+mod gpu_timer;
+mod geometry;
+mod text;
+mod settings;
+mod ktx2;
+mod decal;
+mod session;
+mod streaming;
+mod shapes;
+mod reel;
 
-use vulkano::buffer::{CpuAccessibleBuffer, BufferUsage, CpuBufferPool};
+use vulkano::device::{Device, Queue};
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::instance::InstanceExtensions;
+use vulkano::swapchain::PresentMode;
+use vulkano::sampler::SamplerAddressMode;
+use vulkan::initialization::{create_instance, create_window, vulkan_init, vulkan_init_headless, DeviceConfig,
+                             SwapchainConfig, WindowConfig};
+use vulkan::device::{list_devices, DeviceSelector};
+use vulkan::memory::log_memory_heaps;
+use vulkan::render_pass::{load_op_from_arg, store_op_from_arg, AttachmentOps};
+use vulkan::validation::list_layers;
+use mesh::{Mesh, MeshUsage, UploadBatch, Vertex, grid_instances, load_obj};
+use geometry::{make_cube, make_quad, make_uv_sphere};
+use frame_timer::FrameLimiter;
+use camera::{Camera, CameraMode};
+use renderer::{render_offscreen, BoundsMode, Renderer, RotationAxis, Scene};
+use texture::{address_mode_from_arg, filter_from_arg, load_texture, SamplerConfig};
+use spirv::ShaderPaths;
+use screenshot::save_png;
+use compute::compute_squares;
+use hot_reload::ShaderWatcher;
+use post::{DepthComplexityView, PostEffect};
+use app::run;
+use demo::Demo;
+use settings::{load_settings, DEFAULT_SETTINGS_PATH};
+use session::{load_session, Assets, Session};
+use streaming::{AssetStreamer, TextureOptions, DEFAULT_QUEUE_LENGTH};
+use reel::DemoReel;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use vulkano::pipeline::GraphicsPipeline;
-use vulkano::framebuffer::Subpass;
-use vulkano::command_buffer::{DynamicState, AutoCommandBufferBuilder};
-use vulkano::sync::{self, GpuFuture, FlushError};
-use winit::event::{Event, WindowEvent};
-use winit::event_loop::ControlFlow;
-use vulkano::swapchain::{self, SwapchainCreationError, AcquireError};
-use vulkan::initialization::{vulkan_init, window_size_dependent_setup};
-use std::time::Instant;
-use cgmath::{Matrix3, Matrix4, Rad};
-use vulkano::descriptor::PipelineLayoutAbstract;
-use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
-
-#[derive(Default, Debug, Clone)]
-struct Vertex {
-    position: [f32; 2]
-}
+#[cfg(feature = "renderdoc")]
+use vulkan::capture::FrameCapture;
+
+const HEADLESS_DIMENSIONS: [u32; 2] = [800, 600];
+
+const COMPUTE_VALUE_COUNT: u32 = 1000;
 
-vulkano::impl_vertex!(Vertex, position);
+// In the order --demo-reel shows them, "triangle" is first_triangle and the rest built_in_shape
+const REEL_SCENES: [&str; 4] = ["triangle", "quad", "cube", "sphere"];
+
+// For errors from the system (and arguments a user is likely to get wrong), which aren't bugs worth a backtrace
+fn exit_with_error(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1)
+}
 
 fn main() {
-    let (
-        device,
-        render_pass,
-        images,
-        event_loop,
-        surface,
-        mut swapchain,
-        queue
-    ) = vulkan_init();
-
-    let vertex_buffer = CpuAccessibleBuffer::from_iter(
-        device.clone(),
-        BufferUsage::all(),
-        false,
-        [
-            Vertex { position: [-0.5, -0.25] },
-            Vertex { position: [0.0, 0.5] },
-            Vertex { position: [0.25, -0.1] }
-        ]
-            .iter()
-            .cloned(),
-    )
-        .unwrap();
+    #[cfg(feature = "renderdoc")]
+    let frame_capture = FrameCapture::detect();
 
-    let vertex_buffer2 = CpuAccessibleBuffer::from_iter(
-        device.clone(),
-        BufferUsage::all(),
-        false,
-        [
-            Vertex { position: [-1.0, -1.0] },
-            Vertex { position: [-0.9, -0.9] },
-            Vertex { position: [-0.95, -0.85] }
-        ]
-            .iter()
-            .cloned(),
-    )
-        .unwrap();
+    // --config reads the settings from another file than sandbox.toml, the flags below override them
+    let settings_path = std::env::args()
+        .skip_while(|arg| arg != "--config")
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_SETTINGS_PATH.to_string());
+    let settings = load_settings(Path::new(&settings_path))
+        .unwrap_or_else(|e| panic!("Failed to load {}: {}", settings_path, e));
 
-    let vs = vs::Shader::load(device.clone()).unwrap();
-    let fs = fs::Shader::load(device.clone()).unwrap();
-    let fs2 = fs2::Shader::load(device.clone()).unwrap();
-    let uniform_buffer = CpuBufferPool::<vs::ty::Data>::new(
-        device.clone(),
-        BufferUsage::all()
-    );
+    // --session file.toml picks up where the last run with it left off and saves back to it
+    let session_path = std::env::args()
+        .skip_while(|arg| arg != "--session")
+        .nth(1)
+        .map(PathBuf::from);
+    let session = session_path.as_ref().and_then(|path| {
+        load_session(path)
+            .unwrap_or_else(|e| exit_with_error(format!("Failed to load the session {}: {}", path.display(), e)))
+    });
+    let assets = assets_from_args(session.as_ref());
 
-    let pipeline = Arc::new(
-        GraphicsPipeline::start()
-            .vertex_input_single_buffer()
-            .vertex_shader(vs.main_entry_point(), ())
-            .triangle_list()
-            .viewports_dynamic_scissors_irrelevant(1)
-            .fragment_shader(fs.main_entry_point(), ())
-            .render_pass(
-                Subpass::from(
-                    render_pass.clone(),
-                    0)
-                    .unwrap()
-            )
-            .build(device.clone())
-            .unwrap()
-    );
+    // --device takes "first", "discrete", "integrated" or (part of) a device name
+    let device_config = DeviceConfig {
+        selector: std::env::args()
+            .skip_while(|arg| arg != "--device")
+            .nth(1)
+            .map(|arg| DeviceSelector::from_arg(&arg))
+            .unwrap_or_else(|| settings.device.clone()),
+        // Only asks for the compression features when there's a KTX2 texture to use them
+        compressed_textures: assets.texture.as_ref()
+            .is_some_and(|path| path.extension().is_some_and(|extension| extension == "ktx2")),
+        // --no-transfer-queue does the uploads on the graphics queue, for drivers that get them wrong
+        transfer_queue: !std::env::args().any(|arg| arg == "--no-transfer-queue"),
+        ..DeviceConfig::default()
+    };
+    // Nothing toggles wireframe mode or the axes without a window
+    let headless_device_config = DeviceConfig { debug_features: false, ..device_config.clone() };
 
-    let pipeline2 = Arc::new(
-        GraphicsPipeline::start()
-            .vertex_input_single_buffer::<Vertex>()
-            .vertex_shader(vs.main_entry_point(), ())
-            .triangle_list()
-            .viewports_dynamic_scissors_irrelevant(1)
-            .fragment_shader(fs2.main_entry_point(), ())
-            .render_pass(
-                Subpass::from(
-                    render_pass.clone(),
-                    0)
-                    .unwrap()
-            )
-            .build(device.clone())
-            .unwrap()
-    );
+    if std::env::args().any(|arg| arg == "--list-layers") {
+        list_layers();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "--list-devices") {
+        let (instance, _debug_callback) = create_instance(InstanceExtensions::none())
+            .unwrap_or_else(|e| exit_with_error(format!("Failed to initialize vulkan: {}", e)));
+        list_devices(&instance);
+        return;
+    }
+
+    // --compute runs the compute shader example instead of opening a window
+    if std::env::args().any(|arg| arg == "--compute") {
+        let [width, height] = HEADLESS_DIMENSIONS;
+        let context = vulkan_init_headless(&headless_device_config, width, height)
+            .unwrap_or_else(|e| exit_with_error(format!("Failed to initialize vulkan: {}", e)));
 
-    let mut dynamic_state = DynamicState {
-        line_width: None,
-        viewports: None,
-        scissors: None,
-        compare_mask: None,
-        write_mask: None,
-        reference: None,
+        if !context.queue_capabilities.compute {
+            panic!("The graphics queue family of this device doesn't do compute");
+        }
+
+        let values = compute_squares(&context.queue, COMPUTE_VALUE_COUNT)
+            .unwrap_or_else(|e| exit_with_error(format!("Failed to run the compute shader: {}", e)));
+        let correct = values.iter()
+            .enumerate()
+            .all(|(index, &value)| value == (index * index) as u32);
+
+        println!("Computed {} squares on the GPU: {:?}...", values.len(), &values[..8]);
+        println!("{}", if correct { "All of them are correct" } else { "Some of them are wrong!" });
+        return;
+    }
+
+    // --headless renders a single frame into a PNG without opening a window
+    let headless_path = std::env::args()
+        .skip_while(|arg| arg != "--headless")
+        .nth(1);
+
+    if let Some(path) = headless_path {
+        let [width, height] = HEADLESS_DIMENSIONS;
+        let context = vulkan_init_headless(&headless_device_config, width, height)
+            .unwrap_or_else(|e| exit_with_error(format!("Failed to initialize vulkan: {}", e)));
+
+        // Only ever the one frame, there's no session to pick up
+        let mut scene = create_scene(&context.device, &context.queue, &context.render_pass, 1,
+                                     max_texture_size().or(settings.max_texture_size), &assets_from_args(None), None);
+        scene.clear_color = settings.clear_color;
+
+        let pixels = render_offscreen(&context, &mut scene, 0.0, &Camera::default());
+        save_png(Path::new(&path), context.format, context.dimensions, pixels)
+            .unwrap_or_else(|e| panic!("Failed to save {}: {}", path, e));
+
+        println!("Saved a {}x{} frame to {}", width, height, path);
+        return;
+    }
+
+    let [width, height] = settings.window_size;
+    let window_config = WindowConfig { width, height, ..WindowConfig::default() };
+    // F11 toggles borderless fullscreen, --exclusive-fullscreen makes it exclusive where the driver can.
+    // --depth-prepass lays down the first mesh's depth before shading it, --msaa 4 turns on MSAA.
+    // --decals adds the subpass middle click puts decals on the first mesh in, without the stencil mask.
+    // --letterbox 16:9 keeps that aspect ratio with bars in --letterbox-color r,g,b around it
+    let (letterbox, letterbox_color) = letterbox();
+    let swapchain_config = SwapchainConfig {
+        exclusive_fullscreen: std::env::args().any(|arg| arg == "--exclusive-fullscreen"),
+        depth_prepass: std::env::args().any(|arg| arg == "--depth-prepass"),
+        samples: msaa_samples().unwrap_or(settings.msaa),
+        color_ops: color_ops(),
+        decals: std::env::args().any(|arg| arg == "--decals"),
+        present_mode: vsync_present_mode().unwrap_or(settings.present_mode),
+        letterbox,
+        letterbox_color,
+        ..SwapchainConfig::default()
     };
 
-    let mut framebuffers = window_size_dependent_setup(
-        &images,
-        render_pass.clone(),
-        &mut dynamic_state,
-    );
+    let (context, event_loop) = vulkan_init(&device_config, &window_config, &swapchain_config)
+        .unwrap_or_else(|e| exit_with_error(format!("Failed to initialize vulkan: {}", e)));
 
-    let mut recreate_swapchain = false;
-    let mut previous_frame_end = Some(
-        Box::new(
-            sync::now(device.clone())
-        ) as Box<dyn GpuFuture>
-    );
-    let rotation_duration = Instant::now();
+    println!("Using {}", context.queue_capabilities);
+    log_memory_heaps(context.device.physical_device());
+    if context.samples > 1 {
+        println!("Using {}x MSAA", context.samples);
+    } else {
+        println!("MSAA is off");
+    }
+    if context.transfer_queue.family().id() != context.queue.family().id() {
+        println!("Using queue family {} for transfers", context.transfer_queue.family().id());
+    }
+    if context.present_queue.family().id() != context.queue.family().id() {
+        println!("Using queue family {} for presenting", context.present_queue.family().id());
+    }
 
-    event_loop.run(move |event, _, control_flow| {
-        match event {
-            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
-                *control_flow = ControlFlow::Exit;
-            }
-            Event::WindowEvent { event: WindowEvent::Resized(_), .. } => {
-                recreate_swapchain = true;
-            }
-            Event::RedrawEventsCleared => {
-                previous_frame_end.as_mut()
-                    .unwrap()
-                    .cleanup_finished();
-
-                if recreate_swapchain {
-                    let dimensions: [u32; 2] = surface.capabilities(device.physical_device())
-                        .unwrap()
-                        .min_image_extent;
-
-                    let (new_swapchain, new_images) = match swapchain.recreate_with_dimensions(dimensions) {
-                        Ok(r) => r,
-                        Err(SwapchainCreationError::UnsupportedDimensions) => return,
-                        Err(e) => panic!("Failed to recreate swapchain: {:?}", e)
-                    };
-
-                    swapchain = new_swapchain;
-                    framebuffers = window_size_dependent_setup(
-                        &new_images,
-                        render_pass.clone(),
-                        &mut dynamic_state,
-                    );
-                    recreate_swapchain = false;
-                }
-
-                let (image_num, suboptimal, acquire_future) = match swapchain::acquire_next_image(swapchain.clone(), None) {
-                    Ok(r) => r,
-                    Err(AcquireError::OutOfDate) => {
-                        recreate_swapchain = true;
-                        return;
-                    }
-                    Err(e) => panic!("Failed to acquire next image: {:?}", e)
-                };
-
-                recreate_swapchain = suboptimal;
-                let clear_values = vec!([0.0, 0.0, 1.0, 1.0].into());
-
-                let uniform_buffer_subbuffer = {
-                    let elapsed = rotation_duration.elapsed();
-                    let rotation = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0;
-                    let rotation = Matrix3::from_angle_z(Rad(rotation as f32));
-
-                    let data = vs::ty::Data {
-                        rotation: Matrix4::from(rotation).into()
-                    };
-
-                    uniform_buffer.next(data).unwrap()
-                };
-
-                let layout = pipeline.descriptor_set_layout(0).unwrap();
-                let set = Arc::new(
-                    PersistentDescriptorSet::start(
-                        layout.clone()
-                    )
-                        .add_buffer(uniform_buffer_subbuffer.clone())
-                        .unwrap()
-                        .build()
-                        .unwrap()
-                );
-
-                let layout2 = pipeline2.descriptor_set_layout(0).unwrap();
-                let set2 = Arc::new(
-                    PersistentDescriptorSet::start(
-                        layout2.clone()
-                    )
-                        .add_buffer(uniform_buffer_subbuffer.clone())
-                        .unwrap()
-                        .build()
-                        .unwrap()
-                );
-
-                let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
-                    device.clone(),
-                    queue.family(),
-                )
-                    .unwrap()
-                    .begin_render_pass(framebuffers[image_num].clone(), false, clear_values.clone())
-                    .unwrap()
-                    .draw(
-                        pipeline.clone(),
-                        &dynamic_state,
-                        vertex_buffer.clone(),
-                        set.clone(),
-                        (),
-                    )
-                    .unwrap()
-                    .draw(
-                        pipeline2.clone(),
-                        &dynamic_state,
-                        vertex_buffer2.clone(),
-                        set2.clone(),
-                        (),
-                    )
-                    .unwrap()
-                    .end_render_pass()
-                    .unwrap()
-                    .build()
-                    .unwrap();
-
-                let future = previous_frame_end.take()
-                    .unwrap()
-                    .join(acquire_future)
-                    .then_execute(
-                        queue.clone(),
-                        command_buffer,
-                    )
-                    .unwrap()
-                    .then_swapchain_present(
-                        queue.clone(),
-                        swapchain.clone(),
-                        image_num,
-                    )
-                    .then_signal_fence_and_flush();
-
-                match future {
-                    Ok(future) => {
-                        let _ = future.wait(None);
-                        previous_frame_end = Some(Box::new(future) as Box<_>);
-                    }
-                    Err(FlushError::OutOfDate) => {
-                        recreate_swapchain = true;
-                        previous_frame_end = Some(Box::new(sync::now(device.clone())) as Box<_>);
-                    }
-                    Err(e) => {
-                        println!("Failed to flush future: {:?}", e);
-                        previous_frame_end = Some(Box::new(sync::now(device.clone())) as Box<_>);
-                    }
-                }
+    // --windows 2 opens more windows on the same device, all showing the same scene
+    let window_count = match std::env::args().skip_while(|arg| arg != "--windows").nth(1) {
+        Some(arg) => match arg.parse::<u32>() {
+            Ok(count) if count > 0 => count,
+            _ => panic!("Invalid window count {}, expected a positive number", arg),
+        },
+        None => 1,
+    };
+    let extra_windows = (2..=window_count)
+        .map(|number| {
+            let config = WindowConfig {
+                title: format!("{} ({})", window_config.title, number),
+                ..window_config.clone()
+            };
+            let window = create_window(&context, &event_loop, &config, &swapchain_config)
+                .unwrap_or_else(|e| panic!("Failed to open window {}: {}", number, e));
+            (config.title, window)
+        })
+        .collect::<Vec<_>>();
+
+    let mut scene = create_scene(&context.device, &context.queue, &context.window.render_pass, context.samples,
+                                 max_texture_size().or(settings.max_texture_size), &assets, session.as_ref());
+    scene.clear_color = settings.clear_color;
+    // --post-effect grayscale|invert renders the scene offscreen and filters it on the way to the screen
+    let post_effect = std::env::args()
+        .skip_while(|arg| arg != "--post-effect")
+        .nth(1)
+        .map(|arg| PostEffect::from_arg(&arg)
+            .unwrap_or_else(|| panic!("Unknown post effect {}, expected grayscale or invert", arg)));
+
+    // --hot-reload recompiles vert.glsl and frag.glsl whenever one of them is saved. Changing their
+    // inputs, outputs or uniforms still needs a restart
+    let shader_watcher = if std::env::args().any(|arg| arg == "--hot-reload") {
+        let source_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        match ShaderWatcher::new(source_dir.join("vert.glsl"), source_dir.join("frag.glsl")) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                println!("Hot reloading is off: {}", e);
+                None
             }
-            _ => ()
         }
-    })
+    } else {
+        None
+    };
+
+    // --fps-cap 60 limits the frame rate no matter the present mode, without it Mailbox and
+    // Immediate run as fast as they can. --fps-cap refresh goes by the monitor's refresh rate
+    let fps_cap = std::env::args()
+        .skip_while(|arg| arg != "--fps-cap")
+        .nth(1);
+    let cap_at_refresh_rate = fps_cap.as_deref() == Some("refresh");
+    let frame_limiter = fps_cap
+        .filter(|_| !cap_at_refresh_rate)
+        .map(|arg| match arg.parse::<u32>() {
+            Ok(target_fps) if target_fps > 0 => FrameLimiter::new(target_fps),
+            _ => panic!("Invalid frame rate cap {}, expected a positive number or refresh", arg),
+        });
+
+    let mut demo = Demo::new(
+        &context,
+        window_config.title.clone(),
+        scene,
+        shader_watcher,
+        frame_limiter,
+        #[cfg(feature = "renderdoc")]
+        frame_capture,
+    );
+    demo.set_present_mode(swapchain_config.present_mode);
+    if let Some(path) = session_path {
+        demo.set_session_file(path, assets.clone());
+    }
+    if cap_at_refresh_rate {
+        demo.cap_at_refresh_rate(context.window.surface.window());
+    }
+    let mut renderers = vec!(Renderer::new(&context, &context.window, swapchain_config.clone(), post_effect));
+    for (title, window) in &extra_windows {
+        let renderer = Renderer::new(&context, window, swapchain_config.clone(), post_effect);
+        demo.add_window(&renderer, title.clone())
+            .unwrap_or_else(|e| exit_with_error(format!("Failed to set up the scene for another window: {}", e)));
+        renderers.push(renderer);
+    }
+    if let Some(session) = &session {
+        demo.restore_session(session, &mut renderers);
+    }
+    // --camera orbit circles around the triangles with the left mouse button and the scroll wheel
+    // instead of flying with WASD
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--camera").nth(1) {
+        match CameraMode::from_arg(&arg) {
+            Some(CameraMode::Orbit) => demo.use_orbit_camera(),
+            Some(CameraMode::Fly) => (),
+            None => panic!("Unknown camera mode {}, expected fly or orbit", arg),
+        }
+    }
+    // --keyframe-duration 5 is how many seconds the camera path (N and T) takes between keyframes
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--keyframe-duration").nth(1) {
+        match arg.parse::<f32>() {
+            Ok(seconds) if seconds > 0.0 => demo.set_keyframe_duration(seconds),
+            _ => panic!("Invalid keyframe duration {}, expected a positive number of seconds", arg),
+        }
+    }
+
+    // --stream a.obj,b.png,c.obj loads the next one in the list with Tab, on a thread of its own and
+    // through the transfer queue. Models replace the first mesh, images the --texture one.
+    // --stream-queue 8 is how many can wait for the thread at once
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--stream").nth(1) {
+        let queue_length = match std::env::args().skip_while(|arg| arg != "--stream-queue").nth(1) {
+            Some(arg) => match arg.parse::<usize>() {
+                Ok(length) if length > 0 => length,
+                _ => panic!("Invalid streaming queue length {}, expected a positive number", arg),
+            },
+            None => DEFAULT_QUEUE_LENGTH,
+        };
+        let texture_options = TextureOptions {
+            mipmaps: mipmaps(),
+            max_size: max_texture_size().or(settings.max_texture_size),
+            sampler_config: sampler_config(),
+        };
+        let streamer = AssetStreamer::new(&context.transfer_queue, queue_length, texture_options);
+        demo.set_streamer(streamer, arg.split(',').map(PathBuf::from).collect());
+    }
+
+    // --demo-reel 10 goes through the triangle and the built-in shapes by itself, 10 seconds each
+    // with a crossfade in between
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--demo-reel").nth(1) {
+        match arg.parse::<f32>() {
+            Ok(seconds) if seconds > 0.0 => demo.set_demo_reel(demo_reel(&context.queue, seconds, &assets), &mut renderers),
+            _ => panic!("Invalid demo reel interval {}, expected a positive number of seconds", arg),
+        }
+    }
+
+    // --depth-complexity pixels|average|max starts with O's overdraw view, per pixel or per tile
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--depth-complexity").nth(1) {
+        let view = DepthComplexityView::from_arg(&arg)
+            .unwrap_or_else(|| panic!("Invalid depth complexity view {}, expected pixels, average or max", arg));
+        demo.set_depth_complexity(Some(view), &mut renderers);
+    }
+
+    // --dither 1 starts with G's ordered dithering in the post pass, at this many 8 bit steps
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--dither").nth(1) {
+        match arg.parse::<f32>() {
+            Ok(strength) if strength > 0.0 => demo.set_dither(strength, &mut renderers),
+            _ => panic!("Invalid dither strength {}, expected a positive number", arg),
+        }
+    }
+
+    run(context, event_loop, renderers, demo)
+}
+
+// --no-vsync presents as soon as a frame is done, through Mailbox where the surface has it and
+// Immediate (which tears) otherwise. --vsync goes back to Fifo, the last of the two wins. Without
+// either it's up to the settings
+fn vsync_present_mode() -> Option<PresentMode> {
+    let arg = std::env::args()
+        .rfind(|arg| arg == "--vsync" || arg == "--no-vsync")?;
+    if arg == "--vsync" {
+        Some(PresentMode::Fifo)
+    } else {
+        Some(PresentMode::Mailbox)
+    }
+}
+
+// --color-load clear|load|dont-care and --color-store store|dont-care, for what happens to the
+// window's contents between frames
+fn color_ops() -> AttachmentOps {
+    let mut ops = AttachmentOps::default();
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--color-load").nth(1) {
+        ops.load = load_op_from_arg(&arg)
+            .unwrap_or_else(|| panic!("Invalid load op {}, expected clear, load or dont-care", arg));
+    }
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--color-store").nth(1) {
+        ops.store = store_op_from_arg(&arg)
+            .unwrap_or_else(|| panic!("Invalid store op {}, expected store or dont-care", arg));
+    }
+    ops
 }
 
-mod vs {
-    vulkano_shaders::shader!{
-        ty: "vertex",
-        path: "src/vert.glsl"
+// --msaa takes a sample count, which gets lowered to what the device supports. 1 turns it off,
+// without --msaa it's up to the settings
+fn msaa_samples() -> Option<u32> {
+    let arg = std::env::args().skip_while(|arg| arg != "--msaa").nth(1)?;
+    match arg.parse::<u32>() {
+        Ok(samples) if samples.is_power_of_two() && samples <= 64 => Some(samples),
+        _ => exit_with_error(format!("Invalid sample count {}\nUsage: --msaa 1|2|4|8|16|32|64", arg)),
     }
 }
 
-mod fs {
-    vulkano_shaders::shader!{
-        ty: "fragment",
-        path: "src/frag.glsl"
+// --letterbox takes width:height or a single ratio, --letterbox-color what the bars get
+fn letterbox() -> (Option<f32>, [f32; 4]) {
+    let mut color = SwapchainConfig::default().letterbox_color;
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--letterbox-color").nth(1) {
+        let [r, g, b] = color_from_arg(&arg)
+            .unwrap_or_else(|| panic!("Invalid letterbox color {}, expected r,g,b from 0 to 1", arg));
+        color = [r, g, b, 1.0];
     }
+    let aspect_ratio = std::env::args().skip_while(|arg| arg != "--letterbox").nth(1).map(|arg| {
+        aspect_ratio_from_arg(&arg)
+            .unwrap_or_else(|| panic!("Invalid aspect ratio {}, expected e.g. 16:9 or 1.78", arg))
+    });
+    (aspect_ratio, color)
+}
+
+fn aspect_ratio_from_arg(arg: &str) -> Option<f32> {
+    let ratio = match arg.split_once(':') {
+        Some((width, height)) => width.trim().parse::<f32>().ok()? / height.trim().parse::<f32>().ok()?,
+        None => arg.parse::<f32>().ok()?,
+    };
+    Some(ratio).filter(|ratio| ratio.is_finite() && *ratio > 0.0)
+}
+
+// --max-texture-size 2048 scales down every texture with a longer side, without it it's up to the
+// settings. Either way the device's limit is the most it gets
+fn max_texture_size() -> Option<u32> {
+    let arg = std::env::args().skip_while(|arg| arg != "--max-texture-size").nth(1)?;
+    match arg.parse::<u32>() {
+        Ok(size) if size > 0 => Some(size),
+        _ => panic!("Invalid max texture size {}, expected a number of pixels", arg),
+    }
+}
+
+// "r,g,b" with every component from 0 to 1
+fn color_from_arg(arg: &str) -> Option<[f32; 3]> {
+    let components = arg.split(',')
+        .map(|component| component.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+
+    match components.as_slice() {
+        [r, g, b] if components.iter().all(|component| (0.0..=1.0).contains(component)) => Some([*r, *g, *b]),
+        _ => None,
+    }
+}
+
+// --texture-filter nearest|linear and --texture-address repeat|mirror|clamp|border, for --texture
+// and whatever gets streamed in
+fn sampler_config() -> SamplerConfig {
+    let mut sampler_config = SamplerConfig::default();
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--texture-filter").nth(1) {
+        let filter = filter_from_arg(&arg)
+            .unwrap_or_else(|| panic!("Invalid texture filter {}, expected nearest or linear", arg));
+        sampler_config.mag_filter = filter;
+        sampler_config.min_filter = filter;
+    }
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--texture-address").nth(1) {
+        sampler_config.address_mode = address_mode_from_arg(&arg)
+            .unwrap_or_else(|| panic!("Invalid texture address mode {}, expected repeat, mirror, clamp or border", arg));
+    }
+    sampler_config
+}
+
+// --no-mipmaps only loads the full size image, to compare the aliasing
+fn mipmaps() -> bool {
+    !std::env::args().any(|arg| arg == "--no-mipmaps")
+}
+
+// --model, --shape, --texture and --decal, whatever isn't given comes from the session. Either of
+// --model and --shape replaces both of the session's
+fn assets_from_args(session: Option<&Session>) -> Assets {
+    let arg = |flag: &str| std::env::args().skip_while(|arg| arg != flag).nth(1);
+    let session = session
+        .map(|session| session.assets.clone().without_missing())
+        .unwrap_or_default();
+
+    let (model, shape) = match (arg("--model"), arg("--shape")) {
+        (None, None) => (session.model, session.shape),
+        (model, shape) => (model.map(PathBuf::from), shape),
+    };
+    Assets {
+        model,
+        shape,
+        texture: arg("--texture").map(PathBuf::from).or(session.texture),
+        decal: arg("--decal").map(PathBuf::from).or(session.decal),
+    }
+}
+
+// The colored triangle that's there without --model or --shape
+fn first_triangle() -> (Vec<Vertex>, Vec<u32>) {
+    let vertices = vec!(
+        Vertex { position: [-0.5, -0.25, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 0.0], color: [1.0, 0.0, 0.0] },
+        Vertex { position: [0.0, 0.5, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 0.0], color: [0.0, 1.0, 0.0] },
+        Vertex { position: [0.25, -0.1, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 0.0], color: [0.0, 0.0, 1.0] }
+    );
+    (vertices, vec!(0, 1, 2))
+}
+
+fn built_in_shape(name: &str) -> Option<(Vec<Vertex>, Vec<u32>)> {
+    match name {
+        "quad" => Some(make_quad()),
+        "cube" => Some(make_cube()),
+        "sphere" => Some(make_uv_sphere(32, 16)),
+        _ => None,
+    }
+}
+
+// Everything --demo-reel goes through: the triangle and the built-in shapes, leaving out the one the
+// scene starts with. Named after the starting mesh, which the reel comes back to
+fn demo_reel(queue: &Arc<Queue>, interval: f32, assets: &Assets) -> DemoReel {
+    let current = match (&assets.model, &assets.shape) {
+        (Some(path), _) => path.display().to_string(),
+        (None, Some(shape)) => shape.clone(),
+        (None, None) => "triangle".to_string(),
+    };
+
+    let mut uploads = UploadBatch::new(queue)
+        .unwrap_or_else(|e| exit_with_error(format!("Failed to set up the mesh uploads: {}", e)));
+    let scenes = REEL_SCENES.iter()
+        .filter(|name| **name != current)
+        .map(|name| {
+            let (vertices, indices) = built_in_shape(name)
+                .unwrap_or_else(first_triangle);
+            let mesh = Mesh::from_indexed(&mut uploads, &vertices, &indices, MeshUsage::Static)
+                .unwrap();
+            (name.to_string(), mesh)
+        })
+        .collect();
+    uploads.finish()
+        .unwrap_or_else(|e| exit_with_error(format!("Failed to upload the meshes: {}", e)));
+
+    DemoReel::new(interval, current, scenes)
 }
 
-mod fs2 {
-    vulkano_shaders::shader!{
-        ty: "fragment",
-        path: "src/frag2.glsl"
+fn create_scene(device: &Arc<Device>, queue: &Arc<Queue>, render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+                samples: u32, max_texture_size: Option<u32>, assets: &Assets, session: Option<&Session>) -> Scene {
+    // --model replaces the first triangle with the triangles of an OBJ file
+    let model_path = assets.model.as_ref();
+
+    // --shape quad|cube|sphere does the same with one of the built-in shapes
+    let shape = assets.shape.as_ref()
+        .map(|arg| built_in_shape(arg)
+            .unwrap_or_else(|| panic!("Unknown shape {}, expected quad, cube or sphere", arg)));
+
+    // --dynamic-vertices keeps the first mesh in host visible memory instead of device local memory
+    let usage = if std::env::args().any(|arg| arg == "--dynamic-vertices") { MeshUsage::Dynamic } else { MeshUsage::Static };
+
+    // Everything static below gets uploaded in one go
+    let mut uploads = UploadBatch::new(queue)
+        .unwrap_or_else(|e| exit_with_error(format!("Failed to set up the mesh uploads: {}", e)));
+
+    let mesh = match (model_path, shape) {
+        (Some(path), _) => load_obj(path, &mut uploads, usage)
+            .unwrap_or_else(|e| panic!("Failed to load {}: {}", path.display(), e)),
+        (None, Some((vertices, indices))) => Mesh::from_indexed(&mut uploads, &vertices, &indices, usage)
+            .unwrap(),
+        (None, None) => {
+            let (vertices, indices) = first_triangle();
+            Mesh::from_indexed(&mut uploads, &vertices, &indices, usage)
+                .unwrap()
+        }
+    };
+
+    // --instances draws that many copies of it on a grid with a single instanced draw
+    let instance_count = std::env::args()
+        .skip_while(|arg| arg != "--instances")
+        .nth(1)
+        .map(|arg| arg.parse::<u32>()
+            .unwrap_or_else(|e| panic!("Invalid instance count {}: {}", arg, e)));
+
+    let mesh = match instance_count {
+        // Vulkan doesn't do empty buffers
+        Some(0) => panic!("--instances needs at least one instance"),
+        Some(count) => mesh.with_instances(device.clone(), &grid_instances(count))
+            .unwrap(),
+        None => mesh,
+    };
+
+    // Two separate triangles in one buffer, the draw covers however many vertices there are
+    let mesh2 = Mesh::from_vertices(
+        &mut uploads,
+        &[
+            Vertex { position: [-1.0, -1.0, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 0.0], color: [1.0, 1.0, 1.0] },
+            Vertex { position: [-0.9, -0.9, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 0.0], color: [1.0, 1.0, 1.0] },
+            Vertex { position: [-0.95, -0.85, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 0.0], color: [1.0, 1.0, 1.0] },
+            Vertex { position: [-0.85, -1.0, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 0.0], color: [1.0, 1.0, 1.0] },
+            Vertex { position: [-0.75, -0.9, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 0.0], color: [1.0, 1.0, 1.0] },
+            Vertex { position: [-0.8, -0.85, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 0.0], color: [1.0, 1.0, 1.0] }
+        ],
+        MeshUsage::Static,
+    )
+        .unwrap();
+
+    // --texture draws the image on a quad next to the triangles
+    let texture_path = assets.texture.as_ref();
+
+    let sampler_config = sampler_config();
+    let mipmaps = mipmaps();
+
+    let textured_quad = texture_path.map(|path| {
+        let texture = load_texture(path, queue, true, mipmaps, max_texture_size, &sampler_config)
+            .unwrap_or_else(|e| panic!("Failed to load {}: {}", path.display(), e));
+
+        let quad = Mesh::from_indexed(
+            &mut uploads,
+            &[
+                Vertex { position: [0.6, 0.2, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 0.0], color: [1.0, 1.0, 1.0] },
+                Vertex { position: [0.6, -0.2, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 1.0], color: [1.0, 1.0, 1.0] },
+                Vertex { position: [1.0, -0.2, 0.0], normal: [0.0, 0.0, 1.0], uv: [1.0, 1.0], color: [1.0, 1.0, 1.0] },
+                Vertex { position: [1.0, 0.2, 0.0], normal: [0.0, 0.0, 1.0], uv: [1.0, 0.0], color: [1.0, 1.0, 1.0] }
+            ],
+            &[0, 1, 2, 2, 3, 0],
+            MeshUsage::Static,
+        )
+            .unwrap();
+
+        (quad, texture)
+    });
+    uploads.finish()
+        .unwrap_or_else(|e| exit_with_error(format!("Failed to upload the meshes: {}", e)));
+
+    // --vertex-shader and --fragment-shader take precompiled .spv files to use instead of the
+    // built-in vertex shader and the vertex color fragment shader
+    let shader_paths = ShaderPaths {
+        vertex: std::env::args()
+            .skip_while(|arg| arg != "--vertex-shader")
+            .nth(1)
+            .map(PathBuf::from),
+        fragment: std::env::args()
+            .skip_while(|arg| arg != "--fragment-shader")
+            .nth(1)
+            .map(PathBuf::from),
+    };
+
+    let mut scene = Scene::new(queue, render_pass, samples, mesh, mesh2, textured_quad, &shader_paths)
+        .unwrap_or_else(|e| exit_with_error(format!("Failed to create the scene: {}", e)));
+
+    // --decal path.png is what gets projected instead of the red ring
+    if let Some(path) = &assets.decal {
+        let decal_sampler_config = SamplerConfig {
+            address_mode: SamplerAddressMode::ClampToEdge,
+            ..SamplerConfig::default()
+        };
+        let texture = load_texture(path, queue, true, mipmaps, max_texture_size, &decal_sampler_config)
+            .unwrap_or_else(|e| exit_with_error(format!("Failed to load {}: {}", path.display(), e)));
+        scene.set_decal_texture(texture);
+    }
+    if let Some(session) = session {
+        session.apply_to_scene(&mut scene);
+    }
+
+    // --transparent draws a half transparent triangle over the first one
+    scene.show_transparent |= std::env::args().any(|arg| arg == "--transparent");
+    scene.stencil_mask |= std::env::args().any(|arg| arg == "--stencil-mask");
+    // --uv-grid starts with the UV checker grid on the first mesh, same as U
+    scene.uv_grid |= std::env::args().any(|arg| arg == "--uv-grid");
+    // --affine-uvs starts without perspective correction, same as R
+    scene.affine_uvs |= std::env::args().any(|arg| arg == "--affine-uvs");
+    // --label-occlusion hides the axis labels behind whatever is in front of them
+    scene.label_depth_test |= std::env::args().any(|arg| arg == "--label-occlusion");
+    // --no-static-draws records the axes every frame too, for comparing the recording time
+    scene.use_static_draws = !std::env::args().any(|arg| arg == "--no-static-draws");
+
+    // --bounds aabb|obb starts with the bounding boxes shown (B toggles them), --bounds-color r,g,b
+    // is what they're drawn in
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--bounds").nth(1) {
+        scene.bounds_mode = BoundsMode::from_arg(&arg)
+            .unwrap_or_else(|| panic!("Invalid bounding box mode {}, expected aabb or obb", arg));
+        scene.show_bounds = true;
+    }
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--bounds-color").nth(1) {
+        scene.bounds_color = color_from_arg(&arg)
+            .unwrap_or_else(|| panic!("Invalid bounding box color {}, expected r,g,b from 0 to 1", arg));
+    }
+
+    // --ambient 0.3 is how much light the faces turned away from the light still get
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--ambient").nth(1) {
+        scene.ambient = arg.parse()
+            .unwrap_or_else(|e| panic!("Invalid ambient light {}: {}", arg, e));
     }
+
+    // --rotation-speed in radians per second, --rotation-axis takes x, y, z or x,y,z
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--rotation-speed").nth(1) {
+        scene.rotation_speed = arg.parse()
+            .unwrap_or_else(|e| panic!("Invalid rotation speed {}: {}", arg, e));
+    }
+    if let Some(arg) = std::env::args().skip_while(|arg| arg != "--rotation-axis").nth(1) {
+        scene.rotation_axis = RotationAxis::from_arg(&arg)
+            .unwrap_or_else(|| panic!("Invalid rotation axis {}, expected x, y, z or x,y,z", arg));
+    }
+
+    scene
 }
@@ -0,0 +1,890 @@
+use vulkano::OomError;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, ImmutableBuffer, TypedBufferAccess};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BuildError, CommandBuffer, CommandBufferExecError,
+                              CopyBufferError, DynamicState};
+use vulkano::descriptor::descriptor_set::DescriptorSetsCollection;
+use vulkano::device::{Device, Queue};
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::pipeline::vertex::VertexSource;
+use vulkano::sync::{FlushError, GpuFuture};
+use cgmath::{InnerSpace, Matrix4, Point3, Transform, Vector3};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Default, Debug, Clone)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    pub color: [f32; 3],
+}
+
+vulkano::impl_vertex!(Vertex, position, normal, uv, color);
+
+// Per-instance attributes, every instance is the whole mesh scaled and then moved by offset
+#[derive(Default, Debug, Clone)]
+pub struct InstanceData {
+    pub offset: [f32; 2],
+    pub scale: f32,
+}
+
+vulkano::impl_vertex!(InstanceData, offset, scale);
+
+pub type VertexBuffer = Arc<dyn TypedBufferAccess<Content = [Vertex]> + Send + Sync>;
+pub type InstancedBuffers = (VertexBuffer, Arc<CpuAccessibleBuffer<[InstanceData]>>);
+
+// OBJ files don't have vertex colors, this keeps models the red they've always been
+const OBJ_VERTEX_COLOR: [f32; 3] = [1.0, 0.0, 0.0];
+
+#[derive(Debug)]
+pub enum BufferError {
+    Allocation(DeviceMemoryAllocError),
+    CommandBuffer(OomError),
+    Copy(CopyBufferError),
+    Build(BuildError),
+    Execute(CommandBufferExecError),
+    Upload(FlushError),
+    // Left over vertices at the end that don't make a whole triangle or line
+    IncompletePrimitive { vertices: usize, per_primitive: usize },
+}
+
+impl fmt::Display for BufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BufferError::Allocation(e) => write!(f, "failed to allocate the buffer: {}", e),
+            BufferError::CommandBuffer(e) => write!(f, "failed to create the upload command buffer: {}", e),
+            BufferError::Copy(e) => write!(f, "failed to record the upload: {}", e),
+            BufferError::Build(e) => write!(f, "failed to build the upload command buffer: {}", e),
+            BufferError::Execute(e) => write!(f, "failed to submit the upload: {}", e),
+            BufferError::Upload(e) => write!(f, "failed to upload the buffer: {}", e),
+            BufferError::IncompletePrimitive { vertices, per_primitive } => write!(
+                f,
+                "{} vertices isn't a multiple of {}, the last primitive is incomplete",
+                vertices,
+                per_primitive,
+            ),
+        }
+    }
+}
+
+impl Error for BufferError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BufferError::Allocation(e) => Some(e),
+            BufferError::CommandBuffer(e) => Some(e),
+            BufferError::Copy(e) => Some(e),
+            BufferError::Build(e) => Some(e),
+            BufferError::Execute(e) => Some(e),
+            BufferError::Upload(e) => Some(e),
+            BufferError::IncompletePrimitive { .. } => None,
+        }
+    }
+}
+
+impl From<DeviceMemoryAllocError> for BufferError {
+    fn from(e: DeviceMemoryAllocError) -> BufferError {
+        BufferError::Allocation(e)
+    }
+}
+
+impl From<OomError> for BufferError {
+    fn from(e: OomError) -> BufferError {
+        BufferError::CommandBuffer(e)
+    }
+}
+
+impl From<CopyBufferError> for BufferError {
+    fn from(e: CopyBufferError) -> BufferError {
+        BufferError::Copy(e)
+    }
+}
+
+impl From<BuildError> for BufferError {
+    fn from(e: BuildError) -> BufferError {
+        BufferError::Build(e)
+    }
+}
+
+impl From<CommandBufferExecError> for BufferError {
+    fn from(e: CommandBufferExecError) -> BufferError {
+        BufferError::Execute(e)
+    }
+}
+
+impl From<FlushError> for BufferError {
+    fn from(e: FlushError) -> BufferError {
+        BufferError::Upload(e)
+    }
+}
+
+// Static meshes never change after loading so they live in device local memory, uploaded through
+// an UploadBatch. Dynamic ones stay host visible, for vertices that get rewritten from the CPU
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeshUsage {
+    Static,
+    Dynamic,
+}
+
+pub enum MeshBuffer<T> {
+    Static(Arc<ImmutableBuffer<[T]>>),
+    // write() on it, but not while a frame that draws it is in flight
+    Dynamic(Arc<CpuAccessibleBuffer<[T]>>),
+}
+
+impl<T: Send + Sync + 'static> MeshBuffer<T> {
+    // Either kind, for the draw calls
+    pub fn access(&self) -> Arc<dyn TypedBufferAccess<Content = [T]> + Send + Sync> {
+        match self {
+            MeshBuffer::Static(buffer) => buffer.clone(),
+            MeshBuffer::Dynamic(buffer) => buffer.clone(),
+        }
+    }
+}
+
+// Smallest box around some positions, lined up with the axes of whatever space they're in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Bounds {
+    // None without any positions
+    pub fn from_positions(positions: impl IntoIterator<Item = [f32; 3]>) -> Option<Bounds> {
+        positions.into_iter().fold(None, |bounds, position| {
+            let bounds = bounds.unwrap_or(Bounds { min: position, max: position });
+            Some(Bounds {
+                min: [bounds.min[0].min(position[0]), bounds.min[1].min(position[1]), bounds.min[2].min(position[2])],
+                max: [bounds.max[0].max(position[0]), bounds.max[1].max(position[1]), bounds.max[2].max(position[2])],
+            })
+        })
+    }
+
+    // Bit 0 of the index picks min or max x, bit 1 y and bit 2 z
+    pub fn corners(&self) -> [[f32; 3]; 8] {
+        let mut corners = [[0.0; 3]; 8];
+        for (index, corner) in corners.iter_mut().enumerate() {
+            for (axis, value) in corner.iter_mut().enumerate() {
+                *value = if index & (1 << axis) == 0 { self.min[axis] } else { self.max[axis] };
+            }
+        }
+        corners
+    }
+
+    // The corners moved by matrix, which stay a box but not one lined up with the axes any more
+    pub fn transformed_corners(&self, matrix: Matrix4<f32>) -> [[f32; 3]; 8] {
+        let mut corners = self.corners();
+        for corner in corners.iter_mut() {
+            *corner = matrix.transform_point(Point3::from(*corner)).into();
+        }
+        corners
+    }
+
+    // The axis aligned box around the transformed corners
+    pub fn transformed(&self, matrix: Matrix4<f32>) -> Bounds {
+        Bounds::from_positions(self.transformed_corners(matrix).iter().cloned())
+            .unwrap()
+    }
+}
+
+// The 12 edges of a box with corners in the order of Bounds::corners, as a line list
+pub fn box_lines(corners: &[[f32; 3]; 8], color: [f32; 3]) -> Vec<Vertex> {
+    let mut vertices = vec!();
+    for (index, corner) in corners.iter().enumerate() {
+        // Every corner connects to the ones that differ in a single bit, each edge is added from
+        // the end with the bit unset
+        for axis in 0..3 {
+            if index & (1 << axis) == 0 {
+                vertices.push(Vertex { position: *corner, color, ..Vertex::default() });
+                vertices.push(Vertex { position: corners[index | (1 << axis)], color, ..Vertex::default() });
+            }
+        }
+    }
+    vertices
+}
+
+// Vertices plus an optional index buffer. Without indices the vertices are drawn as a plain
+// triangle list (or whatever the pipeline's topology is), every vertex in the buffer. With
+// instances it's drawn once per instance, which needs a pipeline that takes InstanceData as a
+// second (per-instance) vertex buffer. Instance data is always host visible
+pub struct Mesh {
+    pub vertices: MeshBuffer<Vertex>,
+    pub indices: Option<MeshBuffer<u32>>,
+    pub instances: Option<Arc<CpuAccessibleBuffer<[InstanceData]>>>,
+    // Of the vertices in the mesh's own space, before any instance moves them. None without vertices
+    pub bounds: Option<Bounds>,
+    // The positions again in the same space, for pick. Empty for lines
+    pub triangles: Vec<[[f32; 3]; 3]>,
+}
+
+impl Mesh {
+    // Three vertices per triangle, for a triangle list
+    pub fn from_vertices(uploads: &mut UploadBatch, vertices: &[Vertex], usage: MeshUsage) -> Result<Mesh, BufferError> {
+        Mesh::from_primitives(uploads, vertices, usage, 3)
+    }
+
+    // Two vertices per line, for the LineList pipelines like the axes
+    pub fn from_lines(uploads: &mut UploadBatch, vertices: &[Vertex], usage: MeshUsage) -> Result<Mesh, BufferError> {
+        Mesh::from_primitives(uploads, vertices, usage, 2)
+    }
+
+    fn from_primitives(uploads: &mut UploadBatch, vertices: &[Vertex], usage: MeshUsage, per_primitive: usize)
+                       -> Result<Mesh, BufferError> {
+        if !vertices.len().is_multiple_of(per_primitive) {
+            return Err(BufferError::IncompletePrimitive { vertices: vertices.len(), per_primitive });
+        }
+
+        let triangles = if per_primitive == 3 {
+            vertices.chunks(3)
+                .map(|triangle| [triangle[0].position, triangle[1].position, triangle[2].position])
+                .collect()
+        } else {
+            vec!()
+        };
+
+        Ok(Mesh {
+            vertices: uploads.buffer(BufferUsage::vertex_buffer(), vertices, usage)?,
+            indices: None,
+            instances: None,
+            bounds: Bounds::from_positions(vertices.iter().map(|vertex| vertex.position)),
+            triangles,
+        })
+    }
+
+    pub fn from_indexed(uploads: &mut UploadBatch, vertices: &[Vertex], indices: &[u32], usage: MeshUsage)
+                        -> Result<Mesh, BufferError> {
+        let position = |index: u32| vertices[index as usize].position;
+        let triangles = indices.chunks_exact(3)
+            .map(|triangle| [position(triangle[0]), position(triangle[1]), position(triangle[2])])
+            .collect();
+
+        Ok(Mesh {
+            vertices: uploads.buffer(BufferUsage::vertex_buffer(), vertices, usage)?,
+            indices: Some(uploads.buffer(BufferUsage::index_buffer(), indices, usage)?),
+            instances: None,
+            bounds: Bounds::from_positions(vertices.iter().map(|vertex| vertex.position)),
+            triangles,
+        })
+    }
+
+    // The ray is in the mesh's own space, see pick_triangles. The instances aren't looked at,
+    // only the mesh itself
+    pub fn pick(&self, origin: Point3<f32>, direction: Vector3<f32>) -> Option<(f32, Vector3<f32>)> {
+        pick_triangles(&self.triangles, origin, direction)
+    }
+
+    pub fn with_instances(mut self, device: Arc<Device>, instances: &[InstanceData]) -> Result<Mesh, DeviceMemoryAllocError> {
+        self.instances = Some(create_buffer(device, instances)?);
+        Ok(self)
+    }
+
+    pub fn draw<Gp, S, Pc>(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        pipeline: Gp,
+        dynamic_state: &DynamicState,
+        sets: S,
+        constants: Pc,
+    ) -> Result<AutoCommandBufferBuilder, Box<dyn Error>>
+        where Gp: GraphicsPipelineAbstract + VertexSource<VertexBuffer> + Send + Sync + 'static + Clone,
+              S: DescriptorSetsCollection
+    {
+        let builder = match &self.indices {
+            Some(indices) => builder.draw_indexed(
+                pipeline,
+                dynamic_state,
+                self.vertices.access(),
+                indices.access(),
+                sets,
+                constants,
+            )?,
+            None => builder.draw(
+                pipeline,
+                dynamic_state,
+                self.vertices.access(),
+                sets,
+                constants,
+            )?,
+        };
+
+        Ok(builder)
+    }
+
+    // Panics if the mesh doesn't have instances
+    pub fn draw_instanced<Gp, S, Pc>(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        pipeline: Gp,
+        dynamic_state: &DynamicState,
+        sets: S,
+        constants: Pc,
+    ) -> Result<AutoCommandBufferBuilder, Box<dyn Error>>
+        where Gp: GraphicsPipelineAbstract + VertexSource<InstancedBuffers> + Send + Sync + 'static + Clone,
+              S: DescriptorSetsCollection
+    {
+        let buffers = (
+            self.vertices.access(),
+            self.instances.clone()
+                .expect("draw_instanced needs a mesh with instances"),
+        );
+
+        let builder = match &self.indices {
+            Some(indices) => builder.draw_indexed(
+                pipeline,
+                dynamic_state,
+                buffers,
+                indices.access(),
+                sets,
+                constants,
+            )?,
+            None => builder.draw(
+                pipeline,
+                dynamic_state,
+                buffers,
+                sets,
+                constants,
+            )?,
+        };
+
+        Ok(builder)
+    }
+}
+
+// How far along the ray the closest triangle it hits is and that triangle's normal, turned towards
+// where the ray came from
+pub fn pick_triangles(triangles: &[[[f32; 3]; 3]], origin: Point3<f32>, direction: Vector3<f32>)
+                      -> Option<(f32, Vector3<f32>)> {
+    let (distance, triangle) = triangles.iter()
+        .filter_map(|triangle| Some((ray_triangle(origin, direction, triangle)?, triangle)))
+        .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())?;
+
+    let normal = Vector3::from(flat_normal(triangle[0], triangle[1], triangle[2]));
+    let normal = if normal.dot(direction) > 0.0 { -normal } else { normal };
+    Some((distance, normal))
+}
+
+// Möller–Trumbore, how far along direction (in multiples of it) the ray goes through the triangle.
+// Both sides count, nothing culls
+pub fn ray_triangle(origin: Point3<f32>, direction: Vector3<f32>, triangle: &[[f32; 3]; 3]) -> Option<f32> {
+    let [a, b, c] = triangle.map(Point3::from);
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    // Parallel to the triangle
+    if determinant.abs() < 1e-8 {
+        return None;
+    }
+
+    let to_origin = origin - a;
+    let u = to_origin.dot(p) / determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = to_origin.cross(edge1);
+    let v = direction.dot(q) / determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = edge2.dot(q) / determinant;
+    if distance > 0.0 { Some(distance) } else { None }
+}
+
+// The box around every instance, with rotation applied to each of them before scaling and moving
+// like vert_instanced.glsl does
+pub fn instances_bounds(bounds: &Bounds, rotation: Matrix4<f32>, instances: &[InstanceData]) -> Option<Bounds> {
+    let rotated = bounds.transformed(rotation);
+    Bounds::from_positions(instances.iter().flat_map(|instance| {
+        let place = |corner: [f32; 3]| [
+            corner[0] * instance.scale + instance.offset[0],
+            corner[1] * instance.scale + instance.offset[1],
+            corner[2] * instance.scale,
+        ];
+        vec!(place(rotated.min), place(rotated.max))
+    }))
+}
+
+// count copies on a square grid filling the -1..1 square, each scaled down to fit its cell
+pub fn grid_instances(count: u32) -> Vec<InstanceData> {
+    let columns = (count as f32).sqrt().ceil().max(1.0) as u32;
+    let cell_size = 2.0 / columns as f32;
+
+    (0..count)
+        .map(|i| InstanceData {
+            offset: [
+                -1.0 + cell_size * ((i % columns) as f32 + 0.5),
+                -1.0 + cell_size * ((i / columns) as f32 + 0.5),
+            ],
+            scale: cell_size / 2.0,
+        })
+        .collect()
+}
+
+// Collects the copies for any number of static buffers into one command buffer on the graphics
+// queue, finish submits it and waits once for all of them. The buffers can't be drawn before
+// that. The transfer queue would need an ownership transfer on top
+pub struct UploadBatch {
+    queue: Arc<Queue>,
+    // Only None while a copy is being added
+    builder: Option<AutoCommandBufferBuilder>,
+    copies: usize,
+}
+
+impl UploadBatch {
+    pub fn new(queue: &Arc<Queue>) -> Result<UploadBatch, BufferError> {
+        Ok(UploadBatch {
+            queue: queue.clone(),
+            builder: Some(AutoCommandBufferBuilder::primary_one_time_submit(queue.device().clone(), queue.family())?),
+            copies: 0,
+        })
+    }
+
+    fn buffer<T>(&mut self, usage: BufferUsage, data: &[T], mesh_usage: MeshUsage) -> Result<MeshBuffer<T>, BufferError>
+        where T: Clone + Send + Sync + 'static
+    {
+        let device = self.queue.device().clone();
+        if mesh_usage == MeshUsage::Dynamic {
+            return Ok(MeshBuffer::Dynamic(CpuAccessibleBuffer::from_iter(device, usage, false, data.iter().cloned())?));
+        }
+
+        let staging = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::transfer_source(),
+            false,
+            data.iter()
+                .cloned(),
+        )?;
+        // Safe since the copy below fills all of it, and the buffer refuses to be used by the GPU
+        // until the command buffer with the copy has finished
+        let (buffer, initialization) = unsafe {
+            ImmutableBuffer::uninitialized_array(
+                device,
+                data.len(),
+                BufferUsage { transfer_destination: true, ..usage },
+            )?
+        };
+
+        let builder = self.builder.take()
+            .expect("the builder is always put back");
+        self.builder = Some(builder.copy_buffer(staging, initialization)?);
+        self.copies += 1;
+        Ok(MeshBuffer::Static(buffer))
+    }
+
+    pub fn finish(mut self) -> Result<(), BufferError> {
+        if self.copies == 0 {
+            return Ok(());
+        }
+
+        let command_buffer = self.builder.take()
+            .expect("the builder is always put back")
+            .build()?;
+        command_buffer.execute(self.queue.clone())?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+        Ok(())
+    }
+}
+
+fn create_buffer<T>(device: Arc<Device>, data: &[T]) -> Result<Arc<CpuAccessibleBuffer<[T]>>, DeviceMemoryAllocError>
+    where T: Clone + Send + Sync + 'static
+{
+    CpuAccessibleBuffer::from_iter(
+        device,
+        BufferUsage::all(),
+        false,
+        data.iter()
+            .cloned(),
+    )
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    NonTriangularFace { line: usize, vertex_count: usize },
+    Buffer(BufferError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "failed to read the OBJ file: {}", e),
+            LoadError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            LoadError::NonTriangularFace { line, vertex_count } =>
+                write!(f, "line {}: face has {} vertices, only triangulated meshes are supported", line, vertex_count),
+            LoadError::Buffer(e) => write!(f, "failed to create the vertex buffer: {}", e),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            LoadError::Buffer(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> LoadError {
+        LoadError::Io(e)
+    }
+}
+
+impl From<BufferError> for LoadError {
+    fn from(e: BufferError) -> LoadError {
+        LoadError::Buffer(e)
+    }
+}
+
+pub fn load_obj(path: &Path, uploads: &mut UploadBatch, usage: MeshUsage) -> Result<Mesh, LoadError> {
+    let source = fs::read_to_string(path)?;
+    let (vertices, indices) = parse_obj(&source)?;
+
+    Ok(Mesh::from_indexed(uploads, &vertices, &indices, usage)?)
+}
+
+// Only the bits we need: positions, texture coordinates, normals and triangle faces. Faces without
+// normals get a flat one computed from their corners. Corners that reference the same position,
+// texture coordinate and normal share a vertex, flat shaded corners can't be shared
+fn parse_obj(source: &str) -> Result<(Vec<Vertex>, Vec<u32>), LoadError> {
+    let mut positions = vec!();
+    let mut normals = vec!();
+    let mut texture_coordinates = vec!();
+    let mut vertices = vec!();
+    let mut indices = vec!();
+    let mut shared_vertices = HashMap::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut parts = line.split_whitespace();
+
+        match parts.next() {
+            Some("v") => positions.push(parse_vector(parts, line_number)?),
+            Some("vn") => normals.push(parse_vector(parts, line_number)?),
+            Some("vt") => texture_coordinates.push(parse_texture_coordinate(parts, line_number)?),
+            Some("f") => {
+                let corners = parts
+                    .map(|corner| {
+                        parse_corner(corner, positions.len(), texture_coordinates.len(), normals.len(), line_number)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if corners.len() != 3 {
+                    return Err(LoadError::NonTriangularFace { line: line_number, vertex_count: corners.len() });
+                }
+
+                let face_normal = flat_normal(
+                    positions[corners[0].0],
+                    positions[corners[1].0],
+                    positions[corners[2].0],
+                );
+
+                for (position, texture_coordinate, normal) in corners {
+                    let uv = texture_coordinate
+                        .map(|texture_coordinate| texture_coordinates[texture_coordinate])
+                        .unwrap_or([0.0, 0.0]);
+
+                    let index = match normal {
+                        Some(normal) => *shared_vertices.entry((position, texture_coordinate, normal))
+                            .or_insert_with(|| {
+                                vertices.push(Vertex {
+                                    position: positions[position],
+                                    normal: normals[normal],
+                                    uv,
+                                    color: OBJ_VERTEX_COLOR,
+                                });
+                                vertices.len() as u32 - 1
+                            }),
+                        None => {
+                            vertices.push(Vertex {
+                                position: positions[position],
+                                normal: face_normal,
+                                uv,
+                                color: OBJ_VERTEX_COLOR,
+                            });
+                            vertices.len() as u32 - 1
+                        }
+                    };
+                    indices.push(index);
+                }
+            }
+            _ => ()
+        }
+    }
+
+    Ok((vertices, indices))
+}
+
+fn parse_vector<'a>(parts: impl Iterator<Item=&'a str>, line: usize) -> Result<[f32; 3], LoadError> {
+    let values = parts
+        .take(3)
+        .map(|value| value.parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| LoadError::Parse { line, message: e.to_string() })?;
+
+    if values.len() != 3 {
+        return Err(LoadError::Parse { line, message: "expected 3 components".to_string() });
+    }
+
+    Ok([values[0], values[1], values[2]])
+}
+
+// OBJ puts v = 0 at the bottom of the image, Vulkan samples with v = 0 at the top. The optional
+// third component is for 3D textures which we don't have
+fn parse_texture_coordinate<'a>(parts: impl Iterator<Item=&'a str>, line: usize) -> Result<[f32; 2], LoadError> {
+    let values = parts
+        .take(2)
+        .map(|value| value.parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| LoadError::Parse { line, message: e.to_string() })?;
+
+    match values.as_slice() {
+        [u] => Ok([*u, 1.0]),
+        [u, v] => Ok([*u, 1.0 - v]),
+        _ => Err(LoadError::Parse { line, message: "expected at least 1 component".to_string() }),
+    }
+}
+
+// A face corner is v, v/vt, v//vn or v/vt/vn with 1-based (or negative, relative) indices
+fn parse_corner(corner: &str, position_count: usize, texture_coordinate_count: usize, normal_count: usize, line: usize)
+                -> Result<(usize, Option<usize>, Option<usize>), LoadError> {
+    let mut indices = corner.split('/');
+
+    let position = resolve_index(indices.next(), position_count, line)?
+        .ok_or_else(|| LoadError::Parse { line, message: format!("missing position index in \"{}\"", corner) })?;
+    let texture_coordinate = resolve_index(indices.next(), texture_coordinate_count, line)?;
+    let normal = resolve_index(indices.next(), normal_count, line)?;
+
+    Ok((position, texture_coordinate, normal))
+}
+
+fn resolve_index(index: Option<&str>, count: usize, line: usize) -> Result<Option<usize>, LoadError> {
+    let index = match index {
+        Some(index) if !index.is_empty() => index,
+        _ => return Ok(None),
+    };
+
+    let index = index.parse::<i64>()
+        .map_err(|e| LoadError::Parse { line, message: e.to_string() })?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(LoadError::Parse { line, message: format!("index {} out of range", index) });
+    }
+
+    Ok(Some(resolved as usize))
+}
+
+fn flat_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
+    let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let n = [
+        u[1] * v[2] - u[2] * v[1],
+        u[2] * v[0] - u[0] * v[2],
+        u[0] * v[1] - u[1] * v[0],
+    ];
+
+    let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    if length == 0.0 {
+        return [0.0, 0.0, 1.0];
+    }
+
+    [n[0] / length, n[1] / length, n[2] / length]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: &str = "\
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 0.0 1.0 0.0
+vt 0.0 0.0
+vt 1.0 0.25
+vt 0.0 1.0
+vn 0.0 0.0 1.0
+";
+
+    fn positions(vertices: &[Vertex]) -> Vec<[f32; 3]> {
+        vertices.iter().map(|vertex| vertex.position).collect()
+    }
+
+    #[test]
+    fn position_and_normal_corners() {
+        let source = format!("{}f 1//1 2//1 3//1\nf 3//1 2//1 1//1\n", TRIANGLE);
+        let (vertices, indices) = parse_obj(&source).unwrap();
+
+        // The second face reuses the corners of the first
+        assert_eq!(positions(&vertices), vec!([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]));
+        assert!(vertices.iter().all(|vertex| vertex.normal == [0.0, 0.0, 1.0]));
+        assert!(vertices.iter().all(|vertex| vertex.uv == [0.0, 0.0]));
+        assert_eq!(indices, vec!(0, 1, 2, 2, 1, 0));
+    }
+
+    #[test]
+    fn full_corners() {
+        let source = format!("{}f 1/1/1 2/2/1 3/3/1\n", TRIANGLE);
+        let (vertices, indices) = parse_obj(&source).unwrap();
+
+        assert_eq!(positions(&vertices), vec!([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]));
+        assert!(vertices.iter().all(|vertex| vertex.normal == [0.0, 0.0, 1.0]));
+        // Flipped to Vulkan's v = 0 at the top
+        assert_eq!(vertices[1].uv, [1.0, 0.75]);
+        assert_eq!(indices, vec!(0, 1, 2));
+    }
+
+    #[test]
+    fn negative_indices() {
+        let source = format!("{}f -3/-3/-1 -2/-2/-1 -1/-1/-1\n", TRIANGLE);
+        let (vertices, _) = parse_obj(&source).unwrap();
+
+        assert_eq!(positions(&vertices), vec!([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]));
+        assert_eq!(vertices[2].uv, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn flat_normals_without_vn() {
+        let source = format!("{}f 1 2 3\n", TRIANGLE);
+        let (vertices, _) = parse_obj(&source).unwrap();
+
+        assert!(vertices.iter().all(|vertex| vertex.normal == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn out_of_range_indices() {
+        for face in &["f 1//1 2//1 4//1", "f 1//1 2//2 3//1", "f 0 1 2", "f -4 1 2"] {
+            let source = format!("{}{}\n", TRIANGLE, face);
+            match parse_obj(&source) {
+                Err(LoadError::Parse { line, message }) => {
+                    assert_eq!(line, 8);
+                    assert!(message.contains("out of range"), "{}: {}", face, message);
+                }
+                other => panic!("{}: expected a parse error, got {:?}", face, other),
+            }
+        }
+    }
+
+    #[test]
+    fn bounds_of_positions() {
+        let bounds = Bounds::from_positions(vec!([1.0, -2.0, 0.5], [-1.0, 3.0, 0.0], [0.0, 0.0, 2.0]))
+            .unwrap();
+        assert_eq!(bounds, Bounds { min: [-1.0, -2.0, 0.0], max: [1.0, 3.0, 2.0] });
+        assert_eq!(Bounds::from_positions(vec!()), None);
+    }
+
+    #[test]
+    fn transformed_bounds_stay_axis_aligned() {
+        let bounds = Bounds { min: [0.0, 0.0, 0.0], max: [2.0, 1.0, 1.0] };
+        let rotated = bounds.transformed(Matrix4::from_angle_z(cgmath::Deg(90.0)));
+        let expected = Bounds { min: [-1.0, 0.0, 0.0], max: [0.0, 2.0, 1.0] };
+        for axis in 0..3 {
+            assert!((rotated.min[axis] - expected.min[axis]).abs() < 1e-5, "{:?}", rotated);
+            assert!((rotated.max[axis] - expected.max[axis]).abs() < 1e-5, "{:?}", rotated);
+        }
+    }
+
+    #[test]
+    fn box_has_twelve_edges_along_the_axes() {
+        let bounds = Bounds { min: [0.0, 0.0, 0.0], max: [1.0, 1.0, 1.0] };
+        let lines = box_lines(&bounds.corners(), [1.0, 1.0, 0.0]);
+        assert_eq!(lines.len(), 24);
+        for edge in lines.chunks(2) {
+            let changed = (0..3)
+                .filter(|&axis| edge[0].position[axis] != edge[1].position[axis])
+                .count();
+            assert_eq!(changed, 1);
+        }
+    }
+
+    #[test]
+    fn instances_bounds_cover_every_instance() {
+        let bounds = Bounds { min: [-1.0, -1.0, 0.0], max: [1.0, 1.0, 0.0] };
+        let instances = grid_instances(4);
+        let all = instances_bounds(&bounds, Matrix4::from_scale(1.0), &instances)
+            .unwrap();
+        assert_eq!(all, Bounds { min: [-1.0, -1.0, 0.0], max: [1.0, 1.0, 0.0] });
+    }
+
+    #[test]
+    fn non_triangular_face() {
+        let source = format!("{}v 1.0 1.0 0.0\nf 1 2 4 3\n", TRIANGLE);
+        match parse_obj(&source) {
+            Err(LoadError::NonTriangularFace { line, vertex_count }) => {
+                assert_eq!(line, 9);
+                assert_eq!(vertex_count, 4);
+            }
+            other => panic!("expected a non-triangular face error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ray_hits_the_triangle_from_either_side() {
+        let triangle = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        let front = ray_triangle(Point3::new(0.25, 0.25, 2.0), -Vector3::unit_z(), &triangle);
+        let back = ray_triangle(Point3::new(0.25, 0.25, -1.0), Vector3::unit_z() * 2.0, &triangle);
+        assert_eq!(front, Some(2.0));
+        assert_eq!(back, Some(0.5));
+    }
+
+    #[test]
+    fn ray_misses_the_triangle() {
+        let triangle = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        // Past the long edge, pointing away and parallel
+        assert_eq!(ray_triangle(Point3::new(0.75, 0.75, 1.0), -Vector3::unit_z(), &triangle), None);
+        assert_eq!(ray_triangle(Point3::new(0.25, 0.25, 1.0), Vector3::unit_z(), &triangle), None);
+        assert_eq!(ray_triangle(Point3::new(0.0, 0.0, 1.0), Vector3::unit_x(), &triangle), None);
+    }
+
+    #[test]
+    fn pick_finds_the_closest_triangle() {
+        let near = [[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [0.0, 1.0, 1.0]];
+        let far = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        let (distance, normal) = pick_triangles(&[far, near], Point3::new(0.25, 0.25, 3.0), -Vector3::unit_z())
+            .unwrap();
+        assert_eq!(distance, 2.0);
+        assert_eq!(normal, Vector3::unit_z());
+
+        // From below the normal flips
+        let (_, normal) = pick_triangles(&[far, near], Point3::new(0.25, 0.25, -1.0), Vector3::unit_z())
+            .unwrap();
+        assert_eq!(normal, -Vector3::unit_z());
+    }
+
+    #[test]
+    fn grid_instances_fill_the_square() {
+        assert!(grid_instances(0).is_empty());
+
+        let single = grid_instances(1);
+        assert_eq!(single[0].offset, [0.0, 0.0]);
+        assert_eq!(single[0].scale, 1.0);
+
+        // 3 columns for 7, the last row only partly filled
+        let instances = grid_instances(7);
+        assert_eq!(instances.len(), 7);
+        let cell_size = 2.0 / 3.0;
+        assert!(instances.iter().all(|instance| (instance.scale - cell_size / 2.0).abs() < 1e-6));
+        assert!((instances[0].offset[0] - (-1.0 + cell_size / 2.0)).abs() < 1e-6);
+        assert!((instances[6].offset[1] - (1.0 - cell_size / 2.0)).abs() < 1e-6);
+        // Every instance stays inside -1..1
+        assert!(instances.iter().all(|instance| {
+            instance.offset.iter().all(|offset| offset.abs() + instance.scale <= 1.0 + 1e-6)
+        }));
+    }
+}
@@ -0,0 +1,447 @@
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::{DescriptorSet, PipelineLayoutAbstract};
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::format::{ClearValue, Format};
+use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract};
+use vulkano::image::{AttachmentImage, ImageUsage, SwapchainImage};
+use vulkano::pipeline::vertex::{BufferlessDefinition, BufferlessVertices};
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+use winit::window::Window;
+
+use std::sync::Arc;
+
+use crate::color::is_srgb;
+use crate::texture::{create_sampler, SamplerConfig};
+use crate::vulkan::error::InitError;
+use crate::vulkan::initialization::full_viewport;
+use crate::vulkan::pipeline::{BuiltPipeline, PipelineBuilder};
+use crate::vulkan::shader::{load_shader, ShaderRequirements};
+
+// Each effect is just a fragment shader that samples the scene, see post_grayscale.glsl. Adding one
+// means a new shader (with the same push constants), a variant here and a line in PostPass::new
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostEffect {
+    Grayscale,
+    Invert,
+    // Leaves the scene alone, for when there's only a ColorMode to apply
+    Passthrough,
+}
+
+impl PostEffect {
+    pub fn from_arg(arg: &str) -> Option<PostEffect> {
+        match arg {
+            "grayscale" => Some(PostEffect::Grayscale),
+            "invert" => Some(PostEffect::Invert),
+            _ => None,
+        }
+    }
+}
+
+// A color matrix every effect applies last, to preview the scene the way people with color
+// vision deficiencies (or a sepia photo) would see it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    Normal,
+    Grayscale,
+    Sepia,
+    HighContrast,
+    Protanopia,
+    Deuteranopia,
+}
+
+// P cycles through them in this order
+pub const COLOR_MODES: [ColorMode; 6] = [
+    ColorMode::Normal,
+    ColorMode::Grayscale,
+    ColorMode::Sepia,
+    ColorMode::HighContrast,
+    ColorMode::Protanopia,
+    ColorMode::Deuteranopia,
+];
+
+// How much HighContrast stretches the colors away from the middle
+const HIGH_CONTRAST: f32 = 2.0;
+
+impl ColorMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorMode::Normal => "normal",
+            ColorMode::Grayscale => "grayscale",
+            ColorMode::Sepia => "sepia",
+            ColorMode::HighContrast => "high contrast",
+            ColorMode::Protanopia => "protanopia",
+            ColorMode::Deuteranopia => "deuteranopia",
+        }
+    }
+
+    // Rows of a 3x3 matrix on linear RGB plus what gets added after it, as the column major mat4 the
+    // shaders take. The deficiency ones are Machado et al. 2009 at full severity
+    pub fn matrix(&self) -> [[f32; 4]; 4] {
+        let (rows, offset) = match self {
+            ColorMode::Normal => ([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]], 0.0),
+            // Rec. 709 luma, same as post_grayscale.glsl
+            ColorMode::Grayscale => ([[0.2126, 0.7152, 0.0722]; 3], 0.0),
+            ColorMode::Sepia => ([
+                [0.393, 0.769, 0.189],
+                [0.349, 0.686, 0.168],
+                [0.272, 0.534, 0.131],
+            ], 0.0),
+            ColorMode::HighContrast => (
+                [[HIGH_CONTRAST, 0.0, 0.0], [0.0, HIGH_CONTRAST, 0.0], [0.0, 0.0, HIGH_CONTRAST]],
+                0.5 - 0.5 * HIGH_CONTRAST,
+            ),
+            ColorMode::Protanopia => ([
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ], 0.0),
+            ColorMode::Deuteranopia => ([
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ], 0.0),
+        };
+
+        let mut matrix = [[0.0; 4]; 4];
+        for (column, values) in matrix.iter_mut().take(3).enumerate() {
+            for (row, value) in values.iter_mut().take(3).enumerate() {
+                *value = rows[row][column];
+            }
+        }
+        matrix[3] = [offset, offset, offset, 1.0];
+        matrix
+    }
+}
+
+// Tiles of this many pixels per side for the tile views, same as TILE_SIZE in post_depth_complexity.glsl
+pub const DEPTH_COMPLEXITY_TILE_SIZE: u32 = 16;
+
+// Replaces the effect while the scene is drawn with Scene::overdraw. The numbers are what
+// post_depth_complexity.glsl gets as push.view
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DepthComplexityView {
+    // How many fragments landed on each pixel
+    Pixels = 0,
+    TileAverage = 1,
+    TileMax = 2,
+}
+
+impl DepthComplexityView {
+    pub fn from_arg(arg: &str) -> Option<DepthComplexityView> {
+        match arg {
+            "pixels" => Some(DepthComplexityView::Pixels),
+            "average" => Some(DepthComplexityView::TileAverage),
+            "max" => Some(DepthComplexityView::TileMax),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DepthComplexityView::Pixels => "per pixel",
+            DepthComplexityView::TileAverage => "tile average",
+            DepthComplexityView::TileMax => "tile max",
+        }
+    }
+}
+
+// The scene gets rendered into an offscreen image instead of the swapchain image, this then draws
+// a fullscreen triangle into the swapchain image that samples it through the effect shader
+pub struct PostPass {
+    effect: PostEffect,
+    pub color_mode: ColorMode,
+    // Used instead of the effect while set
+    pub depth_complexity: Option<DepthComplexityView>,
+    // Ordered dithering over the effect's result against banding, in 8 bit steps. 0 is off, 1 is
+    // enough to hide the bands without the pattern showing. Not applied to the depth complexity views
+    pub dither: f32,
+    // The dithering has to happen after the sRGB encoding the attachment does
+    srgb: bool,
+    // How much of the scene from before the last capture_previous is mixed in, 1 is only that. Some
+    // for as long as a demo reel runs, which fades it down to 0 after every switch
+    pub crossfade: Option<f32>,
+    capture_requested: bool,
+    // The image the scene renders into and the copy capture_previous takes of it, set by resize
+    images: Option<(Arc<AttachmentImage>, Arc<AttachmentImage>)>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    pipeline: BuiltPipeline<BufferlessDefinition>,
+    depth_complexity_pipeline: BuiltPipeline<BufferlessDefinition>,
+    sampler: Arc<Sampler>,
+    dynamic_state: DynamicState,
+    // Set by resize
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    set: Option<Arc<dyn DescriptorSet + Send + Sync>>,
+}
+
+impl PostPass {
+    // resize has to be called before the first record
+    pub fn new(device: &Arc<Device>, format: Format, effect: PostEffect) -> Result<PostPass, InitError> {
+        // Every pixel gets overwritten, nothing to load or clear
+        let render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: format,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )?
+        ) as Arc<dyn RenderPassAbstract + Send + Sync>;
+
+        // Plain fullscreen sampling, nothing past the Shader capability
+        let vs = load_shader(device, "post_vert.glsl", ShaderRequirements::none(), || post_vs::Shader::load(device.clone()))?;
+        let builder = PipelineBuilder::new(&render_pass);
+        let pipeline = match effect {
+            PostEffect::Grayscale => {
+                let fs = load_shader(device, "post_grayscale.glsl", ShaderRequirements::none(), || post_grayscale::Shader::load(device.clone()))?;
+                builder.build(device, BufferlessDefinition, vs.main_entry_point(), fs.main_entry_point())
+            }
+            PostEffect::Invert => {
+                let fs = load_shader(device, "post_invert.glsl", ShaderRequirements::none(), || post_invert::Shader::load(device.clone()))?;
+                builder.build(device, BufferlessDefinition, vs.main_entry_point(), fs.main_entry_point())
+            }
+            PostEffect::Passthrough => {
+                let fs = load_shader(device, "post_passthrough.glsl", ShaderRequirements::none(), || post_passthrough::Shader::load(device.clone()))?;
+                builder.build(device, BufferlessDefinition, vs.main_entry_point(), fs.main_entry_point())
+            }
+        }?;
+        let fs_depth_complexity = load_shader(device, "post_depth_complexity.glsl", ShaderRequirements::none(), || post_depth_complexity::Shader::load(device.clone()))?;
+        let depth_complexity_pipeline = builder.build(device, BufferlessDefinition, vs.main_entry_point(), fs_depth_complexity.main_entry_point())?;
+
+        // Same size as the screen so every pixel maps to exactly one texel, clamped so the edges
+        // don't pick up the other side
+        let sampler = create_sampler(device, &SamplerConfig {
+            mag_filter: Filter::Nearest,
+            min_filter: Filter::Nearest,
+            mipmap_mode: MipmapMode::Nearest,
+            address_mode: SamplerAddressMode::ClampToEdge,
+        })
+            .unwrap();
+
+        let dynamic_state = DynamicState {
+            line_width: None,
+            viewports: None,
+            scissors: None,
+            compare_mask: None,
+            write_mask: None,
+            reference: None,
+        };
+
+        Ok(PostPass {
+            effect,
+            color_mode: ColorMode::Normal,
+            depth_complexity: None,
+            dither: 0.0,
+            srgb: is_srgb(format),
+            crossfade: None,
+            capture_requested: false,
+            images: None,
+            render_pass,
+            pipeline,
+            depth_complexity_pipeline,
+            sampler,
+            dynamic_state,
+            framebuffers: vec!(),
+            set: None,
+        })
+    }
+
+    pub fn effect(&self) -> PostEffect {
+        self.effect
+    }
+
+    // Returns the new image for the scene to render into, in the swapchain format and at the
+    // swapchain size. Any effect that blurs or distorts could sample it at a different size as well
+    pub fn resize(&mut self, device: &Arc<Device>, images: &[Arc<SwapchainImage<Arc<Window>>>]) -> Arc<AttachmentImage> {
+        let dimensions = images[0].dimensions();
+        let format = images[0].swapchain().format();
+        let scene_image = AttachmentImage::with_usage(device.clone(), dimensions, format, ImageUsage {
+            sampled: true,
+            transfer_source: true,
+            ..ImageUsage::none()
+        })
+            .unwrap();
+        // Sampled every frame, it only matters while crossfade is above 0
+        let previous_image = AttachmentImage::with_usage(device.clone(), dimensions, format, ImageUsage {
+            sampled: true,
+            transfer_destination: true,
+            ..ImageUsage::none()
+        })
+            .unwrap();
+
+        self.dynamic_state.viewports = Some(vec!(full_viewport(dimensions)));
+
+        self.framebuffers = images.iter()
+            .map(|image| {
+                Arc::new(
+                    Framebuffer::start(self.render_pass.clone())
+                        .add(image.clone())
+                        .unwrap()
+                        .build()
+                        .unwrap()
+                ) as Arc<dyn FramebufferAbstract + Send + Sync>
+            })
+            .collect();
+
+        self.set = Some(Arc::new(
+            PersistentDescriptorSet::start(
+                self.pipeline.descriptor_set_layout(0).unwrap().clone()
+            )
+                .add_sampled_image(scene_image.clone(), self.sampler.clone())
+                .unwrap()
+                .add_sampled_image(previous_image.clone(), self.sampler.clone())
+                .unwrap()
+                .build()
+                .unwrap()
+        ));
+        self.images = Some((scene_image.clone(), previous_image));
+
+        scene_image
+    }
+
+    // The next frame starts by copying whatever the scene image still has from the last one, which
+    // crossfade then fades out of. Right after a resize that's nothing
+    pub fn capture_previous(&mut self) {
+        self.capture_requested = true;
+    }
+
+    // Before the scene's render pass, which clears the image
+    pub fn record_capture(&mut self, builder: AutoCommandBufferBuilder) -> AutoCommandBufferBuilder {
+        if !self.capture_requested {
+            return builder;
+        }
+        self.capture_requested = false;
+        let (scene_image, previous_image) = self.images.clone().expect("PostPass::resize has to be called before recording");
+        let [width, height] = scene_image.dimensions();
+        builder.copy_image(scene_image, [0, 0, 0], 0, 0, previous_image, [0, 0, 0], 0, 0, [width, height, 1], 1)
+            .unwrap()
+    }
+
+    // After the scene's render pass. vulkano puts the barrier between it writing the image and this
+    // sampling it in by itself
+    pub fn record(&self, builder: AutoCommandBufferBuilder, image_index: usize) -> AutoCommandBufferBuilder {
+        let set = self.set.clone().expect("PostPass::resize has to be called before recording");
+        let vertices = BufferlessVertices { vertices: 3, instances: 1 };
+        let builder = builder
+            .begin_render_pass(self.framebuffers[image_index].clone(), false, vec!(ClearValue::None))
+            .unwrap();
+
+        let builder = match self.depth_complexity {
+            Some(view) => {
+                let push_constants = post_depth_complexity::ty::PushConstants {
+                    color_matrix: self.color_mode.matrix(),
+                    view: view as u32,
+                    crossfade: self.crossfade.unwrap_or(0.0),
+                };
+                builder.draw(self.depth_complexity_pipeline.clone(), &self.dynamic_state, vertices, set, push_constants)
+            }
+            None => {
+                // Every effect shader has the same push constants
+                let push_constants = post_passthrough::ty::PushConstants {
+                    color_matrix: self.color_mode.matrix(),
+                    dither: self.dither,
+                    srgb: self.srgb as u32,
+                    crossfade: self.crossfade.unwrap_or(0.0),
+                };
+                builder.draw(self.pipeline.clone(), &self.dynamic_state, vertices, set, push_constants)
+            }
+        };
+
+        builder
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+    }
+}
+
+mod post_vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path: "src/post_vert.glsl"
+    }
+}
+
+mod post_grayscale {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/post_grayscale.glsl"
+    }
+}
+
+mod post_invert {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/post_invert.glsl"
+    }
+}
+
+mod post_passthrough {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/post_passthrough.glsl"
+    }
+}
+
+mod post_depth_complexity {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/post_depth_complexity.glsl"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(matrix: [[f32; 4]; 4], color: [f32; 3]) -> [f32; 3] {
+        let mut result = [0.0; 3];
+        for (row, value) in result.iter_mut().enumerate() {
+            *value = matrix[3][row] + (0..3).map(|column| matrix[column][row] * color[column]).sum::<f32>();
+        }
+        result
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        assert!(a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-3), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn normal_changes_nothing() {
+        assert_close(apply(ColorMode::Normal.matrix(), [0.2, 0.5, 0.9]), [0.2, 0.5, 0.9]);
+    }
+
+    #[test]
+    fn white_and_black_stay_gray() {
+        // Sepia tints white and the contrast pushes it out of range, everything else keeps it
+        for mode in &[ColorMode::Normal, ColorMode::Grayscale, ColorMode::Protanopia, ColorMode::Deuteranopia] {
+            assert_close(apply(mode.matrix(), [1.0, 1.0, 1.0]), [1.0, 1.0, 1.0]);
+            assert_close(apply(mode.matrix(), [0.0, 0.0, 0.0]), [0.0, 0.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn grayscale_is_luma() {
+        let [r, g, b] = apply(ColorMode::Grayscale.matrix(), [1.0, 0.0, 0.0]);
+        assert_close([r, g, b], [0.2126; 3]);
+    }
+
+    #[test]
+    fn high_contrast_keeps_the_middle() {
+        assert_close(apply(ColorMode::HighContrast.matrix(), [0.5, 0.5, 0.5]), [0.5, 0.5, 0.5]);
+        assert_close(apply(ColorMode::HighContrast.matrix(), [0.75, 0.25, 0.5]), [1.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn sepia_is_warm() {
+        let [r, g, b] = apply(ColorMode::Sepia.matrix(), [0.5, 0.5, 0.5]);
+        assert!(r > g && g > b, "{} {} {}", r, g, b);
+    }
+}
@@ -0,0 +1,84 @@
+#version 460
+
+layout(location = 0) in vec2 v_uv;
+layout(location = 0) out vec4 f_color;
+// What frag_overdraw.glsl accumulated
+layout(set = 0, binding = 0) uniform sampler2D scene;
+// Same as the other effects, what frag_overdraw.glsl accumulated before the demo reel's last switch
+layout(set = 0, binding = 1) uniform sampler2D previous;
+// color_matrix and crossfade like every other effect, view is DepthComplexityView as a number
+layout(push_constant) uniform PushConstants {
+    mat4 color_matrix;
+    uint view;
+    float crossfade;
+} push;
+
+// Same as frag_overdraw.glsl, 32 layers fill the image completely
+const float LAYER_STEP = 1.0 / 32.0;
+// Pixels per side, same as DEPTH_COMPLEXITY_TILE_SIZE in post.rs
+const int TILE_SIZE = 16;
+// Where the heat ramp ends up red
+const float MAX_LAYERS = 16.0;
+
+const uint VIEW_PIXELS = 0;
+const uint VIEW_TILE_AVERAGE = 1;
+
+float layers(sampler2D image, ivec2 texel) {
+    // An sRGB image stores the sum rounded to its own steps, rounding gets rid of the error
+    return round(texelFetch(image, texel, 0).r / LAYER_STEP);
+}
+
+// Black for nothing, then blue, cyan, green, yellow and red
+vec3 heat(float layers) {
+    if (layers < 0.5) {
+        return vec3(0.0);
+    }
+    float t = clamp((layers - 1.0) / (MAX_LAYERS - 1.0), 0.0, 1.0) * 4.0;
+    vec3 ramp[5] = vec3[](
+        vec3(0.0, 0.0, 1.0),
+        vec3(0.0, 1.0, 1.0),
+        vec3(0.0, 1.0, 0.0),
+        vec3(1.0, 1.0, 0.0),
+        vec3(1.0, 0.0, 0.0)
+    );
+    int index = min(int(t), 3);
+    return mix(ramp[index], ramp[index + 1], t - float(index));
+}
+
+vec3 shade(sampler2D image, ivec2 texel) {
+    if (push.view == VIEW_PIXELS) {
+        return heat(layers(image, texel));
+    } else {
+        // Every pixel of a tile goes through the same texels and comes up with the same number
+        ivec2 size = textureSize(image, 0);
+        ivec2 origin = texel / TILE_SIZE * TILE_SIZE;
+        ivec2 end = min(origin + TILE_SIZE, size);
+        float total = 0.0;
+        float maximum = 0.0;
+        for (int y = origin.y; y < end.y; y++) {
+            for (int x = origin.x; x < end.x; x++) {
+                float value = layers(image, ivec2(x, y));
+                total += value;
+                maximum = max(maximum, value);
+            }
+        }
+        ivec2 tile = end - origin;
+        vec3 color = heat(push.view == VIEW_TILE_AVERAGE ? total / float(tile.x * tile.y) : maximum);
+
+        // The grid, darker lines along the top and left edge of every tile
+        if (texel.x == origin.x || texel.y == origin.y) {
+            color = color * 0.5 + 0.1;
+        }
+        return color;
+    }
+}
+
+void main() {
+    ivec2 texel = ivec2(gl_FragCoord.xy);
+    vec3 color = shade(scene, texel);
+    // The tile views go through a lot of texels, the previous image only gets them while it shows
+    if (push.crossfade > 0.0) {
+        color = mix(color, shade(previous, texel), push.crossfade);
+    }
+    f_color = vec4((push.color_matrix * vec4(color, 1.0)).rgb, 1.0);
+}
@@ -0,0 +1,31 @@
+// Included by the effect shaders, not a shader by itself. Written out, 8 bits per channel can only
+// show so many steps and a smooth gradient turns into bands. Nudging every pixel by a fraction of
+// a step, in a 4x4 ordered (Bayer) pattern, makes the bands blend into each other
+
+// Every threshold once, neighbours as far apart as they can be
+const float BAYER[16] = float[](
+     0.0,  8.0,  2.0, 10.0,
+    12.0,  4.0, 14.0,  6.0,
+     3.0, 11.0,  1.0,  9.0,
+    15.0,  7.0, 13.0,  5.0
+);
+const float STEPS = 255.0;
+
+vec3 linear_to_srgb(vec3 color) {
+    return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, greaterThan(color, vec3(0.0031308)));
+}
+
+vec3 srgb_to_linear(vec3 color) {
+    return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), greaterThan(color, vec3(0.04045)));
+}
+
+// strength is in steps, at 1 a pixel moves anywhere from half a step down to half a step up. An
+// sRGB attachment encodes what gets written, its steps are even after the encoding so that's
+// where the pattern has to go
+vec3 dither(vec3 color, float strength, bool srgb) {
+    ivec2 cell = ivec2(gl_FragCoord.xy) % 4;
+    float offset = ((BAYER[cell.y * 4 + cell.x] + 0.5) / 16.0 - 0.5) * strength / STEPS;
+    vec3 encoded = srgb ? linear_to_srgb(max(color, 0.0)) : color;
+    encoded = clamp(encoded + offset, 0.0, 1.0);
+    return srgb ? srgb_to_linear(encoded) : encoded;
+}
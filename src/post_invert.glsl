@@ -0,0 +1,23 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+layout(location = 0) in vec2 v_uv;
+layout(location = 0) out vec4 f_color;
+layout(set = 0, binding = 0) uniform sampler2D scene;
+// The scene from before the demo reel's last switch, crossfade says how much of it is left
+layout(set = 0, binding = 1) uniform sampler2D previous;
+// ColorMode::matrix, applied to whatever the effect comes up with
+layout(push_constant) uniform PushConstants {
+    mat4 color_matrix;
+    float dither;
+    uint srgb;
+    float crossfade;
+} push;
+
+#include "post_dither.glsl"
+
+void main() {
+    vec3 color = 1.0 - mix(texture(scene, v_uv).rgb, texture(previous, v_uv).rgb, push.crossfade);
+    vec3 mapped = (push.color_matrix * vec4(color, 1.0)).rgb;
+    f_color = vec4(dither(mapped, push.dither, push.srgb != 0), 1.0);
+}
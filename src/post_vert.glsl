@@ -0,0 +1,10 @@
+#version 460
+
+layout(location = 0) out vec2 v_uv;
+
+// One triangle that covers the whole screen, the parts outside get clipped
+void main() {
+    vec2 position = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+    v_uv = position * 0.5 + 0.5;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
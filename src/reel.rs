@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+
+use crate::mesh::Mesh;
+use crate::renderer::Scene;
+
+// How long the fade into the next scene takes, at most the interval
+const CROSSFADE_SECONDS: f32 = 1.0;
+
+// When to switch and how far the fade is, apart from the meshes so it can be tested without a device
+#[derive(Debug, Clone, PartialEq)]
+struct ReelTimer {
+    // Seconds per scene
+    interval: f32,
+    // Since the last switch
+    time: f32,
+    // Nothing to fade from before the first switch
+    switched: bool,
+}
+
+impl ReelTimer {
+    // True when it's time for the next scene, a long frame never skips one
+    fn advance(&mut self, dt: f32) -> bool {
+        self.time += dt;
+        if self.time < self.interval {
+            return false;
+        }
+        self.time = (self.time - self.interval).min(self.interval);
+        self.switched = true;
+        true
+    }
+
+    // 1 right after a switch down to 0 once the fade is done, PostPass::crossfade
+    fn crossfade(&self) -> f32 {
+        if !self.switched {
+            return 0.0;
+        }
+        let duration = CROSSFADE_SECONDS.min(self.interval);
+        (1.0 - self.time / duration).max(0.0)
+    }
+}
+
+// --demo-reel 10 cycles the first mesh through the built-in scenes every 10 seconds, fading over
+// from the one before in the post pass. It goes by real time, pausing only stops the rotation
+pub struct DemoReel {
+    timer: ReelTimer,
+    current: String,
+    // Waiting for their turn, the one that gets switched away from goes to the back
+    upcoming: VecDeque<(String, Mesh)>,
+}
+
+impl DemoReel {
+    // current is the name of the mesh the scene starts with, scenes the ones it goes through after
+    pub fn new(interval: f32, current: String, scenes: Vec<(String, Mesh)>) -> DemoReel {
+        DemoReel {
+            timer: ReelTimer { interval, time: 0.0, switched: false },
+            current,
+            upcoming: scenes.into_iter().collect(),
+        }
+    }
+
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    // Swaps the scene's first mesh for the next one once it's time, true when it did. Every
+    // window's post pass has to capture what it showed before the frame with the new one
+    pub fn advance(&mut self, dt: f32, scene: &mut Scene) -> bool {
+        if !self.timer.advance(dt) {
+            return false;
+        }
+        let (name, mesh) = match self.upcoming.pop_front() {
+            Some(next) => next,
+            None => return false,
+        };
+        let previous = scene.replace_mesh(mesh);
+        self.upcoming.push_back((std::mem::replace(&mut self.current, name), previous));
+        true
+    }
+
+    pub fn crossfade(&self) -> f32 {
+        self.timer.crossfade()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switches_every_interval() {
+        let mut timer = ReelTimer { interval: 10.0, time: 0.0, switched: false };
+        assert!(!timer.advance(9.0));
+        assert!(timer.advance(1.5));
+        assert_eq!(timer.time, 0.5);
+        // Way too long, the next one still gets its time
+        assert!(timer.advance(100.0));
+        assert_eq!(timer.time, 10.0);
+    }
+
+    #[test]
+    fn fades_after_a_switch() {
+        let mut timer = ReelTimer { interval: 10.0, time: 0.0, switched: false };
+        assert_eq!(timer.crossfade(), 0.0);
+        timer.advance(10.0);
+        assert_eq!(timer.crossfade(), 1.0);
+        timer.advance(CROSSFADE_SECONDS / 2.0);
+        assert_eq!(timer.crossfade(), 0.5);
+        timer.advance(CROSSFADE_SECONDS);
+        assert_eq!(timer.crossfade(), 0.0);
+    }
+
+    #[test]
+    fn short_interval_still_fades_out() {
+        let timer = ReelTimer { interval: 0.5, time: 0.5, switched: true };
+        assert_eq!(timer.crossfade(), 0.0);
+    }
+}
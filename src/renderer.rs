@@ -0,0 +1,2562 @@
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, CpuBufferPool};
+use vulkano::command_buffer::{AutoCommandBuffer, AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::{DescriptorSet, PipelineLayoutAbstract};
+use vulkano::descriptor::descriptor::ShaderStages;
+use vulkano::descriptor::descriptor_set::{FixedSizeDescriptorSetsPool, PersistentDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::format::{ClearValue, FormatTy};
+use vulkano::framebuffer::{FramebufferAbstract, LoadOp, RenderPassAbstract, RenderPassDesc, Subpass};
+use vulkano::pipeline::blend::{AttachmentBlend, BlendFactor, BlendOp};
+use vulkano::pipeline::depth_stencil::{Compare, DepthStencil, DynamicStencilValue, Stencil, StencilFaceFlags, StencilOp};
+use vulkano::pipeline::input_assembly::PrimitiveTopology;
+use vulkano::pipeline::vertex::{BufferlessDefinition, BufferlessVertices, OneVertexOneInstanceDefinition,
+                                SingleBufferDefinition};
+use vulkano::pipeline::shader::{GraphicsEntryPoint, GraphicsShaderType, ShaderModule};
+use vulkano::image::{AttachmentImage, SwapchainImage};
+use vulkano::swapchain::{self, AcquireError, FullscreenExclusive, PresentMode, Surface, Swapchain,
+                         SwapchainCreationError};
+use vulkano::sync::{self, FenceSignalFuture, FlushError, GpuFuture};
+use winit::window::{Fullscreen, Window};
+use cgmath::{InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Transform, Vector3};
+
+use std::f32::consts::PI;
+
+use std::error::Error;
+use std::ffi::CStr;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::app::App;
+use crate::camera::Camera;
+use crate::color::clear_color_for;
+use crate::decal::{default_decal_texture, Decal};
+use crate::mesh::{box_lines, instances_bounds, BufferError, InstanceData, Mesh, MeshUsage, UploadBatch, Vertex};
+use crate::texture::{Texture, TextureError};
+use crate::text::{create_font_atlas, label_position, text_vertices, text_vertices_at_depth};
+use crate::shapes::{shape_vertices, Shape, ShapeVertex};
+use crate::spirv::{ShaderPaths, load_spirv};
+use crate::gpu_timer::GpuTimer;
+use crate::post::{ColorMode, DepthComplexityView, PostEffect, PostPass};
+use crate::screenshot::save_png;
+use crate::vulkan::error::InitError;
+use crate::vulkan::initialization::{create_render_pass, create_swapchain, current_dimensions, full_viewport,
+                                    recreate_surface, recreate_swapchain_with_config, surface_dimensions,
+                                    window_size_dependent_setup, HeadlessContext, SwapchainConfig,
+                                    VulkanContext, WindowTarget};
+use crate::vulkan::pipeline::{BuiltPipeline, PipelineBuilder, PipelineError};
+use crate::vulkan::render_pass::decal_subpass;
+use crate::vulkan::shader::{load_shader, ShaderLoadError, ShaderRequirements};
+
+// Dragging a window edge sends a stream of Resized events, the swapchain only gets recreated once
+// they've stopped for this long. Until then the old one keeps being presented (and stretched)
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(100);
+
+// Light that reaches faces turned away from the light, relative to the light color
+const DEFAULT_AMBIENT: f32 = 0.15;
+
+// Anything but 1.0 needs wide_lines, without it the axes are drawn 1 pixel wide
+const AXES_LINE_WIDTH: f32 = 2.0;
+
+// What Scene::show_bounds draws the boxes in until something sets bounds_color
+const DEFAULT_BOUNDS_COLOR: [f32; 3] = [1.0, 1.0, 0.0];
+
+// The stencil mask is a disc around the origin, what the mask draw writes into the stencil and what
+// the first mesh then tests against
+const STENCIL_MASK_RADIUS: f32 = 0.3;
+const STENCIL_MASK_SEGMENTS: usize = 32;
+const STENCIL_REFERENCE: u32 = 1;
+
+// Scene::add_decal drops the oldest one past this many, each one is a fullscreen draw
+const MAX_DECALS: usize = 16;
+
+type MeshPipeline = BuiltPipeline<SingleBufferDefinition<Vertex>>;
+
+type VertexEntryPoint<'a> = GraphicsEntryPoint<'a, (), vs::MainInput, vs::MainOutput, vs::Layout>;
+type FragmentEntryPoint<'a> = GraphicsEntryPoint<'a, (), fs::MainInput, fs::MainOutput, fs::Layout>;
+
+type FrameFence = Arc<FenceSignalFuture<Box<dyn GpuFuture + Send + Sync>>>;
+
+type InstancedPipeline = BuiltPipeline<OneVertexOneInstanceDefinition<Vertex, InstanceData>>;
+
+#[derive(Debug)]
+pub enum SceneError {
+    ShaderLoad(ShaderLoadError),
+    Pipeline { pipeline: &'static str, error: PipelineError },
+    // For the meshes the scene brings along itself
+    Buffer(BufferError),
+    // The font atlas and the default decal
+    Texture(TextureError),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::ShaderLoad(e) => write!(f, "{}", e),
+            SceneError::Pipeline { pipeline, error } => write!(f, "{} pipeline: {}", pipeline, error),
+            SceneError::Buffer(e) => write!(f, "{}", e),
+            SceneError::Texture(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::ShaderLoad(e) => Some(e),
+            SceneError::Pipeline { error, .. } => Some(error),
+            SceneError::Buffer(e) => Some(e),
+            SceneError::Texture(e) => Some(e),
+        }
+    }
+}
+
+impl From<ShaderLoadError> for SceneError {
+    fn from(e: ShaderLoadError) -> SceneError {
+        SceneError::ShaderLoad(e)
+    }
+}
+
+impl From<BufferError> for SceneError {
+    fn from(e: BufferError) -> SceneError {
+        SceneError::Buffer(e)
+    }
+}
+
+impl From<TextureError> for SceneError {
+    fn from(e: TextureError) -> SceneError {
+        SceneError::Texture(e)
+    }
+}
+
+// For map_err on a pipeline build
+fn pipeline_error(pipeline: &'static str) -> impl Fn(PipelineError) -> SceneError {
+    move |error| SceneError::Pipeline { pipeline, error }
+}
+
+// Every pipeline is built twice so wireframe mode is just a matter of picking the other one. The
+// wireframe one needs fill_mode_non_solid, without it we keep drawing filled
+struct PipelinePair<P = MeshPipeline> {
+    filled: P,
+    wireframe: Option<P>,
+}
+
+impl<P: Clone> PipelinePair<P> {
+    fn new<E>(wireframe_supported: bool, base: &PipelineBuilder, build: impl Fn(&PipelineBuilder) -> Result<P, E>)
+              -> Result<PipelinePair<P>, E> {
+        Ok(PipelinePair {
+            filled: build(base)?,
+            wireframe: if wireframe_supported {
+                Some(build(&base.wireframe())?)
+            } else {
+                None
+            },
+        })
+    }
+
+    fn get(&self, wireframe: bool) -> P {
+        match &self.wireframe {
+            Some(pipeline) if wireframe => pipeline.clone(),
+            _ => self.filled.clone(),
+        }
+    }
+}
+
+// Gets its own pipeline (Pipelines::textured) since its descriptor set also needs the texture and sampler
+struct TexturedDraw {
+    mesh: Mesh,
+    texture: Texture,
+}
+
+// What the meshes spin around
+#[derive(Debug, Clone, Copy)]
+pub enum RotationAxis {
+    X,
+    Y,
+    Z,
+    Arbitrary(Vector3<f32>),
+}
+
+impl RotationAxis {
+    // "x", "y", "z" or "x,y,z" for any other axis
+    pub fn from_arg(arg: &str) -> Option<RotationAxis> {
+        match arg {
+            "x" => Some(RotationAxis::X),
+            "y" => Some(RotationAxis::Y),
+            "z" => Some(RotationAxis::Z),
+            _ => {
+                let components = arg.split(',')
+                    .map(|component| component.trim().parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .ok()?;
+
+                let axis = match components.as_slice() {
+                    [x, y, z] => Vector3::new(*x, *y, *z),
+                    _ => return None,
+                };
+
+                // Can't normalize a zero vector
+                if axis.magnitude2() == 0.0 {
+                    return None;
+                }
+                Some(RotationAxis::Arbitrary(axis))
+            }
+        }
+    }
+
+    fn rotation(&self, angle: Rad<f32>) -> Matrix4<f32> {
+        match self {
+            RotationAxis::X => Matrix4::from_angle_x(angle),
+            RotationAxis::Y => Matrix4::from_angle_y(angle),
+            RotationAxis::Z => Matrix4::from_angle_z(angle),
+            RotationAxis::Arbitrary(axis) => Matrix4::from_axis_angle(axis.normalize(), angle),
+        }
+    }
+}
+
+// What Scene::show_bounds draws around each mesh. Aabb is the box lined up with the world axes
+// around the mesh as it is now, Obb is the mesh's own box turning along with it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoundsMode {
+    Aabb,
+    Obb,
+}
+
+impl BoundsMode {
+    pub fn from_arg(arg: &str) -> Option<BoundsMode> {
+        match arg {
+            "aabb" => Some(BoundsMode::Aabb),
+            "obb" => Some(BoundsMode::Obb),
+            _ => None,
+        }
+    }
+}
+
+// Depth only versions of the first mesh's pipelines for the prepass subpass. They have to use the
+// same vertex shader as the color pass or the depth values won't match exactly
+struct Prepass {
+    pipeline: MeshPipeline,
+    instanced_pipeline: Option<InstancedPipeline>,
+    // Kept for replace_shaders
+    fs_depth: fs_depth::Shader,
+}
+
+// For Scene::stencil_mask. The mask only writes the stencil, the first mesh's pipelines then only draw
+// where it did. Reference and masks are dynamic, see stencil_dynamic_state
+struct StencilMask {
+    mask: MeshPipeline,
+    masked: PipelinePair,
+    instanced_masked: Option<PipelinePair<InstancedPipeline>>,
+}
+
+// For Scene's decals, in the subpass create_render_pass adds after the color pass for
+// SwapchainConfig::decals. A fullscreen triangle per decal, see frag_decal.glsl
+struct DecalPipeline {
+    subpass: u32,
+    pipeline: BuiltPipeline<BufferlessDefinition>,
+}
+
+fn stencil_write() -> Stencil {
+    Stencil {
+        compare: Compare::Always,
+        pass_op: StencilOp::Replace,
+        fail_op: StencilOp::Keep,
+        depth_fail_op: StencilOp::Keep,
+        compare_mask: None,
+        write_mask: None,
+        reference: None,
+    }
+}
+
+fn stencil_test() -> Stencil {
+    Stencil {
+        compare: Compare::Equal,
+        pass_op: StencilOp::Keep,
+        fail_op: StencilOp::Keep,
+        depth_fail_op: StencilOp::Keep,
+        compare_mask: None,
+        write_mask: None,
+        reference: None,
+    }
+}
+
+fn stencil_dynamic_state(dynamic_state: &DynamicState) -> DynamicState {
+    let value = |value| Some(DynamicStencilValue { face: StencilFaceFlags::StencilFrontAndBack, value });
+    DynamicState {
+        compare_mask: value(u32::MAX),
+        write_mask: value(u32::MAX),
+        reference: value(STENCIL_REFERENCE),
+        ..dynamic_state.clone()
+    }
+}
+
+// What a scene needs for each render pass it draws into, e.g. one per window
+struct Target {
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    pipelines: Pipelines,
+    static_draws: Vec<StaticDraw>,
+    // Whether the static draws have the axes in them, record uses the same viewport
+    static_draws_axes: bool,
+    dynamic_state: DynamicState,
+    // What the last frame used, reused as long as nothing in it changes
+    uniforms: Option<CachedUniforms>,
+    // Never changes, built the first time there's text to draw
+    text_set: Option<Arc<dyn DescriptorSet + Send + Sync>>,
+    // From Scene::set_depth_image, what the decals read
+    depth_image: Option<Arc<AttachmentImage>>,
+    // Built the first time there are decals to draw, until the depth image or the texture changes
+    decal_set: Option<Arc<dyn DescriptorSet + Send + Sync>>,
+    // From Scene::set_letterbox_color, None clears the bars like the rest
+    letterbox_color: Option<[f32; 4]>,
+}
+
+// One sub-buffer from the pool with the sets that point at it. The sets keep the sub-buffer from
+// going back to the pool, so frames still in flight can keep reading it
+#[derive(Clone)]
+struct CachedUniforms {
+    data: vs::ty::Data,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    textured_set: Option<Arc<dyn DescriptorSet + Send + Sync>>,
+}
+
+// The generated struct doesn't implement PartialEq
+fn same_uniforms(a: &vs::ty::Data, b: &vs::ty::Data) -> bool {
+    a.view == b.view
+        && a.proj == b.proj
+        && a.light_direction == b.light_direction
+        && a.time == b.time
+        && a.light_color == b.light_color
+        && a.ambient == b.ambient
+}
+
+impl Target {
+    fn new(render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>, pipelines: Pipelines) -> Target {
+        Target {
+            render_pass: render_pass.clone(),
+            pipelines,
+            static_draws: vec!(),
+            static_draws_axes: false,
+            dynamic_state: DynamicState::none(),
+            uniforms: None,
+            text_set: None,
+            depth_image: None,
+            decal_set: None,
+            letterbox_color: None,
+        }
+    }
+}
+
+struct StaticDraw {
+    uniform_buffer: Arc<CpuAccessibleBuffer<vs::ty::Data>>,
+    // None while there's nothing static to draw
+    command_buffer: Option<Arc<AutoCommandBuffer>>,
+}
+
+// Everything that has to match the render pass, so it gets rebuilt along with it
+struct Pipelines {
+    // Everything but the prepass and the decals draws into this one
+    color_subpass: u32,
+    prepass: Option<Prepass>,
+    // What pipeline was built from, for replace_shaders
+    pipeline_base: PipelineBuilder,
+    pipeline: PipelinePair,
+    pipeline2: PipelinePair,
+    // Replaces pipeline when the first mesh has instances
+    instanced: Option<PipelinePair<InstancedPipeline>>,
+    // The same two with frag_uv_grid.glsl, for Scene::uv_grid
+    uv_grid: PipelinePair,
+    instanced_uv_grid: Option<PipelinePair<InstancedPipeline>>,
+    // Kept for replace_shaders
+    fs_uv_grid: fs_uv_grid::Shader,
+    // Additive and without a depth test, for Scene::overdraw. Every mesh gets drawn with one of
+    // these, never in wireframe since it's the filled fragments that cost
+    overdraw: PipelinePair,
+    instanced_overdraw: Option<PipelinePair<InstancedPipeline>>,
+    // None when the depth format has no stencil
+    stencil: Option<StencilMask>,
+    textured: Option<PipelinePair>,
+    // textured and uv_grid with the UVs interpolated without perspective, for Scene::affine_uvs.
+    // Always with the built-in vert.glsl, a --vertex-shader wouldn't have the noperspective
+    affine_textured: Option<PipelinePair>,
+    affine_uv_grid: PipelinePair,
+    axes: MeshPipeline,
+    transparent: PipelinePair,
+    // The textured quad shaders with alpha blending, for Scene::draw_text
+    text: MeshPipeline,
+    // The same with a depth test that doesn't write, for Scene::label_depth_test
+    label: MeshPipeline,
+    // Blended like the text, for Scene::draw_shape
+    shapes: BuiltPipeline<SingleBufferDefinition<ShapeVertex>>,
+    // None without a decal subpass
+    decals: Option<DecalPipeline>,
+    // The per-frame sets come from these instead of a new PersistentDescriptorSet each time. They
+    // keep the layout around and reuse a set's allocation once the frame that used it is dropped
+    uniform_sets: FixedSizeDescriptorSetsPool,
+    textured_sets: Option<FixedSizeDescriptorSetsPool>,
+}
+
+impl Pipelines {
+    // vertex and fragment replace vert.glsl and the first mesh's frag.glsl
+    fn new(device: &Arc<Device>, render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>, vertex: Option<&ShaderModule>,
+           fragment: Option<&ShaderModule>, instanced: bool, textured: bool) -> Result<Pipelines, SceneError> {
+        // None of the built-in shaders need anything past the Shader capability, see ShaderRequirements
+        let vs = load_shader(device, "vert.glsl", ShaderRequirements::none(), || vs::Shader::load(device.clone()))?;
+        let fs = load_shader(device, "frag.glsl", ShaderRequirements::none(), || fs::Shader::load(device.clone()))?;
+        let fs2 = load_shader(device, "frag2.glsl", ShaderRequirements::none(), || fs2::Shader::load(device.clone()))?;
+
+        let vs_entry = match vertex {
+            Some(module) => unsafe { vertex_entry_point(module) },
+            None => vs.main_entry_point(),
+        };
+        let fs_entry = match fragment {
+            Some(module) => unsafe { fragment_entry_point(module) },
+            None => fs.main_entry_point(),
+        };
+
+        let wireframe_supported = device.enabled_features().fill_mode_non_solid;
+
+        // create_render_pass puts the prepass in front of the color pass and the decals after it
+        let color_subpass = render_pass.num_subpasses() as u32 - 1 - decal_subpass(render_pass).is_some() as u32;
+        let depth_prepass = color_subpass > 0;
+        let base = PipelineBuilder::new(render_pass).subpass(color_subpass);
+
+        // The first mesh is the one that can be a whole model, so it's the one that tests and writes
+        // depth. Everything else is flat and just drawn in order. After a prepass the depth is
+        // already there and only the fragments that match it get shaded
+        let pipeline_base = if depth_prepass {
+            base.clone().depth_stencil(DepthStencil {
+                depth_compare: Compare::Equal,
+                depth_write: false,
+                ..DepthStencil::disabled()
+            })
+        } else {
+            base.clone().depth_test(true)
+        };
+
+        let pipeline = PipelinePair::new(wireframe_supported, &pipeline_base, |builder| {
+            builder.build(device, SingleBufferDefinition::<Vertex>::new(), vs_entry.clone(), fs_entry.clone())
+        })
+            .map_err(pipeline_error("mesh"))?;
+
+        let pipeline2 = PipelinePair::new(wireframe_supported, &base, |builder| {
+            builder.build(device, SingleBufferDefinition::<Vertex>::new(), vs_entry.clone(), fs2.main_entry_point())
+        })
+            .map_err(pipeline_error("second mesh"))?;
+
+        // Needs the instance data as a second vertex buffer and a vertex shader that reads it.
+        // --vertex-shader only replaces vert.glsl so it doesn't apply here
+        let vs_instanced = load_shader(device, "vert_instanced.glsl", ShaderRequirements::none(), || vs_instanced::Shader::load(device.clone()))?;
+        let instanced_pipeline = if instanced {
+            Some(PipelinePair::new(wireframe_supported, &pipeline_base, |builder| {
+                builder.build(
+                    device,
+                    OneVertexOneInstanceDefinition::<Vertex, InstanceData>::new(),
+                    vs_instanced.main_entry_point(),
+                    fs_entry.clone(),
+                )
+            })
+                .map_err(pipeline_error("instanced"))?)
+        } else {
+            None
+        };
+
+        // Only the fragment shader changes, the UVs come out of whatever vertex shader is used
+        let fs_uv_grid = load_shader(device, "frag_uv_grid.glsl", ShaderRequirements::none(), || fs_uv_grid::Shader::load(device.clone()))?;
+        let uv_grid = PipelinePair::new(wireframe_supported, &pipeline_base, |builder| {
+            builder.build(device, SingleBufferDefinition::<Vertex>::new(), vs_entry.clone(), fs_uv_grid.main_entry_point())
+        })
+            .map_err(pipeline_error("uv grid"))?;
+        let instanced_uv_grid = if instanced {
+            Some(PipelinePair::new(wireframe_supported, &pipeline_base, |builder| {
+                builder.build(
+                    device,
+                    OneVertexOneInstanceDefinition::<Vertex, InstanceData>::new(),
+                    vs_instanced.main_entry_point(),
+                    fs_uv_grid.main_entry_point(),
+                )
+            })
+                .map_err(pipeline_error("instanced uv grid"))?)
+        } else {
+            None
+        };
+
+        let vs_affine = load_shader(device, "vert.glsl", ShaderRequirements::none(), || vs_affine::Shader::load(device.clone()))?;
+        let fs_uv_grid_affine = load_shader(device, "frag_uv_grid.glsl", ShaderRequirements::none(), || fs_uv_grid_affine::Shader::load(device.clone()))?;
+        let affine_uv_grid = PipelinePair::new(wireframe_supported, &pipeline_base, |builder| {
+            builder.build(device, SingleBufferDefinition::<Vertex>::new(), vs_affine.main_entry_point(), fs_uv_grid_affine.main_entry_point())
+        })
+            .map_err(pipeline_error("affine uv grid"))?;
+
+        // Every fragment adds the same amount, whatever is in front of or behind it
+        let fs_overdraw = load_shader(device, "frag_overdraw.glsl", ShaderRequirements::none(), || fs_overdraw::Shader::load(device.clone()))?;
+        let overdraw_base = base.clone()
+            .depth_stencil(DepthStencil::disabled())
+            .blend(AttachmentBlend {
+                enabled: true,
+                color_op: BlendOp::Add,
+                color_source: BlendFactor::One,
+                color_destination: BlendFactor::One,
+                alpha_op: BlendOp::Add,
+                alpha_source: BlendFactor::One,
+                alpha_destination: BlendFactor::One,
+                ..AttachmentBlend::pass_through()
+            });
+        let overdraw = PipelinePair::new(false, &overdraw_base, |builder| {
+            builder.build(device, SingleBufferDefinition::<Vertex>::new(), vs_entry.clone(), fs_overdraw.main_entry_point())
+        })
+            .map_err(pipeline_error("overdraw"))?;
+        let instanced_overdraw = if instanced {
+            Some(PipelinePair::new(false, &overdraw_base, |builder| {
+                builder.build(
+                    device,
+                    OneVertexOneInstanceDefinition::<Vertex, InstanceData>::new(),
+                    vs_instanced.main_entry_point(),
+                    fs_overdraw.main_entry_point(),
+                )
+            })
+                .map_err(pipeline_error("instanced overdraw"))?)
+        } else {
+            None
+        };
+
+        // Always vert.glsl and an unlit shader, the axes are a debugging aid and shouldn't depend on
+        // whatever shaders were passed in. Lines don't have normals to light them with anyway
+        let fs_unlit = load_shader(device, "frag_unlit.glsl", ShaderRequirements::none(), || fs_unlit::Shader::load(device.clone()))?;
+
+        // The mask is always filled and writes neither color nor depth
+        let has_stencil = Subpass::from(render_pass.clone(), color_subpass).unwrap().has_stencil();
+        let stencil = if has_stencil {
+            let masked_base = pipeline_base.clone().stencil(stencil_test());
+
+            Some(StencilMask {
+                mask: base.clone()
+                    .stencil(stencil_write())
+                    .blend(AttachmentBlend {
+                        mask_red: false,
+                        mask_green: false,
+                        mask_blue: false,
+                        mask_alpha: false,
+                        ..AttachmentBlend::pass_through()
+                    })
+                    .build(device, SingleBufferDefinition::<Vertex>::new(), vs.main_entry_point(), fs_unlit.main_entry_point())
+                    .map_err(pipeline_error("stencil mask"))?,
+                masked: PipelinePair::new(wireframe_supported, &masked_base, |builder| {
+                    builder.build(device, SingleBufferDefinition::<Vertex>::new(), vs_entry.clone(), fs_entry.clone())
+                })
+                    .map_err(pipeline_error("masked"))?,
+                instanced_masked: if instanced {
+                    Some(PipelinePair::new(wireframe_supported, &masked_base, |builder| {
+                        builder.build(
+                            device,
+                            OneVertexOneInstanceDefinition::<Vertex, InstanceData>::new(),
+                            vs_instanced.main_entry_point(),
+                            fs_entry.clone(),
+                        )
+                    })
+                        .map_err(pipeline_error("instanced masked"))?)
+                } else {
+                    None
+                },
+            })
+        } else {
+            None
+        };
+
+        // Always filled, record skips it in wireframe mode
+        let prepass = if depth_prepass {
+            let fs_depth = load_shader(device, "frag_depth.glsl", ShaderRequirements::none(), || fs_depth::Shader::load(device.clone()))?;
+            let prepass_base = PipelineBuilder::new(render_pass).depth_test(true);
+
+            Some(Prepass {
+                pipeline: prepass_base
+                    .build(device, SingleBufferDefinition::<Vertex>::new(), vs_entry.clone(), fs_depth.main_entry_point())
+                    .map_err(pipeline_error("depth prepass"))?,
+                instanced_pipeline: if instanced {
+                    Some(prepass_base
+                        .build(
+                            device,
+                            OneVertexOneInstanceDefinition::<Vertex, InstanceData>::new(),
+                            vs_instanced.main_entry_point(),
+                            fs_depth.main_entry_point(),
+                        )
+                        .map_err(pipeline_error("instanced depth prepass"))?)
+                } else {
+                    None
+                },
+                fs_depth,
+            })
+        } else {
+            None
+        };
+
+        let fs_textured = load_shader(device, "frag_textured.glsl", ShaderRequirements::none(), || fs_textured::Shader::load(device.clone()))?;
+        let textured_pipeline = if textured {
+            Some(PipelinePair::new(wireframe_supported, &base, |builder| {
+                builder.build(device, SingleBufferDefinition::<Vertex>::new(), vs_entry.clone(), fs_textured.main_entry_point())
+            })
+                .map_err(pipeline_error("textured"))?)
+        } else {
+            None
+        };
+        let affine_textured = if textured {
+            let fs_textured_affine = load_shader(device, "frag_textured.glsl", ShaderRequirements::none(), || fs_textured_affine::Shader::load(device.clone()))?;
+            Some(PipelinePair::new(wireframe_supported, &base, |builder| {
+                builder.build(device, SingleBufferDefinition::<Vertex>::new(), vs_affine.main_entry_point(), fs_textured_affine.main_entry_point())
+            })
+                .map_err(pipeline_error("affine textured"))?)
+        } else {
+            None
+        };
+
+        let axes_pipeline = base.clone()
+            .topology(PrimitiveTopology::LineList)
+            .line_width_dynamic()
+            .build(device, SingleBufferDefinition::<Vertex>::new(), vs.main_entry_point(), fs_unlit.main_entry_point())
+            .map_err(pipeline_error("axes"))?;
+
+        // Alpha blending mixes it with whatever is already in the attachment, so it only looks
+        // right if it's drawn after everything it's in front of
+        let fs_transparent = load_shader(device, "frag_transparent.glsl", ShaderRequirements::none(), || fs_transparent::Shader::load(device.clone()))?;
+        let transparent_pipeline = PipelinePair::new(
+            wireframe_supported,
+            &base.clone().blend(AttachmentBlend::alpha_blending()),
+            |builder| {
+                builder.build(device, SingleBufferDefinition::<Vertex>::new(), vs_entry.clone(), fs_transparent.main_entry_point())
+            },
+        )
+            .map_err(pipeline_error("transparent"))?;
+
+        // Always vert.glsl, the text is already in screen space and goes through it with identity
+        // matrices. Never wireframe, it's there to be read
+        let text_pipeline = base.clone()
+            .blend(AttachmentBlend::alpha_blending())
+            .build(device, SingleBufferDefinition::<Vertex>::new(), vs.main_entry_point(), fs_textured.main_entry_point())
+            .map_err(pipeline_error("text"))?;
+        // Less or equal so a label right on a surface still shows
+        let label_pipeline = base.clone()
+            .blend(AttachmentBlend::alpha_blending())
+            .depth_stencil(DepthStencil {
+                depth_compare: Compare::LessOrEqual,
+                depth_write: false,
+                ..DepthStencil::disabled()
+            })
+            .build(device, SingleBufferDefinition::<Vertex>::new(), vs.main_entry_point(), fs_textured.main_entry_point())
+            .map_err(pipeline_error("label"))?;
+        let vs_shape = load_shader(device, "vert_shape.glsl", ShaderRequirements::none(), || vs_shape::Shader::load(device.clone()))?;
+        let fs_shape = load_shader(device, "frag_shape.glsl", ShaderRequirements::none(), || fs_shape::Shader::load(device.clone()))?;
+        let shapes_pipeline = base.clone()
+            .blend(AttachmentBlend::alpha_blending())
+            .build(device, SingleBufferDefinition::<ShapeVertex>::new(), vs_shape.main_entry_point(), fs_shape.main_entry_point())
+            .map_err(pipeline_error("shapes"))?;
+
+        // Blended over whatever the color pass left, the depth is only read. With MSAA the depth
+        // buffer has to be read as a multisampled input attachment
+        let decals = match decal_subpass(render_pass) {
+            Some(subpass) => {
+                let vs_fullscreen = load_shader(device, "post_vert.glsl", ShaderRequirements::none(), || vs_fullscreen::Shader::load(device.clone()))?;
+                let builder = PipelineBuilder::new(render_pass)
+                    .subpass(subpass)
+                    .blend(AttachmentBlend::alpha_blending());
+                let multisampled = Subpass::from(render_pass.clone(), subpass)
+                    .and_then(|subpass| subpass.num_samples())
+                    .is_some_and(|samples| samples > 1);
+                let pipeline = if multisampled {
+                    let fs_decal = load_shader(device, "frag_decal.glsl", ShaderRequirements::none(), || fs_decal_ms::Shader::load(device.clone()))?;
+                    builder.build(device, BufferlessDefinition, vs_fullscreen.main_entry_point(), fs_decal.main_entry_point())
+                } else {
+                    let fs_decal = load_shader(device, "frag_decal.glsl", ShaderRequirements::none(), || fs_decal::Shader::load(device.clone()))?;
+                    builder.build(device, BufferlessDefinition, vs_fullscreen.main_entry_point(), fs_decal.main_entry_point())
+                }
+                    .map_err(pipeline_error("decal"))?;
+                Some(DecalPipeline { subpass, pipeline })
+            }
+            None => None,
+        };
+
+        // Every pipeline the uniform set is bound to has the same set 0, see record
+        let uniform_sets = FixedSizeDescriptorSetsPool::new(
+            pipeline.filled.descriptor_set_layout(0).unwrap().clone()
+        );
+        let textured_sets = textured_pipeline.as_ref().map(|textured| {
+            FixedSizeDescriptorSetsPool::new(textured.filled.descriptor_set_layout(0).unwrap().clone())
+        });
+
+        Ok(Pipelines {
+            color_subpass,
+            prepass,
+            pipeline_base,
+            pipeline,
+            pipeline2,
+            instanced: instanced_pipeline,
+            uv_grid,
+            instanced_uv_grid,
+            fs_uv_grid,
+            overdraw,
+            instanced_overdraw,
+            stencil,
+            textured: textured_pipeline,
+            affine_textured,
+            affine_uv_grid,
+            axes: axes_pipeline,
+            transparent: transparent_pipeline,
+            text: text_pipeline,
+            label: label_pipeline,
+            shapes: shapes_pipeline,
+            decals,
+            uniform_sets,
+            textured_sets,
+        })
+    }
+}
+
+// Everything the demo draws, independent of where it ends up. The Demo app records it into the
+// swapchain, render_offscreen into an image
+pub struct Scene {
+    queue: Arc<Queue>,
+    // Indexed by the target numbers record and friends take. The render pass passed to new is 0
+    targets: Vec<Target>,
+    // The --vertex-shader and --fragment-shader replacements (or the last hot reloaded shaders),
+    // for rebuilding the pipelines
+    vertex_shader: Option<Arc<ShaderModule>>,
+    fragment_shader: Option<Arc<ShaderModule>>,
+    uniform_buffer: CpuBufferPool<vs::ty::Data>,
+    mesh: Mesh,
+    mesh2: Mesh,
+    textured: Option<TexturedDraw>,
+    // Unit length X (red), Y (green) and Z (blue) lines from the origin
+    axes: Mesh,
+    axes_line_width: f32,
+    // Half transparent, drawn over the first mesh
+    transparent: Mesh,
+    stencil_mask_mesh: Mesh,
+    // In the first mesh's space, oldest first. They only show up in targets with a decal subpass
+    decals: Vec<Decal>,
+    decal_texture: Texture,
+    // A fixed-width bitmap font, see text.rs
+    font: Texture,
+    // Identity view and projection, text_vertices already are in device coordinates
+    text_uniform_buffer: Arc<CpuAccessibleBuffer<vs::ty::Data>>,
+    text_vertex_buffer: CpuBufferPool<Vertex>,
+    shape_vertex_buffer: CpuBufferPool<ShapeVertex>,
+    // The lines for show_bounds, they move with the meshes so they're new every frame
+    bounds_vertex_buffer: CpuBufferPool<Vertex>,
+    // Everything draw_text got since the last clear_text, in pixels from the top left
+    text: Vec<(f32, f32, String)>,
+    // Everything draw_label got since the last clear_text, centered on a point in world space
+    labels: Vec<(Point3<f32>, String)>,
+    // Everything draw_shape got since the last clear_text, in the order they got it
+    shapes: Vec<Shape>,
+    samples: u32,
+    // In sRGB, converted to whatever the attachment needs
+    pub clear_color: [f32; 4],
+    pub wireframe: bool,
+    pub show_axes: bool,
+    pub show_transparent: bool,
+    // Boxes around every mesh, drawn with the axes pipeline
+    pub show_bounds: bool,
+    pub bounds_mode: BoundsMode,
+    pub bounds_color: [f32; 3],
+    // Hides labels behind the meshes that write depth, instead of drawing them on top of everything
+    pub label_depth_test: bool,
+    // Only draws the first mesh inside a disc around the origin, through the stencil buffer
+    pub stencil_mask: bool,
+    // Draws the first mesh with a checker grid of its UVs instead of its colors, which isn't masked
+    pub uv_grid: bool,
+    // Interpolates the UVs of the textured mesh and the UV grid straight across the screen like a
+    // PlayStation would, so the texture warps on anything at an angle. Instanced meshes keep
+    // perspective-correct UVs
+    pub affine_uvs: bool,
+    // Draws every mesh additively into a black image instead, so it ends up with how many fragments
+    // landed on each pixel. Only meant to be looked at through DepthComplexityView in the post pass.
+    // The axes, bounds and text don't count
+    pub overdraw: bool,
+    // Radians per second of animation time
+    pub rotation_speed: f32,
+    pub rotation_axis: RotationAxis,
+    // A directional light for frag.glsl. The direction points towards the light and doesn't have
+    // to be normalized, ambient keeps the faces turned away from it from going black
+    pub light_direction: Vector3<f32>,
+    pub light_color: [f32; 3],
+    pub ambient: f32,
+    // Off records the axes every frame along with everything else, to compare record_time with
+    pub use_static_draws: bool,
+    // CPU time the last record took
+    record_time: Duration,
+    // Descriptor sets built since take_descriptor_sets_built, with the uniform cache and the pools
+    // that stays at 0 while nothing changes
+    descriptor_sets_built: u32,
+}
+
+impl Scene {
+    // record_static_draws has to be called once the viewport is set up, before the first record.
+    // render_pass becomes target 0, add_target adds more
+    pub fn new(queue: &Arc<Queue>, render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>, samples: u32,
+               mesh: Mesh, mesh2: Mesh, textured: Option<(Mesh, Texture)>, shader_paths: &ShaderPaths)
+               -> Result<Scene, SceneError> {
+        let device = queue.device().clone();
+
+        let vertex_shader = shader_paths.vertex.as_ref()
+            .and_then(|path| load_spirv(&device, path));
+        let fragment_shader = shader_paths.fragment.as_ref()
+            .and_then(|path| load_spirv(&device, path));
+
+        if !device.enabled_features().fill_mode_non_solid {
+            println!("Warning: fill_mode_non_solid isn't enabled, wireframe mode won't do anything");
+        }
+
+        let pipelines = Pipelines::new(
+            &device,
+            render_pass,
+            vertex_shader.as_deref(),
+            fragment_shader.as_deref(),
+            mesh.instances.is_some(),
+            textured.is_some(),
+        )?;
+
+        let uniform_buffer = CpuBufferPool::<vs::ty::Data>::new(
+            device.clone(),
+            BufferUsage::all()
+        );
+
+        let textured = textured.map(|(mesh, texture)| TexturedDraw { mesh, texture });
+
+        let axis = |end: [f32; 3]| vec!(
+            Vertex { position: [0.0, 0.0, 0.0], color: end, ..Vertex::default() },
+            Vertex { position: end, color: end, ..Vertex::default() },
+        );
+        let mut uploads = UploadBatch::new(queue)?;
+        let axes = Mesh::from_lines(
+            &mut uploads,
+            &[axis([1.0, 0.0, 0.0]), axis([0.0, 1.0, 0.0]), axis([0.0, 0.0, 1.0])].concat(),
+            MeshUsage::Static,
+        )?;
+
+        // DeviceConfig::debug_features turns on wide_lines whenever the device supports it
+        let axes_line_width = if device.enabled_features().wide_lines {
+            let [min, max] = device.physical_device().limits().line_width_range();
+            AXES_LINE_WIDTH.max(min).min(max)
+        } else {
+            1.0
+        };
+
+        let transparent = Mesh::from_vertices(
+            &mut uploads,
+            &[
+                Vertex { position: [-0.2, -0.4, 0.0], color: [0.0, 1.0, 1.0], ..Vertex::default() },
+                Vertex { position: [0.1, 0.3, 0.0], color: [0.0, 1.0, 1.0], ..Vertex::default() },
+                Vertex { position: [0.5, -0.4, 0.0], color: [0.0, 1.0, 1.0], ..Vertex::default() },
+            ],
+            MeshUsage::Static,
+        )?;
+
+        // A fan of triangles, as a list since that's the only topology the pipelines do
+        let point = |segment: usize| {
+            let angle = segment as f32 / STENCIL_MASK_SEGMENTS as f32 * 2.0 * PI;
+            Vertex {
+                position: [angle.cos() * STENCIL_MASK_RADIUS, angle.sin() * STENCIL_MASK_RADIUS, 0.0],
+                ..Vertex::default()
+            }
+        };
+        let mask_vertices = (0..STENCIL_MASK_SEGMENTS)
+            .flat_map(|segment| vec!(Vertex::default(), point(segment), point(segment + 1)))
+            .collect::<Vec<_>>();
+        let stencil_mask_mesh = Mesh::from_vertices(&mut uploads, &mask_vertices, MeshUsage::Static)?;
+        uploads.finish()?;
+
+        if pipelines.stencil.is_none() {
+            println!("Warning: the depth buffer has no stencil, the stencil mask won't do anything");
+        }
+
+        let font = create_font_atlas(queue)?;
+        let decal_texture = default_decal_texture(queue)?;
+        let text_uniform_buffer = CpuAccessibleBuffer::from_data(
+            device.clone(),
+            BufferUsage::uniform_buffer(),
+            false,
+            vs::ty::Data {
+                view: Matrix4::identity().into(),
+                proj: Matrix4::identity().into(),
+                light_direction: [0.0, 0.0, 1.0],
+                time: 0.0,
+                light_color: [1.0, 1.0, 1.0],
+                ambient: 1.0,
+            },
+        )
+            .unwrap();
+        let text_vertex_buffer = CpuBufferPool::vertex_buffer(device.clone());
+        let shape_vertex_buffer = CpuBufferPool::vertex_buffer(device.clone());
+        let bounds_vertex_buffer = CpuBufferPool::vertex_buffer(device.clone());
+
+        Ok(Scene {
+            queue: queue.clone(),
+            targets: vec!(Target::new(render_pass, pipelines)),
+            vertex_shader,
+            fragment_shader,
+            uniform_buffer,
+            mesh,
+            mesh2,
+            textured,
+            axes,
+            axes_line_width,
+            transparent,
+            stencil_mask_mesh,
+            decals: vec!(),
+            decal_texture,
+            font,
+            text_uniform_buffer,
+            text_vertex_buffer,
+            shape_vertex_buffer,
+            bounds_vertex_buffer,
+            text: vec!(),
+            labels: vec!(),
+            shapes: vec!(),
+            samples,
+            clear_color: [0.0, 0.0, 1.0, 1.0],
+            wireframe: false,
+            show_axes: false,
+            show_transparent: false,
+            show_bounds: false,
+            bounds_mode: BoundsMode::Aabb,
+            bounds_color: DEFAULT_BOUNDS_COLOR,
+            label_depth_test: false,
+            stencil_mask: false,
+            uv_grid: false,
+            affine_uvs: false,
+            overdraw: false,
+            rotation_speed: 1.0,
+            rotation_axis: RotationAxis::Z,
+            // Up, right and in front of the camera
+            light_direction: Vector3::new(0.5, 1.0, 1.0),
+            light_color: [1.0, 1.0, 1.0],
+            ambient: DEFAULT_AMBIENT,
+            use_static_draws: true,
+            record_time: Duration::from_secs(0),
+            descriptor_sets_built: 0,
+        })
+    }
+
+    // Drawn over everything else in every target until clear_text, x and y are in pixels from the
+    // top left corner of the viewport
+    pub fn draw_text(&mut self, x: f32, y: f32, text: &str) {
+        self.text.push((x, y, text.to_string()));
+    }
+
+    // Like draw_text, but follows the camera around. Skipped while position is behind it and
+    // kept on the edge of the viewport while it's off to the side
+    pub fn draw_label(&mut self, position: Point3<f32>, text: &str) {
+        self.labels.push((position, text.to_string()));
+    }
+
+    // Like draw_text, under the text but over the labels. For panels behind the text and the like
+    pub fn draw_shape(&mut self, shape: Shape) {
+        self.shapes.push(shape);
+    }
+
+    // The shapes go along with the text
+    pub fn clear_text(&mut self) {
+        self.text.clear();
+        self.labels.clear();
+        self.shapes.clear();
+    }
+
+    // Has to be called whenever the framebuffers of target get a new depth image, for the decals to read
+    pub fn set_depth_image(&mut self, target: usize, depth_image: &Arc<AttachmentImage>) {
+        self.targets[target].depth_image = Some(depth_image.clone());
+        self.targets[target].decal_set = None;
+    }
+
+    // What's outside the viewport of target when it's letterboxed, see Renderer::letterbox_color. The
+    // render pass clears everything to it and the viewport gets the clear color drawn over it
+    pub fn set_letterbox_color(&mut self, target: usize, color: Option<[f32; 4]>) {
+        self.targets[target].letterbox_color = color;
+    }
+
+    // Replaces the ring every decal gets until then
+    pub fn set_decal_texture(&mut self, texture: Texture) {
+        self.decal_texture = texture;
+        for target in &mut self.targets {
+            target.decal_set = None;
+        }
+    }
+
+    // Whether target's render pass has the decal subpass, see SwapchainConfig::decals
+    pub fn decals_supported(&self, target: usize) -> bool {
+        self.targets[target].pipelines.decals.is_some()
+    }
+
+    // In the first mesh's space, like pick returns
+    pub fn add_decal(&mut self, decal: Decal) {
+        if self.decals.len() == MAX_DECALS {
+            self.decals.remove(0);
+        }
+        self.decals.push(decal);
+    }
+
+    pub fn clear_decals(&mut self) {
+        self.decals.clear();
+    }
+
+    pub fn decals(&self) -> &[Decal] {
+        &self.decals
+    }
+
+    // Swaps the first mesh for another one between frames, for streaming and the demo reel. It gets
+    // the old one's instances, the pipelines were built for them. The decals were on the old one so
+    // they go. Returns the old one without its instances
+    pub fn replace_mesh(&mut self, mut mesh: Mesh) -> Mesh {
+        mesh.instances = self.mesh.instances.take();
+        self.decals.clear();
+        std::mem::replace(&mut self.mesh, mesh)
+    }
+
+    // The textured quad's texture. There's only a quad (and a pipeline for it) with --texture,
+    // without one the texture comes back
+    pub fn replace_texture(&mut self, texture: Texture) -> Result<(), Texture> {
+        let textured = match &mut self.textured {
+            Some(textured) => textured,
+            None => return Err(texture),
+        };
+        textured.texture = texture;
+        // The cached sets still have the old one
+        for target in &mut self.targets {
+            target.uniforms = None;
+        }
+        Ok(())
+    }
+
+    // Where the ray through cursor (in pixels from the top left of the window) hits the first mesh
+    // and its normal there, in the mesh's space before it spins. Only the first mesh writes depth so
+    // it's the only one decals land on. None for instanced meshes, the instances aren't picked
+    pub fn pick(&self, target: usize, cursor: [f32; 2], time: f32, camera: &Camera) -> Option<(Point3<f32>, Vector3<f32>)> {
+        if self.mesh.instances.is_some() {
+            return None;
+        }
+
+        let viewport = &self.targets[target].dynamic_state.viewports.as_ref()?[0];
+        let [width, height] = viewport.dimensions;
+        let ndc = [
+            (cursor[0] - viewport.origin[0]) / width * 2.0 - 1.0,
+            (cursor[1] - viewport.origin[1]) / height * 2.0 - 1.0,
+        ];
+        // On the letterbox bars
+        if ndc.iter().any(|coordinate| coordinate.abs() > 1.0) {
+            return None;
+        }
+
+        // Undoing the spin moves the ray into the mesh's space
+        let (origin, direction) = camera.ray(ndc, width / height);
+        let to_mesh = self.rotation_axis.rotation(Rad(time * self.rotation_speed))
+            .invert()?;
+        let origin = to_mesh.transform_point(origin);
+        let direction = to_mesh.transform_vector(direction);
+        let (distance, normal) = self.mesh.pick(origin, direction)?;
+        Some((origin + direction * distance, normal))
+    }
+
+    // Another render pass to draw the same scene into, with the same sample count. Returns the
+    // target number for it
+    pub fn add_target(&mut self, render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>) -> Result<usize, SceneError> {
+        let pipelines = self.pipelines_for(render_pass)?;
+        self.targets.push(Target::new(render_pass, pipelines));
+        Ok(self.targets.len() - 1)
+    }
+
+    // For hot reloading. Only the first mesh's pipeline is rebuilt, everything else keeps the
+    // shaders it was built with until the render pass changes. On error the old pipelines stay
+    // in every target
+    pub fn replace_shaders(&mut self, vertex: &Arc<ShaderModule>, fragment: &Arc<ShaderModule>)
+                           -> Result<(), PipelineError> {
+        let device = self.queue.device().clone();
+        let vs_entry = unsafe { vertex_entry_point(vertex) };
+        let fs_entry = unsafe { fragment_entry_point(fragment) };
+
+        let mut rebuilt = vec!();
+        for target in &self.targets {
+            let pipeline = PipelinePair::new(device.enabled_features().fill_mode_non_solid, &target.pipelines.pipeline_base, |builder| {
+                builder.build(&device, SingleBufferDefinition::<Vertex>::new(), vs_entry.clone(), fs_entry.clone())
+            })?;
+
+            let uv_grid = PipelinePair::new(device.enabled_features().fill_mode_non_solid, &target.pipelines.pipeline_base, |builder| {
+                let fs_entry = target.pipelines.fs_uv_grid.main_entry_point();
+                builder.build(&device, SingleBufferDefinition::<Vertex>::new(), vs_entry.clone(), fs_entry)
+            })?;
+
+            let masked = match &target.pipelines.stencil {
+                Some(_) => Some(PipelinePair::new(
+                    device.enabled_features().fill_mode_non_solid,
+                    &target.pipelines.pipeline_base.clone().stencil(stencil_test()),
+                    |builder| builder.build(&device, SingleBufferDefinition::<Vertex>::new(), vs_entry.clone(), fs_entry.clone()),
+                )?),
+                None => None,
+            };
+
+            // The prepass has to move to the new vertex shader as well, or the depth test stops matching
+            let prepass = match &target.pipelines.prepass {
+                Some(prepass) => Some(
+                    PipelineBuilder::new(&target.render_pass)
+                        .depth_test(true)
+                        .build(&device, SingleBufferDefinition::<Vertex>::new(), vs_entry.clone(), prepass.fs_depth.main_entry_point())?
+                ),
+                None => None,
+            };
+            rebuilt.push((pipeline, uv_grid, masked, prepass));
+        }
+
+        for (target, (pipeline, uv_grid, masked, prepass_pipeline)) in self.targets.iter_mut().zip(rebuilt) {
+            target.pipelines.pipeline = pipeline;
+            target.pipelines.uv_grid = uv_grid;
+            // The sets were built against the old pipelines' layouts
+            target.uniforms = None;
+            target.text_set = None;
+            if let (Some(stencil), Some(masked)) = (&mut target.pipelines.stencil, masked) {
+                stencil.masked = masked;
+            }
+            if let (Some(prepass), Some(prepass_pipeline)) = (&mut target.pipelines.prepass, prepass_pipeline) {
+                prepass.pipeline = prepass_pipeline;
+            }
+        }
+        self.vertex_shader = Some(vertex.clone());
+        self.fragment_shader = Some(fragment.clone());
+        Ok(())
+    }
+
+    // After the swapchain format changed. Everything recorded for the old render pass is useless
+    // too, so record_static_draws has to be called again before the next record
+    // The shaders already loaded once for the old render pass, but the new one can still have
+    // something the pipelines can't be built for
+    pub fn set_render_pass(&mut self, target: usize, render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>)
+                           -> Result<(), SceneError> {
+        let pipelines = self.pipelines_for(render_pass)?;
+        self.targets[target] = Target::new(render_pass, pipelines);
+        Ok(())
+    }
+
+    fn pipelines_for(&self, render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>) -> Result<Pipelines, SceneError> {
+        Pipelines::new(
+            self.queue.device(),
+            render_pass,
+            self.vertex_shader.as_deref(),
+            self.fragment_shader.as_deref(),
+            self.mesh.instances.is_some(),
+            self.textured.is_some(),
+        )
+    }
+
+    // The axes never move so their draw lives in secondary command buffers that are only recorded
+    // again when the viewport changes or they get toggled. One per swapchain image, each with its
+    // own uniform buffer since a buffer can't be written while a frame that reads it is in flight.
+    // Anything that spins has to be recorded every frame, its rotation is a push constant
+    pub fn record_static_draws(&mut self, target: usize, image_count: usize, dynamic_state: &DynamicState) {
+        let render_pass = self.targets[target].render_pass.clone();
+        let color_subpass = self.targets[target].pipelines.color_subpass;
+        let axes_pipeline = self.targets[target].pipelines.axes.clone();
+        let layout = axes_pipeline.descriptor_set_layout(0).unwrap();
+        let line_dynamic_state = DynamicState {
+            line_width: Some(self.axes_line_width),
+            ..dynamic_state.clone()
+        };
+        let record_axes = self.show_axes && self.use_static_draws;
+
+        let static_draws = (0..image_count)
+            .map(|_| {
+                let uniform_buffer = CpuAccessibleBuffer::from_data(
+                    self.queue.device().clone(),
+                    BufferUsage::uniform_buffer(),
+                    false,
+                    vs::ty::Data {
+                        view: Matrix4::identity().into(),
+                        proj: Matrix4::identity().into(),
+                        light_direction: self.light_direction.into(),
+                        time: 0.0,
+                        light_color: self.light_color,
+                        ambient: self.ambient,
+                    },
+                )
+                    .unwrap();
+
+                if !record_axes {
+                    return StaticDraw { uniform_buffer, command_buffer: None };
+                }
+
+                let set = Arc::new(
+                    PersistentDescriptorSet::start(
+                        layout.clone()
+                    )
+                        .add_buffer(uniform_buffer.clone())
+                        .unwrap()
+                        .build()
+                        .unwrap()
+                );
+
+                let builder = AutoCommandBufferBuilder::secondary_graphics(
+                    self.queue.device().clone(),
+                    self.queue.family(),
+                    Subpass::from(render_pass.clone(), color_subpass).unwrap(),
+                )
+                    .unwrap();
+
+                let identity = vs::ty::PushConstants {
+                    rotation: Matrix4::identity().into(),
+                };
+
+                let command_buffer = self.axes.draw(
+                    builder,
+                    axes_pipeline.clone(),
+                    &line_dynamic_state,
+                    set,
+                    identity,
+                )
+                    .unwrap()
+                    .build()
+                    .unwrap();
+
+                StaticDraw { uniform_buffer, command_buffer: Some(Arc::new(command_buffer)) }
+            })
+            .collect();
+        if record_axes {
+            self.descriptor_sets_built += image_count as u32;
+        }
+        self.targets[target].static_draws = static_draws;
+        self.targets[target].static_draws_axes = record_axes;
+        self.targets[target].dynamic_state = dynamic_state.clone();
+    }
+
+    // Every mesh that spins gets rotation, like the push constants in record, the transparent
+    // triangle stays where it is. Instanced meshes get a single box around all of their instances
+    fn bounds_lines(&self, rotation: Matrix4<f32>) -> Vec<Vertex> {
+        let mut meshes = vec!((&self.mesh, rotation), (&self.mesh2, rotation));
+        if let Some(textured) = &self.textured {
+            meshes.push((&textured.mesh, rotation));
+        }
+        if self.show_transparent {
+            meshes.push((&self.transparent, Matrix4::identity()));
+        }
+
+        meshes.into_iter()
+            .filter_map(|(mesh, model)| {
+                let bounds = mesh.bounds?;
+                let corners = match &mesh.instances {
+                    // The GPU only ever reads the instances, so this doesn't have to wait for it
+                    Some(instances) => instances_bounds(&bounds, model, &instances.read().ok()?)?.corners(),
+                    None => match self.bounds_mode {
+                        BoundsMode::Aabb => bounds.transformed(model).corners(),
+                        BoundsMode::Obb => bounds.transformed_corners(model),
+                    },
+                };
+                Some(box_lines(&corners, self.bounds_color))
+            })
+            .flatten()
+            .collect()
+    }
+
+    // The caller has to make sure the last frame recorded for image_index of target has finished.
+    // Draws with the viewport record_static_draws got, the projection gets its aspect ratio, which
+    // already accounts for letterboxing
+    pub fn record(&mut self, target: usize, builder: AutoCommandBufferBuilder,
+                  framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>, image_index: usize, time: f32,
+                  camera: &Camera) -> AutoCommandBufferBuilder {
+        let record_start = Instant::now();
+        let dynamic_state = self.targets[target].dynamic_state.clone();
+        let dynamic_state = &dynamic_state;
+        let [width, height] = dynamic_state.viewports.as_ref()
+            .expect("record_static_draws has to be called before recording")[0]
+            .dimensions;
+        let aspect_ratio = width / height;
+
+        if self.targets[target].static_draws_axes != (self.show_axes && self.use_static_draws) {
+            self.record_static_draws(target, self.targets[target].static_draws.len(), dynamic_state);
+        }
+        let rotation = self.rotation_axis.rotation(Rad(time * self.rotation_speed));
+        let bounds_lines = if self.show_bounds && !self.overdraw { self.bounds_lines(rotation) } else { vec!() };
+        let Target { render_pass, pipelines, static_draws, uniforms, text_set, depth_image, decal_set, letterbox_color, .. } =
+            &mut self.targets[target];
+
+        // The first attachment is always the color one, its clear value only matters when it gets cleared
+        let color = render_pass.attachment_desc(0)
+            .unwrap();
+        // Without a clear the bars keep whatever they had, same as the viewport
+        let letterbox_color = if color.load == LoadOp::Clear && !self.overdraw { *letterbox_color } else { None };
+        // The overdraw counts start at 0
+        let clear_color = if self.overdraw { [0.0, 0.0, 0.0, 0.0] } else { letterbox_color.unwrap_or(self.clear_color) };
+        let mut clear_values = if color.load == LoadOp::Clear {
+            vec!(clear_color_for(color.format, clear_color).into())
+        } else {
+            vec!(ClearValue::None)
+        };
+        if self.samples > 1 {
+            // The resolve target is never cleared, it's overwritten completely
+            clear_values.push(ClearValue::None);
+        }
+        let depth_format = render_pass.attachment_desc(render_pass.num_attachments() - 1)
+            .unwrap()
+            .format;
+        clear_values.push(match depth_format.ty() {
+            FormatTy::DepthStencil => ClearValue::DepthStencil((1.0, 0)),
+            _ => ClearValue::Depth(1.0),
+        });
+
+        // A single matrix that changes every frame, pushing it is a lot cheaper than going through
+        // the buffer pool and a new descriptor set. The camera stays in the uniform buffer
+        let push_constants = vs::ty::PushConstants {
+            rotation: rotation.into(),
+        };
+
+        let view = camera.view();
+        let proj = camera.projection(aspect_ratio);
+        let data = vs::ty::Data {
+            view: view.into(),
+            proj: proj.into(),
+            light_direction: self.light_direction.into(),
+            time,
+            light_color: self.light_color,
+            ambient: self.ambient,
+        };
+
+        let static_draw = &static_draws[image_index];
+        *static_draw.uniform_buffer.write().unwrap() = data;
+
+        // Paused with the camera standing still nothing in here changes, so there's no point in
+        // uploading it again
+        let cached = match uniforms {
+            Some(cached) if same_uniforms(&cached.data, &data) => cached.clone(),
+            _ => {
+                let uniform_buffer_subbuffer = self.uniform_buffer.next(data).unwrap();
+                let set = Arc::new(
+                    pipelines.uniform_sets.next()
+                        .add_buffer(uniform_buffer_subbuffer.clone())
+                        .unwrap()
+                        .build()
+                        .unwrap()
+                ) as Arc<dyn DescriptorSet + Send + Sync>;
+
+                let textured_set = self.textured.as_ref().map(|textured| {
+                    Arc::new(
+                        pipelines.textured_sets.as_mut()
+                            .unwrap()
+                            .next()
+                            .add_buffer(uniform_buffer_subbuffer.clone())
+                            .unwrap()
+                            .add_sampled_image(textured.texture.image.clone(), textured.texture.sampler.clone())
+                            .unwrap()
+                            .build()
+                            .unwrap()
+                    ) as Arc<dyn DescriptorSet + Send + Sync>
+                });
+
+                self.descriptor_sets_built += 1 + textured_set.is_some() as u32;
+                let cached = CachedUniforms { data, set, textured_set };
+                *uniforms = Some(cached.clone());
+                cached
+            }
+        };
+        let set = cached.set;
+
+        // Everything else is recorded every frame. A subpass either takes draws directly or only
+        // secondary command buffers, so this goes into one as well
+        let dynamic_builder = AutoCommandBufferBuilder::secondary_graphics_one_time_submit(
+            self.queue.device().clone(),
+            self.queue.family(),
+            Subpass::from(render_pass.clone(), pipelines.color_subpass).unwrap(),
+        )
+            .unwrap();
+
+        // The axes, the transparent triangle and the stencil mask stay put while everything else spins
+        let identity = vs::ty::PushConstants {
+            rotation: Matrix4::identity().into(),
+        };
+
+        // The mask has to be in the stencil before the first mesh is tested against it
+        let stencil = if self.stencil_mask && !self.overdraw { pipelines.stencil.as_ref() } else { None };
+        let stencil_dynamic_state = stencil_dynamic_state(dynamic_state);
+        let dynamic_builder = match stencil {
+            Some(stencil) => self.stencil_mask_mesh.draw(
+                dynamic_builder,
+                stencil.mask.clone(),
+                &stencil_dynamic_state,
+                set.clone(),
+                identity,
+            )
+                .unwrap(),
+            None => dynamic_builder,
+        };
+
+        let (mesh_dynamic_state, pipeline, instanced_pipeline) = match stencil {
+            _ if self.overdraw => (dynamic_state, &pipelines.overdraw, pipelines.instanced_overdraw.as_ref()),
+            _ if self.uv_grid && self.affine_uvs => (dynamic_state, &pipelines.affine_uv_grid, pipelines.instanced_uv_grid.as_ref()),
+            _ if self.uv_grid => (dynamic_state, &pipelines.uv_grid, pipelines.instanced_uv_grid.as_ref()),
+            Some(stencil) => (&stencil_dynamic_state, &stencil.masked, stencil.instanced_masked.as_ref()),
+            None => (dynamic_state, &pipelines.pipeline, pipelines.instanced.as_ref()),
+        };
+
+        // vert_instanced.glsl has the same uniforms as vert.glsl so it can use the same set
+        let mut dynamic_builder = match instanced_pipeline {
+            Some(instanced_pipeline) => self.mesh.draw_instanced(
+                dynamic_builder,
+                instanced_pipeline.get(self.wireframe),
+                mesh_dynamic_state,
+                set.clone(),
+                push_constants,
+            ),
+            None => self.mesh.draw(
+                dynamic_builder,
+                pipeline.get(self.wireframe),
+                mesh_dynamic_state,
+                set.clone(),
+                push_constants,
+            ),
+        }
+            .unwrap();
+
+        let pipeline2 = if self.overdraw { &pipelines.overdraw } else { &pipelines.pipeline2 };
+        dynamic_builder = self.mesh2.draw(
+            dynamic_builder,
+            pipeline2.get(self.wireframe),
+            dynamic_state,
+            set.clone(),
+            push_constants,
+        )
+            .unwrap();
+
+        if let Some(textured) = &self.textured {
+            // The overdraw shader doesn't sample anything, it takes the same set as the other meshes
+            let (pipeline3, set3) = if self.overdraw {
+                (pipelines.overdraw.get(self.wireframe), set.clone())
+            } else {
+                let textured = if self.affine_uvs { &pipelines.affine_textured } else { &pipelines.textured };
+                let pipeline3 = textured.as_ref()
+                    .unwrap()
+                    .get(self.wireframe);
+                let set3 = cached.textured_set.clone()
+                    .unwrap();
+                (pipeline3, set3)
+            };
+
+            dynamic_builder = textured.mesh.draw(
+                dynamic_builder,
+                pipeline3,
+                dynamic_state,
+                set3,
+                push_constants,
+            )
+                .unwrap();
+        }
+
+        if self.show_axes && !self.use_static_draws && !self.overdraw {
+            let line_dynamic_state = DynamicState {
+                line_width: Some(self.axes_line_width),
+                ..dynamic_state.clone()
+            };
+
+            dynamic_builder = self.axes.draw(
+                dynamic_builder,
+                pipelines.axes.clone(),
+                &line_dynamic_state,
+                set.clone(),
+                identity,
+            )
+                .unwrap();
+        }
+
+        if !bounds_lines.is_empty() {
+            let line_dynamic_state = DynamicState {
+                line_width: Some(self.axes_line_width),
+                ..dynamic_state.clone()
+            };
+
+            dynamic_builder = dynamic_builder
+                .draw(
+                    pipelines.axes.clone(),
+                    &line_dynamic_state,
+                    self.bounds_vertex_buffer.chunk(bounds_lines).unwrap(),
+                    set.clone(),
+                    identity,
+                )
+                .unwrap();
+        }
+
+        // Last, everything opaque has to be in the attachment already
+        if self.show_transparent {
+            let transparent_pipeline = if self.overdraw { &pipelines.overdraw } else { &pipelines.transparent };
+            dynamic_builder = self.transparent.draw(
+                dynamic_builder,
+                transparent_pipeline.get(self.wireframe),
+                dynamic_state,
+                set.clone(),
+                identity,
+            )
+                .unwrap();
+        }
+
+        // On top of everything, including the transparent triangle. The labels go first so the
+        // overlay text stays readable over them
+        let view_proj = proj * view;
+        let label_depth_test = self.label_depth_test;
+        let labels = self.labels.iter()
+            .filter_map(|(position, text)| {
+                let (x, y, depth) = label_position(*position, view_proj, text, [width, height])?;
+                // Without the depth test they're just more overlay text
+                let depth = if label_depth_test { depth } else { 0.0 };
+                Some(text_vertices_at_depth(x, y, depth, text, [width, height]))
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+        let text = self.text.iter()
+            .flat_map(|(x, y, text)| text_vertices(*x, *y, text, [width, height]))
+            .collect::<Vec<_>>();
+        // In the color the attachment needs, same as the clear color
+        let color_format = color.format;
+        let shapes = self.shapes.iter()
+            .flat_map(|shape| shape_vertices(shape, [width, height], |srgb| clear_color_for(color_format, srgb)))
+            .collect::<Vec<_>>();
+        // Text would end up in the counts
+        if (!text.is_empty() || !labels.is_empty() || !shapes.is_empty()) && !self.overdraw {
+            if text_set.is_none() {
+                self.descriptor_sets_built += 1;
+            }
+            let font = &self.font;
+            let text_uniform_buffer = &self.text_uniform_buffer;
+            let set = text_set.get_or_insert_with(|| {
+                Arc::new(
+                    PersistentDescriptorSet::start(pipelines.text.descriptor_set_layout(0).unwrap().clone())
+                        .add_buffer(text_uniform_buffer.clone())
+                        .unwrap()
+                        .add_sampled_image(font.image.clone(), font.sampler.clone())
+                        .unwrap()
+                        .build()
+                        .unwrap()
+                )
+            });
+
+            if !labels.is_empty() {
+                let label_pipeline = if label_depth_test { &pipelines.label } else { &pipelines.text };
+                dynamic_builder = dynamic_builder
+                    .draw(
+                        label_pipeline.clone(),
+                        dynamic_state,
+                        self.text_vertex_buffer.chunk(labels).unwrap(),
+                        set.clone(),
+                        identity,
+                    )
+                    .unwrap();
+            }
+            if !shapes.is_empty() {
+                dynamic_builder = dynamic_builder
+                    .draw(
+                        pipelines.shapes.clone(),
+                        dynamic_state,
+                        self.shape_vertex_buffer.chunk(shapes).unwrap(),
+                        (),
+                        (),
+                    )
+                    .unwrap();
+            }
+            if !text.is_empty() {
+                dynamic_builder = dynamic_builder
+                    .draw(
+                        pipelines.text.clone(),
+                        dynamic_state,
+                        self.text_vertex_buffer.chunk(text).unwrap(),
+                        set.clone(),
+                        identity,
+                    )
+                    .unwrap();
+            }
+        }
+
+        let dynamic_draws = dynamic_builder
+            .build()
+            .unwrap();
+
+        // The bars got cleared to the letterbox color, the viewport gets the real clear color drawn
+        // over it before anything else. A pixel past every edge so the antialiasing stays outside
+        let background_draws = if letterbox_color.is_some() {
+            let background = Shape::rounded_rect(-1.0, -1.0, width + 2.0, height + 2.0, 0.0)
+                .fill(self.clear_color);
+            let vertices = shape_vertices(&background, [width, height], |srgb| clear_color_for(color_format, srgb));
+            Some(AutoCommandBufferBuilder::secondary_graphics_one_time_submit(
+                self.queue.device().clone(),
+                self.queue.family(),
+                Subpass::from(render_pass.clone(), pipelines.color_subpass).unwrap(),
+            )
+                .unwrap()
+                .draw(
+                    pipelines.shapes.clone(),
+                    dynamic_state,
+                    self.shape_vertex_buffer.chunk(vertices).unwrap(),
+                    (),
+                    (),
+                )
+                .unwrap()
+                .build()
+                .unwrap())
+        } else {
+            None
+        };
+
+        // The decals are in the first mesh's space, which spins
+        let decal_draws = match (&pipelines.decals, depth_image) {
+            (Some(decals), Some(depth_image)) if !self.decals.is_empty() && !self.overdraw => {
+                if decal_set.is_none() {
+                    self.descriptor_sets_built += 1;
+                }
+                let decal_texture = &self.decal_texture;
+                let set = decal_set.get_or_insert_with(|| {
+                    Arc::new(
+                        PersistentDescriptorSet::start(decals.pipeline.descriptor_set_layout(0).unwrap().clone())
+                            .add_image(depth_image.clone())
+                            .unwrap()
+                            .add_sampled_image(decal_texture.image.clone(), decal_texture.sampler.clone())
+                            .unwrap()
+                            .build()
+                            .unwrap()
+                    )
+                });
+
+                let inverse_view_proj = view_proj.invert()
+                    .unwrap();
+                let from_mesh = rotation.invert()
+                    .unwrap();
+                let mut decal_builder = AutoCommandBufferBuilder::secondary_graphics_one_time_submit(
+                    self.queue.device().clone(),
+                    self.queue.family(),
+                    Subpass::from(render_pass.clone(), decals.subpass).unwrap(),
+                )
+                    .unwrap();
+                for decal in &self.decals {
+                    let push_constants = fs_decal::ty::PushConstants {
+                        inverse_view_proj: inverse_view_proj.into(),
+                        world_to_decal: (decal.box_matrix() * from_mesh).into(),
+                    };
+                    decal_builder = decal_builder
+                        .draw(
+                            decals.pipeline.clone(),
+                            dynamic_state,
+                            BufferlessVertices { vertices: 3, instances: 1 },
+                            set.clone(),
+                            push_constants,
+                        )
+                        .unwrap();
+                }
+                Some(decal_builder
+                    .build()
+                    .unwrap())
+            }
+            _ => None,
+        };
+
+        // The closure would borrow all of self, which target's fields are already borrowed from
+        let queue = &self.queue;
+        let mesh = &self.mesh;
+        // Not in wireframe mode, the wireframe pipelines do their own depth test instead (see
+        // PipelineBuilder::wireframe). The prepass subpass is still there, it just stays empty
+        let draw_prepass = !(self.wireframe && queue.device().enabled_features().fill_mode_non_solid);
+        let prepass_draws = pipelines.prepass.as_ref().filter(|_| draw_prepass).map(|prepass| {
+            let prepass_builder = AutoCommandBufferBuilder::secondary_graphics_one_time_submit(
+                queue.device().clone(),
+                queue.family(),
+                Subpass::from(render_pass.clone(), 0).unwrap(),
+            )
+                .unwrap();
+
+            match &prepass.instanced_pipeline {
+                Some(instanced_pipeline) => mesh.draw_instanced(
+                    prepass_builder,
+                    instanced_pipeline.clone(),
+                    dynamic_state,
+                    set.clone(),
+                    push_constants,
+                ),
+                None => mesh.draw(
+                    prepass_builder,
+                    prepass.pipeline.clone(),
+                    dynamic_state,
+                    set.clone(),
+                    push_constants,
+                ),
+            }
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+
+        let mut builder = builder
+            .begin_render_pass(framebuffer, true, clear_values)
+            .unwrap();
+
+        // Same reasoning as for the other secondary command buffers below
+        if pipelines.prepass.is_some() {
+            if let Some(prepass_draws) = prepass_draws {
+                builder = unsafe {
+                    builder
+                        .execute_commands(prepass_draws)
+                        .unwrap()
+                };
+            }
+            builder = builder
+                .next_subpass(true)
+                .unwrap();
+        }
+
+        if let Some(background_draws) = background_draws {
+            builder = unsafe {
+                builder
+                    .execute_commands(background_draws)
+                    .unwrap()
+            };
+        }
+
+        // vulkano doesn't track what secondary command buffers use yet. Everything in there is
+        // either never written (meshes, texture), only handed out again once the GPU is done with
+        // it (the buffer pool) or only written after waiting for the frame (the static uniforms).
+        // The static draws are opaque and go first so blended draws in the other one land on top
+        let builder = match &static_draw.command_buffer {
+            Some(command_buffer) if !self.overdraw => unsafe {
+                builder
+                    .execute_commands(command_buffer.clone())
+                    .unwrap()
+            },
+            _ => builder,
+        };
+        let mut builder = unsafe {
+            builder
+                .execute_commands(dynamic_draws)
+                .unwrap()
+        };
+
+        // The decal subpass is there whether there are decals or not
+        if pipelines.decals.is_some() {
+            builder = builder
+                .next_subpass(true)
+                .unwrap();
+            if let Some(decal_draws) = decal_draws {
+                builder = unsafe {
+                    builder
+                        .execute_commands(decal_draws)
+                        .unwrap()
+                };
+            }
+        }
+
+        let builder = builder
+            .end_render_pass()
+            .unwrap();
+        self.record_time = record_start.elapsed();
+        builder
+    }
+
+    pub fn record_time(&self) -> Duration {
+        self.record_time
+    }
+
+    pub fn take_descriptor_sets_built(&mut self) -> u32 {
+        std::mem::replace(&mut self.descriptor_sets_built, 0)
+    }
+}
+
+pub struct Renderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    // Usually the same as queue, see VulkanContext::present_queue
+    present_queue: Arc<Queue>,
+    surface: Arc<Surface<Arc<Window>>>,
+    swapchain: Arc<Swapchain<Arc<Window>>>,
+    swapchain_config: SwapchainConfig,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    images: Vec<Arc<SwapchainImage<Arc<Window>>>>,
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    // Shared by all the framebuffers
+    depth_image: Arc<AttachmentImage>,
+    // One per swapchain image so the CPU only waits when it's about to reuse an image that's still
+    // in flight instead of after every submit
+    frame_fences: Vec<Option<FrameFence>>,
+    previous_fence_index: usize,
+    recreate_swapchain: bool,
+    // The next recreate replaces the surface as well
+    surface_lost: bool,
+    present_mode_changed: bool,
+    recreated_last_frame: bool,
+    swapchain_dimensions: [u32; 2],
+    // From ScaleFactorChanged, which happens before the window itself has the new size
+    new_window_size: Option<[u32; 2]>,
+    last_resize: Option<Instant>,
+    fullscreen_exclusive_held: bool,
+    screenshot_path: Option<PathBuf>,
+    post: Option<PostPass>,
+    samples: u32,
+    // Has the viewport, handed to the app for every draw
+    dynamic_state: DynamicState,
+    // The app gets told before the next frame
+    render_pass_recreated: bool,
+    // The app couldn't rebuild its pipelines for the current render pass, nothing gets drawn until
+    // the render pass changes again
+    render_pass_failed: bool,
+    framebuffers_recreated: bool,
+    gpu_timer: Option<GpuTimer>,
+}
+
+impl Renderer {
+    // One per window, each with its own swapchain_config
+    pub fn new(context: &VulkanContext, window: &WindowTarget, swapchain_config: SwapchainConfig,
+               post_effect: Option<PostEffect>) -> Renderer {
+        let mut post = post_effect.map(|effect| {
+            PostPass::new(&context.device, window.swapchain.format(), effect)
+                .unwrap()
+        });
+        let scene_image = post.as_mut()
+            .map(|post| post.resize(&context.device, &window.swapchain_images));
+
+        // line_width stays None here, every pipeline but the axes one has a fixed line width and
+        // vulkano refuses to draw with one set
+        let mut dynamic_state = DynamicState::none();
+        let (framebuffers, depth_image) = window_size_dependent_setup(
+            &context.device,
+            &window.swapchain_images,
+            window.render_pass.clone(),
+            &mut dynamic_state,
+            swapchain_config.letterbox,
+            context.samples,
+            scene_image,
+        );
+
+        let frame_fences = vec!(None; window.swapchain_images.len());
+
+        Renderer {
+            device: context.device.clone(),
+            queue: context.queue.clone(),
+            present_queue: context.present_queue.clone(),
+            surface: window.surface.clone(),
+            swapchain: window.swapchain.clone(),
+            swapchain_config,
+            render_pass: window.render_pass.clone(),
+            images: window.swapchain_images.clone(),
+            framebuffers,
+            depth_image,
+            frame_fences,
+            previous_fence_index: 0,
+            recreate_swapchain: false,
+            surface_lost: false,
+            present_mode_changed: false,
+            recreated_last_frame: false,
+            swapchain_dimensions: window.swapchain.dimensions(),
+            new_window_size: None,
+            last_resize: None,
+            fullscreen_exclusive_held: false,
+            screenshot_path: None,
+            post,
+            samples: context.samples,
+            dynamic_state,
+            render_pass_recreated: false,
+            render_pass_failed: false,
+            framebuffers_recreated: true,
+            gpu_timer: None,
+        }
+    }
+
+    pub fn window(&self) -> &Window {
+        self.surface.window()
+    }
+
+    pub fn render_pass(&self) -> &Arc<dyn RenderPassAbstract + Send + Sync> {
+        &self.render_pass
+    }
+
+    // What Scene::set_letterbox_color wants for this window, None unless SwapchainConfig::letterbox is set
+    pub fn letterbox_color(&self) -> Option<[f32; 4]> {
+        self.swapchain_config.letterbox.map(|_| self.swapchain_config.letterbox_color)
+    }
+
+    // The new size is read from the window once the resizing has settled
+    pub fn window_resized(&mut self) {
+        self.new_window_size = None;
+        self.last_resize = Some(Instant::now());
+        self.recreate_swapchain = true;
+    }
+
+    // Moving the window to a monitor with a different DPI changes its size in pixels
+    pub fn scale_factor_changed(&mut self, new_window_size: [u32; 2]) {
+        self.new_window_size = Some(new_window_size);
+        self.recreate_swapchain = true;
+    }
+
+    // Borderless on the monitor the window is on. With SwapchainConfig::exclusive_fullscreen the
+    // swapchain also tries to take the display for itself once it's been recreated at the new size
+    pub fn toggle_fullscreen(&mut self) {
+        let window = self.surface.window();
+
+        if window.fullscreen().is_some() {
+            if self.fullscreen_exclusive_held {
+                if let Err(e) = self.swapchain.release_fullscreen_exclusive() {
+                    println!("Failed to release exclusive fullscreen: {}", e);
+                }
+                self.fullscreen_exclusive_held = false;
+            }
+            window.set_fullscreen(None);
+        } else {
+            window.set_fullscreen(Some(Fullscreen::Borderless(window.current_monitor())));
+        }
+
+        self.recreate_swapchain = true;
+    }
+
+    // Call before dropping everything, destroying things the GPU is still using makes the
+    // validation layers (and some drivers) unhappy
+    pub fn wait_idle(&self) {
+        // Safe as long as nothing submits while we wait, which nothing does since we're single threaded
+        if let Err(e) = unsafe { self.device.wait() } {
+            println!("Failed to wait for the device to go idle: {}", e);
+        }
+    }
+
+    // Saved after the next frame has been rendered
+    pub fn take_screenshot(&mut self, path: PathBuf) {
+        self.screenshot_path = Some(path);
+    }
+
+    // Prints how long the GPU took for the frames once a second
+    pub fn set_gpu_timing(&mut self, enabled: bool) {
+        self.gpu_timer = if enabled {
+            let timer = GpuTimer::new(&self.queue, self.images.len());
+            if timer.is_none() {
+                println!("The graphics queue doesn't support timestamps, no GPU frame times");
+            }
+            timer
+        } else {
+            None
+        };
+    }
+
+    // Applied in the post pass. A window without one gets one that only does the color mode, for as long
+    // as the mode isn't Normal
+    pub fn set_color_mode(&mut self, color_mode: ColorMode) {
+        if color_mode != ColorMode::Normal {
+            self.add_passthrough_post();
+        }
+        if let Some(post) = &mut self.post {
+            post.color_mode = color_mode;
+        }
+        self.drop_unused_passthrough_post();
+    }
+
+    // Shows what Scene::overdraw drew instead of the post effect, which the scene should only do
+    // while this is set. Gets a post pass the same way as set_color_mode
+    pub fn set_depth_complexity(&mut self, view: Option<DepthComplexityView>) {
+        if view.is_some() {
+            self.add_passthrough_post();
+        }
+        if let Some(post) = &mut self.post {
+            post.depth_complexity = view;
+        }
+        self.drop_unused_passthrough_post();
+    }
+
+    // Gets a post pass the same way as set_color_mode, 0 turns the dithering off
+    pub fn set_dither(&mut self, strength: f32) {
+        if strength > 0.0 {
+            self.add_passthrough_post();
+        }
+        if let Some(post) = &mut self.post {
+            post.dither = strength;
+        }
+        self.drop_unused_passthrough_post();
+    }
+
+    // For a demo reel, which keeps the post pass (one that only does this if there's nothing else)
+    // until it's set back to None. A new crossfade starts with capture_previous
+    pub fn set_crossfade(&mut self, crossfade: Option<f32>) {
+        if crossfade.is_some() {
+            self.add_passthrough_post();
+        }
+        if let Some(post) = &mut self.post {
+            post.crossfade = crossfade;
+        }
+        self.drop_unused_passthrough_post();
+    }
+
+    // The next frame fades from this one, see PostPass::capture_previous
+    pub fn capture_previous(&mut self) {
+        if let Some(post) = &mut self.post {
+            post.capture_previous();
+        }
+    }
+
+    fn add_passthrough_post(&mut self) {
+        if self.post.is_some() {
+            return;
+        }
+        match PostPass::new(&self.device, self.swapchain.format(), PostEffect::Passthrough) {
+            Ok(post) => {
+                self.post = Some(post);
+                self.rebuild_framebuffers();
+            }
+            Err(e) => println!("Failed to set up the post pass: {}", e),
+        }
+    }
+
+    fn drop_unused_passthrough_post(&mut self) {
+        let unused = self.post.as_ref().is_some_and(|post| {
+            post.effect() == PostEffect::Passthrough && post.color_mode == ColorMode::Normal
+                && post.depth_complexity.is_none() && post.dither == 0.0 && post.crossfade.is_none()
+        });
+        if unused {
+            self.post = None;
+            self.rebuild_framebuffers();
+        }
+    }
+
+    // When the images or the post pass changed, the frames still in flight keep the old framebuffers alive
+    fn rebuild_framebuffers(&mut self) {
+        let device = &self.device;
+        let images = &self.images;
+        let scene_image = self.post.as_mut()
+            .map(|post| post.resize(device, images));
+        let (framebuffers, depth_image) = window_size_dependent_setup(
+            &self.device,
+            &self.images,
+            self.render_pass.clone(),
+            &mut self.dynamic_state,
+            self.swapchain_config.letterbox,
+            self.samples,
+            scene_image,
+        );
+        self.framebuffers = framebuffers;
+        self.depth_image = depth_image;
+        self.framebuffers_recreated = true;
+    }
+
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        self.swapchain_config.present_mode = present_mode;
+        self.present_mode_changed = true;
+        self.recreate_swapchain = true;
+    }
+
+    // Returns false if there's nothing to render to right now, e.g. while the window is minimized
+    fn recreate(&mut self) -> bool {
+        if self.surface_lost {
+            match recreate_surface(&self.surface, &self.present_queue) {
+                Ok(surface) => {
+                    self.surface = surface;
+                    self.surface_lost = false;
+                }
+                Err(e) => {
+                    println!("Failed to recreate the lost surface, trying again next frame: {}", e);
+                    return false;
+                }
+            }
+        }
+
+        let caps = self.surface.capabilities(self.device.physical_device())
+            .unwrap();
+        let dimensions = match self.new_window_size {
+            Some(window_size) => surface_dimensions(&caps, window_size),
+            None => current_dimensions(&self.surface, &caps),
+        };
+
+        // Minimized windows report a 0x0 extent (Windows does it through current_extent) and a
+        // swapchain can't be that small. recreate_swapchain stays set so we try again next frame
+        if dimensions[0] == 0 || dimensions[1] == 0 {
+            return false;
+        }
+
+        // Display settings (like turning HDR on) can take the swapchain's format away, it needs a
+        // new one and everything that was built for the old one
+        let format_lost = !caps.supported_formats.iter()
+            .any(|(format, _)| *format == self.swapchain.format());
+        // The swapchain is still on a surface that got lost, it can't be recreated from
+        let surface_replaced = !Arc::ptr_eq(self.swapchain.surface(), &self.surface);
+
+        // Tiling WMs like to report a freshly created swapchain as suboptimal/out of date,
+        // which ends up recreating it every frame and flickering. Let a recreated swapchain
+        // live for at least one frame unless the size actually changed
+        if self.recreated_last_frame && dimensions == self.swapchain_dimensions && !self.present_mode_changed
+            && !format_lost && !surface_replaced {
+            self.recreated_last_frame = false;
+            self.recreate_swapchain = false;
+            return true;
+        }
+
+        // recreate_with_dimensions would keep the old format
+        let (new_swapchain, new_images) = if surface_replaced {
+            match create_swapchain(&self.queue, &self.present_queue, &self.surface, dimensions, &self.swapchain_config) {
+                Ok(r) => r,
+                // Most likely lost again already, starts over with another surface next frame
+                Err(e) => {
+                    println!("Failed to create a swapchain for the new surface, trying again next frame: {}", e);
+                    self.surface_lost = true;
+                    return false;
+                }
+            }
+        } else if self.present_mode_changed || format_lost {
+            match recreate_swapchain_with_config(&self.swapchain, &self.queue, &self.present_queue, dimensions,
+                                                &self.swapchain_config) {
+                Ok(r) => r,
+                Err(InitError::SwapchainCreation(SwapchainCreationError::UnsupportedDimensions)) => return false,
+                Err(e) => panic!("Failed to recreate swapchain: {}", e)
+            }
+        } else {
+            match self.swapchain.recreate_with_dimensions(dimensions) {
+                Ok(r) => r,
+                Err(SwapchainCreationError::UnsupportedDimensions) => return false,
+                Err(e) => panic!("Failed to recreate swapchain: {:?}", e)
+            }
+        };
+
+        // Dropping a fence blocks until it's signaled, so this also waits for everything still in flight
+        self.frame_fences = vec!(None; new_images.len());
+        self.previous_fence_index = 0;
+
+        if new_swapchain.format() != self.swapchain.format() {
+            self.render_pass = create_render_pass(
+                &self.device,
+                new_swapchain.format(),
+                self.samples,
+                self.swapchain_config.depth_prepass,
+                self.swapchain_config.color_ops,
+                self.swapchain_config.decals,
+            )
+                .unwrap();
+            let format = new_swapchain.format();
+            let device = &self.device;
+            self.post = self.post.as_ref()
+                .map(|post| {
+                    let mut new_post = PostPass::new(device, format, post.effect())
+                        .unwrap();
+                    new_post.color_mode = post.color_mode;
+                    new_post.depth_complexity = post.depth_complexity;
+                    new_post.dither = post.dither;
+                    new_post.crossfade = post.crossfade;
+                    new_post
+                });
+            self.render_pass_recreated = true;
+        }
+        self.swapchain = new_swapchain;
+
+        // Exclusivity belongs to the old swapchain, the new one has to ask again
+        self.fullscreen_exclusive_held = false;
+        if self.surface.window().fullscreen().is_some()
+            && self.swapchain.fullscreen_exclusive() == FullscreenExclusive::AppControlled {
+            match self.swapchain.acquire_fullscreen_exclusive() {
+                Ok(()) => self.fullscreen_exclusive_held = true,
+                Err(e) => println!("Failed to get exclusive fullscreen, staying borderless: {}", e),
+            }
+        }
+        self.present_mode_changed = false;
+        self.swapchain_dimensions = dimensions;
+        self.new_window_size = None;
+        self.last_resize = None;
+        self.images = new_images;
+        self.rebuild_framebuffers();
+        // The queries are per swapchain image as well
+        if let Some(timer) = &mut self.gpu_timer {
+            *timer = GpuTimer::new(&self.queue, self.images.len())
+                .unwrap();
+        }
+        self.recreate_swapchain = false;
+        self.recreated_last_frame = true;
+        true
+    }
+
+    pub fn render_frame(&mut self, app: &mut impl App) {
+        for fence in self.frame_fences.iter_mut().flatten() {
+            fence.cleanup_finished();
+        }
+
+        let resizing = self.last_resize
+            .map(|last_resize| last_resize.elapsed() < RESIZE_DEBOUNCE)
+            .unwrap_or(false);
+
+        if self.recreate_swapchain && !resizing {
+            if !self.recreate() {
+                return;
+            }
+        } else {
+            self.recreated_last_frame = false;
+        }
+
+        let window = self.surface.window().id();
+        if self.render_pass_recreated {
+            self.render_pass_recreated = false;
+            self.render_pass_failed = false;
+            if let Err(e) = app.render_pass_recreated(window, &self.render_pass) {
+                println!("Failed to rebuild the pipelines for the new render pass, not drawing until it changes again: {}", e);
+                self.render_pass_failed = true;
+            }
+        }
+        if self.render_pass_failed {
+            return;
+        }
+        if self.framebuffers_recreated {
+            app.framebuffers_recreated(window, self.framebuffers.len(), &self.dynamic_state, &self.depth_image);
+            self.framebuffers_recreated = false;
+        }
+
+        // While resizing an out of date swapchain just means skipping frames until it settles
+        let (image_num, suboptimal, acquire_future) = match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+            Ok(r) => r,
+            // Alt-tabbing out of exclusive fullscreen loses it, recreating tries to get it back
+            Err(AcquireError::OutOfDate) | Err(AcquireError::FullscreenExclusiveLost) => {
+                self.recreate_swapchain = true;
+                return;
+            }
+            Err(AcquireError::SurfaceLost) => {
+                println!("The surface got lost, recreating it");
+                self.surface_lost = true;
+                self.recreate_swapchain = true;
+                return;
+            }
+            // Can't happen without a timeout, but there's nothing wrong with just trying again next frame
+            Err(AcquireError::Timeout) => return,
+            // Whatever else the driver comes up with, the next frame might go better
+            Err(e) => {
+                println!("Failed to acquire the next image, skipping the frame: {}", e);
+                return;
+            }
+        };
+
+        // Still set if it's waiting for the resizing to stop
+        if suboptimal {
+            self.recreate_swapchain = true;
+        }
+
+        if let Some(fence) = &self.frame_fences[image_num] {
+            let _ = fence.wait(None);
+        }
+
+        if let Some(stats) = self.gpu_timer.as_mut().and_then(|timer| timer.frame_finished(image_num)) {
+            println!("GPU frame time: {:.2}ms (min {:.2}ms, max {:.2}ms)", stats.average, stats.min, stats.max);
+        }
+
+        let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(
+            self.device.clone(),
+            self.queue.family(),
+        )
+            .unwrap();
+        if let Some(post) = &mut self.post {
+            builder = post.record_capture(builder);
+        }
+
+        let mut builder = app.record_draw(
+            window,
+            builder,
+            self.framebuffers[image_num].clone(),
+            image_num,
+            &self.dynamic_state,
+        );
+        if let Some(post) = &self.post {
+            builder = post.record(builder, image_num);
+        }
+
+        // Copied in the same command buffer, before the image goes off to be presented
+        let screenshot = match self.screenshot_path.take() {
+            Some(path) => {
+                let image = self.images[image_num].clone();
+                let buffer = readback_buffer(&self.device, image.dimensions());
+                builder = builder.copy_image_to_buffer(image.clone(), buffer.clone())
+                    .unwrap();
+                Some((path, image, buffer))
+            }
+            None => None,
+        };
+
+        let command_buffer = builder
+            .build()
+            .unwrap();
+
+        let previous_frame_end = match self.frame_fences[self.previous_fence_index].clone() {
+            Some(fence) => Box::new(fence) as Box<dyn GpuFuture + Send + Sync>,
+            None => Box::new(sync::now(self.device.clone())) as Box<dyn GpuFuture + Send + Sync>,
+        };
+
+        // The timestamps go in the same submission, right before and after the frame
+        let mut future = Box::new(previous_frame_end.join(acquire_future)) as Box<dyn GpuFuture + Send + Sync>;
+        if let Some(timer) = &mut self.gpu_timer {
+            future = Box::new(future.then_execute(self.queue.clone(), timer.start_commands(image_num)).unwrap());
+        }
+        future = Box::new(future.then_execute(self.queue.clone(), command_buffer).unwrap());
+        if let Some(timer) = &mut self.gpu_timer {
+            future = Box::new(future.then_execute(self.queue.clone(), timer.end_commands(image_num)).unwrap());
+        }
+
+        // Presenting on another queue has to wait for the rendering through a semaphore, on the same
+        // queue the submission order takes care of that
+        if self.present_queue.family().id() != self.queue.family().id() {
+            future = Box::new(future.then_signal_semaphore());
+        }
+
+        let future = Box::new(
+            future.then_swapchain_present(
+                self.present_queue.clone(),
+                self.swapchain.clone(),
+                image_num,
+            )
+        ) as Box<dyn GpuFuture + Send + Sync>;
+
+        self.frame_fences[image_num] = match future.then_signal_fence_and_flush() {
+            Ok(fence) => Some(Arc::new(fence)),
+            Err(FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                None
+            }
+            Err(FlushError::SurfaceLost) => {
+                println!("The surface got lost, recreating it");
+                self.surface_lost = true;
+                self.recreate_swapchain = true;
+                None
+            }
+            Err(e) => {
+                println!("Failed to flush future: {:?}", e);
+                None
+            }
+        };
+        self.previous_fence_index = image_num;
+
+        if self.frame_fences[image_num].is_none() {
+            if let Some(timer) = &mut self.gpu_timer {
+                timer.frame_dropped(image_num);
+            }
+        }
+
+        if let Some((path, image, buffer)) = screenshot {
+            let fence = match &self.frame_fences[image_num] {
+                Some(fence) => fence,
+                None => {
+                    // Nothing got rendered, try again next frame
+                    self.screenshot_path = Some(path);
+                    return;
+                }
+            };
+            fence.wait(None)
+                .unwrap();
+
+            let pixels = buffer.read()
+                .unwrap()
+                .to_vec();
+            match save_png(&path, image.swapchain().format(), image.dimensions(), pixels) {
+                Ok(()) => println!("Saved a screenshot to {}", path.display()),
+                Err(e) => println!("Failed to save the screenshot: {}", e),
+            }
+        }
+    }
+}
+
+// Renders a single frame into the headless context's image and reads it back as RGBA8. Uses target
+// 0, so the scene has to have been created with the context's render pass
+pub fn render_offscreen(context: &HeadlessContext, scene: &mut Scene, time: f32, camera: &Camera) -> Vec<u8> {
+    let dynamic_state = DynamicState {
+        viewports: Some(vec!(full_viewport(context.dimensions))),
+        ..DynamicState::none()
+    };
+    scene.record_static_draws(0, 1, &dynamic_state);
+
+    let buffer = readback_buffer(&context.device, context.dimensions);
+
+    let builder = AutoCommandBufferBuilder::primary_one_time_submit(
+        context.device.clone(),
+        context.queue.family(),
+    )
+        .unwrap();
+
+    let command_buffer = scene.record(
+        0,
+        builder,
+        context.framebuffer.clone(),
+        0,
+        time,
+        camera,
+    )
+        .copy_image_to_buffer(context.image.clone(), buffer.clone())
+        .unwrap()
+        .build()
+        .unwrap();
+
+    sync::now(context.device.clone())
+        .then_execute(context.queue.clone(), command_buffer)
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap()
+        .wait(None)
+        .unwrap();
+
+    let pixels = buffer.read()
+        .unwrap();
+    pixels.to_vec()
+}
+
+// Not checked by anything: the modules have to match the interface of vert.glsl and frag.glsl,
+// which is the one we hand to the pipeline
+unsafe fn vertex_entry_point(module: &ShaderModule) -> VertexEntryPoint<'_> {
+    module.graphics_entry_point(
+        CStr::from_bytes_with_nul(b"main\0").unwrap(),
+        vs::MainInput,
+        vs::MainOutput,
+        vs::Layout(ShaderStages { vertex: true, ..ShaderStages::none() }),
+        GraphicsShaderType::Vertex,
+    )
+}
+
+unsafe fn fragment_entry_point(module: &ShaderModule) -> FragmentEntryPoint<'_> {
+    module.graphics_entry_point(
+        CStr::from_bytes_with_nul(b"main\0").unwrap(),
+        fs::MainInput,
+        fs::MainOutput,
+        fs::Layout(ShaderStages { fragment: true, ..ShaderStages::none() }),
+        GraphicsShaderType::Fragment,
+    )
+}
+
+// Room for an RGBA8 copy of an image of that size
+fn readback_buffer(device: &Arc<Device>, dimensions: [u32; 2]) -> Arc<CpuAccessibleBuffer<[u8]>> {
+    CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::transfer_destination(),
+        false,
+        (0..dimensions[0] * dimensions[1] * 4).map(|_| 0u8),
+    )
+        .unwrap()
+}
+
+mod vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path: "src/vert.glsl"
+    }
+}
+
+mod vs_affine {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path: "src/vert.glsl",
+        define: [("AFFINE", "1")]
+    }
+}
+
+mod vs_instanced {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path: "src/vert_instanced.glsl"
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/frag.glsl"
+    }
+}
+
+mod vs_fullscreen {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path: "src/post_vert.glsl"
+    }
+}
+
+mod fs_decal {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/frag_decal.glsl"
+    }
+}
+
+mod fs_decal_ms {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/frag_decal.glsl",
+        define: [("MULTISAMPLED", "1")]
+    }
+}
+
+mod fs_overdraw {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/frag_overdraw.glsl"
+    }
+}
+
+mod fs_unlit {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/frag_unlit.glsl"
+    }
+}
+
+mod fs2 {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/frag2.glsl"
+    }
+}
+
+mod fs_textured {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/frag_textured.glsl"
+    }
+}
+
+mod fs_uv_grid {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/frag_uv_grid.glsl"
+    }
+}
+
+mod fs_textured_affine {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/frag_textured.glsl",
+        define: [("AFFINE", "1")]
+    }
+}
+
+mod fs_uv_grid_affine {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/frag_uv_grid.glsl",
+        define: [("AFFINE", "1")]
+    }
+}
+
+mod vs_shape {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path: "src/vert_shape.glsl"
+    }
+}
+
+mod fs_shape {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/frag_shape.glsl"
+    }
+}
+
+mod fs_depth {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/frag_depth.glsl"
+    }
+}
+
+mod fs_transparent {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/frag_transparent.glsl"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_axis_from_arg() {
+        assert!(matches!(RotationAxis::from_arg("x"), Some(RotationAxis::X)));
+        assert!(matches!(RotationAxis::from_arg("z"), Some(RotationAxis::Z)));
+        assert!(matches!(RotationAxis::from_arg("1, 0.5,0"), Some(RotationAxis::Arbitrary(axis)) if axis == Vector3::new(1.0, 0.5, 0.0)));
+        // Not three numbers, or nothing to normalize
+        assert!(RotationAxis::from_arg("1,0").is_none());
+        assert!(RotationAxis::from_arg("w").is_none());
+        assert!(RotationAxis::from_arg("0,0,0").is_none());
+    }
+}
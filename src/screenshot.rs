@@ -0,0 +1,55 @@
+use vulkano::format::Format;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ScreenshotError {
+    UnsupportedFormat(Format),
+    Save(io::Error),
+}
+
+impl fmt::Display for ScreenshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScreenshotError::UnsupportedFormat(format) =>
+                write!(f, "don't know how to save images in the {:?} format", format),
+            ScreenshotError::Save(e) => write!(f, "failed to write the PNG: {}", e),
+        }
+    }
+}
+
+impl Error for ScreenshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScreenshotError::Save(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ScreenshotError {
+    fn from(e: io::Error) -> ScreenshotError {
+        ScreenshotError::Save(e)
+    }
+}
+
+// The bytes of an sRGB image are already gamma encoded, which is exactly what PNG stores, so they're
+// written as is. Converting them to "linear" first would make the screenshot darker than the screen.
+// Most surfaces hand out BGRA though, so those get their red and blue swapped
+pub fn save_png(path: &Path, format: Format, dimensions: [u32; 2], mut pixels: Vec<u8>) -> Result<(), ScreenshotError> {
+    match format {
+        Format::R8G8B8A8Srgb | Format::R8G8B8A8Unorm => (),
+        Format::B8G8R8A8Srgb | Format::B8G8R8A8Unorm => {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        _ => return Err(ScreenshotError::UnsupportedFormat(format)),
+    }
+
+    image::save_buffer(path, &pixels, dimensions[0], dimensions[1], image::ColorType::RGBA(8))?;
+    Ok(())
+}
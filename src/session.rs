@@ -0,0 +1,346 @@
+use serde::{Deserialize, Serialize};
+use cgmath::{Deg, Point3, Quaternion, Rad, Vector3};
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::camera::{Camera, Keyframe};
+use crate::decal::Decal;
+use crate::demo::DEFAULT_KEYFRAME_DURATION;
+use crate::renderer::Scene;
+
+// Everything --session file.toml restores on the next launch. It gets saved back to the same file
+// with F5 and when the demo quits. Where the settings are how the demo starts, this is whatever
+// state it was left in, e.g.
+//
+//     animation_time = 12.5
+//     paused = true
+//     color_mode = "sepia"
+//
+//     [assets]
+//     model = "models/teapot.obj"
+//
+//     [camera]
+//     position = [0.0, 0.5, 2.0]
+//     target = [0.0, 0.0, 0.0]
+//
+// Anything left out keeps its default. The flags still win, a flag that turns something on turns
+// it on whatever the session says
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub animation_time: f32,
+    pub paused: bool,
+    pub show_fps: bool,
+    // Into CLEAR_COLORS, one past the end is the hue cycle. None keeps the settings' clear color
+    pub clear_color_index: usize,
+    pub clear_color: Option<[f32; 4]>,
+    // ColorMode::name
+    pub color_mode: String,
+    // The strength, None while it's off
+    pub dither: Option<f32>,
+    pub wireframe: bool,
+    pub show_axes: bool,
+    pub show_bounds: bool,
+    pub show_transparent: bool,
+    pub stencil_mask: bool,
+    pub uv_grid: bool,
+    pub affine_uvs: bool,
+    pub label_depth_test: bool,
+    pub rotation_speed: f32,
+    pub orbit_camera: bool,
+    pub keyframe_duration: f32,
+    // TOML wants the tables after everything else
+    pub assets: Assets,
+    pub camera: CameraState,
+    pub keyframes: Vec<KeyframeState>,
+    pub decals: Vec<DecalState>,
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session {
+            animation_time: 0.0,
+            paused: false,
+            show_fps: false,
+            clear_color_index: 0,
+            clear_color: None,
+            color_mode: "normal".to_string(),
+            dither: None,
+            wireframe: false,
+            show_axes: false,
+            show_bounds: false,
+            show_transparent: false,
+            stencil_mask: false,
+            uv_grid: false,
+            affine_uvs: false,
+            label_depth_test: false,
+            rotation_speed: 1.0,
+            orbit_camera: false,
+            keyframe_duration: DEFAULT_KEYFRAME_DURATION,
+            assets: Assets::default(),
+            camera: CameraState::from(&Camera::default()),
+            keyframes: vec!(),
+            decals: vec!(),
+        }
+    }
+}
+
+impl Session {
+    // What's saved of the scene, before any of the flags for the same things
+    pub fn apply_to_scene(&self, scene: &mut Scene) {
+        scene.wireframe = self.wireframe;
+        scene.show_axes = self.show_axes;
+        scene.show_bounds = self.show_bounds;
+        scene.show_transparent = self.show_transparent;
+        scene.stencil_mask = self.stencil_mask;
+        scene.uv_grid = self.uv_grid;
+        scene.affine_uvs = self.affine_uvs;
+        scene.label_depth_test = self.label_depth_test;
+        scene.rotation_speed = self.rotation_speed;
+        for decal in &self.decals {
+            scene.add_decal(Decal::from(decal));
+        }
+    }
+}
+
+// The files the scene was made from, what --model, --shape, --texture and --decal were
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Assets {
+    pub model: Option<PathBuf>,
+    pub shape: Option<String>,
+    pub texture: Option<PathBuf>,
+    pub decal: Option<PathBuf>,
+}
+
+impl Assets {
+    // A file that has been moved or deleted since gets left out with a message, so the rest of
+    // the session still loads with the default in its place
+    pub fn without_missing(mut self) -> Assets {
+        for (name, path) in &mut [("model", &mut self.model), ("texture", &mut self.texture), ("decal", &mut self.decal)] {
+            if path.as_ref().is_some_and(|path| !path.exists()) {
+                println!("The session's {} {} is missing, using the default", name, path.as_ref().unwrap().display());
+                **path = None;
+            }
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraState {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    pub fov_degrees: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl From<&Camera> for CameraState {
+    fn from(camera: &Camera) -> CameraState {
+        CameraState {
+            position: camera.position.into(),
+            target: camera.target.into(),
+            up: camera.up.into(),
+            fov_degrees: Deg::from(camera.fov).0,
+            near: camera.near,
+            far: camera.far,
+        }
+    }
+}
+
+impl From<&CameraState> for Camera {
+    fn from(state: &CameraState) -> Camera {
+        Camera {
+            position: Point3::from(state.position),
+            target: Point3::from(state.target),
+            up: Vector3::from(state.up),
+            fov: Rad::from(Deg(state.fov_degrees)),
+            near: state.near,
+            far: state.far,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyframeState {
+    pub position: [f32; 3],
+    // x, y, z and w
+    pub orientation: [f32; 4],
+}
+
+impl From<&Keyframe> for KeyframeState {
+    fn from(keyframe: &Keyframe) -> KeyframeState {
+        let orientation = keyframe.orientation;
+        KeyframeState {
+            position: keyframe.position.into(),
+            orientation: [orientation.v.x, orientation.v.y, orientation.v.z, orientation.s],
+        }
+    }
+}
+
+impl From<&KeyframeState> for Keyframe {
+    fn from(state: &KeyframeState) -> Keyframe {
+        let [x, y, z, w] = state.orientation;
+        Keyframe {
+            position: Point3::from(state.position),
+            orientation: Quaternion::new(w, x, y, z),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecalState {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub size: f32,
+}
+
+impl From<&Decal> for DecalState {
+    fn from(decal: &Decal) -> DecalState {
+        DecalState {
+            position: decal.position.into(),
+            normal: decal.normal.into(),
+            size: decal.size,
+        }
+    }
+}
+
+impl From<&DecalState> for Decal {
+    fn from(state: &DecalState) -> Decal {
+        Decal {
+            position: Point3::from(state.position),
+            normal: Vector3::from(state.normal),
+            size: state.size,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::Io(e) => write!(f, "{}", e),
+            SessionError::Parse(e) => write!(f, "failed to parse the session: {}", e),
+            SessionError::Serialize(e) => write!(f, "failed to write the session: {}", e),
+        }
+    }
+}
+
+impl Error for SessionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SessionError::Io(e) => Some(e),
+            SessionError::Parse(e) => Some(e),
+            SessionError::Serialize(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for SessionError {
+    fn from(e: io::Error) -> SessionError {
+        SessionError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for SessionError {
+    fn from(e: toml::de::Error) -> SessionError {
+        SessionError::Parse(e)
+    }
+}
+
+impl From<toml::ser::Error> for SessionError {
+    fn from(e: toml::ser::Error) -> SessionError {
+        SessionError::Serialize(e)
+    }
+}
+
+// None when there's no file yet, the first run with --session starts from scratch and creates it
+pub fn load_session(path: &Path) -> Result<Option<Session>, SessionError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Some(toml::from_str(&text)?))
+}
+
+pub fn save_session(path: &Path, session: &Session) -> Result<(), SessionError> {
+    fs::write(path, toml::to_string_pretty(session)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("vulkan-sandbox-{}-{}.toml", name, std::process::id()))
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let session = Session {
+            paused: true,
+            clear_color: Some([0.1, 0.2, 0.3, 1.0]),
+            color_mode: "sepia".to_string(),
+            dither: Some(1.5),
+            assets: Assets { model: Some(PathBuf::from("teapot.obj")), ..Assets::default() },
+            keyframes: vec!(KeyframeState { position: [1.0, 2.0, 3.0], orientation: [0.0, 0.0, 0.0, 1.0] }),
+            decals: vec!(DecalState { position: [0.0, 0.0, 0.0], normal: [0.0, 0.0, 1.0], size: 0.3 }),
+            ..Session::default()
+        };
+        let path = temp_path("round-trip");
+        save_session(&path, &session)
+            .unwrap();
+        let loaded = load_session(&path)
+            .unwrap();
+        fs::remove_file(&path)
+            .unwrap();
+
+        assert_eq!(loaded, Some(session));
+    }
+
+    #[test]
+    fn missing_file_is_no_session() {
+        assert_eq!(load_session(&temp_path("missing")).unwrap(), None);
+    }
+
+    #[test]
+    fn left_out_fields_keep_their_defaults() {
+        let session: Session = toml::from_str("wireframe = true\n[camera]\nposition = [0.0, 1.0, 2.0]\ntarget = [0.0, 0.0, 0.0]\nup = [0.0, 1.0, 0.0]\nfov_degrees = 60.0\nnear = 0.1\nfar = 100.0\n")
+            .unwrap();
+        assert!(session.wireframe);
+        assert_eq!(session.rotation_speed, 1.0);
+        assert_eq!(session.camera.position, [0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn drops_missing_assets() {
+        let assets = Assets {
+            model: Some(temp_path("no-such-model")),
+            shape: Some("cube".to_string()),
+            ..Assets::default()
+        };
+        assert_eq!(assets.without_missing(), Assets { shape: Some("cube".to_string()), ..Assets::default() });
+    }
+
+    #[test]
+    fn camera_survives_the_conversion() {
+        let camera = Camera { position: Point3::new(1.0, 2.0, 3.0), ..Camera::default() };
+        let restored = Camera::from(&CameraState::from(&camera));
+        assert_eq!(restored.position, camera.position);
+        assert!((restored.fov.0 - camera.fov.0).abs() < 1e-6);
+    }
+}
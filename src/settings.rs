@@ -0,0 +1,208 @@
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use vulkano::swapchain::PresentMode;
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::demo::CLEAR_COLORS;
+use crate::vulkan::device::DeviceSelector;
+
+// Read from --config or sandbox.toml in the working directory, e.g.
+//
+//     device = "discrete"
+//     present_mode = "mailbox"
+//     msaa = 4
+//     window_size = [1280, 720]
+//     clear_color = [0.0, 0.0, 0.0, 1.0]
+//     max_texture_size = 2048
+//
+// Anything left out keeps its default, the command line flags for the same things win over the file
+pub const DEFAULT_SETTINGS_PATH: &str = "sandbox.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    // "first", "discrete", "integrated" or "name:" and (part of) a device name, see deserialize_device
+    #[serde(deserialize_with = "deserialize_device")]
+    pub device: DeviceSelector,
+    // "fifo", "mailbox" or "immediate"
+    #[serde(deserialize_with = "deserialize_present_mode")]
+    pub present_mode: PresentMode,
+    // Same as --msaa
+    pub msaa: u32,
+    // In logical pixels
+    pub window_size: [u32; 2],
+    // In sRGB, C still cycles through the built-in ones after it
+    pub clear_color: [f32; 4],
+    // Same as --max-texture-size, the device's limit applies either way
+    pub max_texture_size: Option<u32>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            device: DeviceSelector::First,
+            present_mode: PresentMode::Fifo,
+            msaa: 1,
+            window_size: [800, 600],
+            clear_color: CLEAR_COLORS[0],
+            max_texture_size: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Read(io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Read(e) => write!(f, "failed to read the file: {}", e),
+            SettingsError::Parse(e) => write!(f, "failed to parse the settings: {}", e),
+            SettingsError::Invalid(message) => write!(f, "invalid settings: {}", message),
+        }
+    }
+}
+
+impl Error for SettingsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SettingsError::Read(e) => Some(e),
+            SettingsError::Parse(e) => Some(e),
+            SettingsError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for SettingsError {
+    fn from(e: io::Error) -> SettingsError {
+        SettingsError::Read(e)
+    }
+}
+
+impl From<toml::de::Error> for SettingsError {
+    fn from(e: toml::de::Error) -> SettingsError {
+        SettingsError::Parse(e)
+    }
+}
+
+// A missing file just means the defaults, anything else wrong with it is an error
+pub fn load_settings(path: &Path) -> Result<Settings, SettingsError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Settings::default()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let settings: Settings = toml::from_str(&text)?;
+    if !settings.msaa.is_power_of_two() || settings.msaa > 64 {
+        return Err(SettingsError::Invalid(
+            format!("msaa is {}, expected 1, 2, 4, 8, 16, 32 or 64", settings.msaa)
+        ));
+    }
+    if settings.window_size[0] == 0 || settings.window_size[1] == 0 {
+        return Err(SettingsError::Invalid("window_size can't be 0".to_string()));
+    }
+    if settings.max_texture_size == Some(0) {
+        return Err(SettingsError::Invalid("max_texture_size can't be 0".to_string()));
+    }
+
+    println!("Loaded the settings from {}", path.display());
+    Ok(settings)
+}
+
+// Unlike --device, names need the prefix. Otherwise a typo in the file like "discreet" would quietly
+// turn into looking for a device called that
+fn deserialize_device<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DeviceSelector, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    match value.as_str() {
+        "first" | "discrete" | "integrated" => Ok(DeviceSelector::from_arg(&value)),
+        _ => match value.strip_prefix("name:") {
+            Some(name) if !name.is_empty() => Ok(DeviceSelector::ByName(name.to_string())),
+            _ => Err(D::Error::custom(format!(
+                "unknown device {}, expected first, discrete, integrated or name: followed by a device name",
+                value,
+            ))),
+        },
+    }
+}
+
+fn deserialize_present_mode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PresentMode, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    match name.as_str() {
+        "fifo" => Ok(PresentMode::Fifo),
+        "mailbox" => Ok(PresentMode::Mailbox),
+        "immediate" => Ok(PresentMode::Immediate),
+        _ => Err(D::Error::custom(format!("unknown present mode {}, expected fifo, mailbox or immediate", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Written to a file of its own for every test, so they can run at the same time
+    fn load(name: &str, text: &str) -> Result<Settings, SettingsError> {
+        let path = std::env::temp_dir().join(format!("vulkan-sandbox-settings-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, text)
+            .unwrap();
+        let settings = load_settings(&path);
+        fs::remove_file(&path)
+            .unwrap();
+        settings
+    }
+
+    #[test]
+    fn missing_file_is_the_defaults() {
+        let path = std::env::temp_dir().join(format!("vulkan-sandbox-no-settings-{}.toml", std::process::id()));
+        let settings = load_settings(&path)
+            .unwrap();
+        assert_eq!(settings.msaa, 1);
+        assert_eq!(settings.window_size, [800, 600]);
+    }
+
+    #[test]
+    fn reads_what_is_there() {
+        let settings = load("read", "msaa = 4\nwindow_size = [1280, 720]\npresent_mode = \"mailbox\"\n")
+            .unwrap();
+        assert_eq!(settings.msaa, 4);
+        assert_eq!(settings.window_size, [1280, 720]);
+        assert_eq!(settings.present_mode, PresentMode::Mailbox);
+        assert_eq!(settings.max_texture_size, None);
+    }
+
+    #[test]
+    fn rejects_bad_msaa() {
+        assert!(matches!(load("msaa", "msaa = 3\n"), Err(SettingsError::Invalid(_))));
+        assert!(matches!(load("msaa-high", "msaa = 128\n"), Err(SettingsError::Invalid(_))));
+    }
+
+    #[test]
+    fn rejects_zero_window_size() {
+        assert!(matches!(load("window", "window_size = [0, 600]\n"), Err(SettingsError::Invalid(_))));
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(matches!(load("unknown", "msa = 4\n"), Err(SettingsError::Parse(_))));
+    }
+
+    #[test]
+    fn device_names_need_the_prefix() {
+        assert!(matches!(load("discrete", "device = \"discrete\"\n").unwrap().device, DeviceSelector::PreferDiscrete));
+        match load("name", "device = \"name:GeForce\"\n").unwrap().device {
+            DeviceSelector::ByName(name) => assert_eq!(name, "GeForce"),
+            device => panic!("expected a name, got {:?}", device),
+        }
+        assert!(matches!(load("typo", "device = \"discreet\"\n"), Err(SettingsError::Parse(_))));
+        assert!(matches!(load("empty-name", "device = \"name:\"\n"), Err(SettingsError::Parse(_))));
+    }
+}
@@ -0,0 +1,128 @@
+// Flat 2D shapes for the overlay, drawn with Scene::draw_shape. Each one is a quad a bit bigger
+// than the shape, frag_shape.glsl works out from the signed distance to the rounded rectangle how
+// much of every pixel is inside. That comes out antialiased at any size without MSAA
+
+// The quads reach this many pixels past the shape, for the antialiased edge
+const EDGE: f32 = 1.0;
+
+// Rectangles with rounded corners, a circle is one with a radius of half its size. The colors are
+// in sRGB with straight alpha, like Scene::clear_color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shape {
+    // In pixels from the top left corner of the viewport
+    pub center: [f32; 2],
+    pub half_size: [f32; 2],
+    pub corner_radius: f32,
+    pub fill: [f32; 4],
+    // Drawn on the inside of the edge, in pixels. 0 for none
+    pub border_width: f32,
+    pub border: [f32; 4],
+}
+
+impl Shape {
+    // x and y are the top left corner. Half see-through black with no border until fill and
+    // border say otherwise
+    pub fn rounded_rect(x: f32, y: f32, width: f32, height: f32, corner_radius: f32) -> Shape {
+        let half_size = [width / 2.0, height / 2.0];
+        Shape {
+            center: [x + half_size[0], y + half_size[1]],
+            half_size,
+            // More than that would make the corners overlap
+            corner_radius: corner_radius.clamp(0.0, half_size[0].min(half_size[1])),
+            fill: [0.0, 0.0, 0.0, 0.6],
+            border_width: 0.0,
+            border: [0.0; 4],
+        }
+    }
+
+    pub fn circle(x: f32, y: f32, radius: f32) -> Shape {
+        Shape::rounded_rect(x - radius, y - radius, radius * 2.0, radius * 2.0, radius)
+    }
+
+    pub fn fill(self, color: [f32; 4]) -> Shape {
+        Shape { fill: color, ..self }
+    }
+
+    pub fn border(self, color: [f32; 4], width: f32) -> Shape {
+        Shape { border: color, border_width: width, ..self }
+    }
+}
+
+// Everything but the position is the same in all six vertices of a shape
+#[derive(Default, Debug, Clone)]
+pub struct ShapeVertex {
+    // Straight in normalized device coordinates, vert_shape.glsl doesn't transform anything
+    pub position: [f32; 2],
+    // In pixels from the center of the shape
+    pub local: [f32; 2],
+    pub half_size: [f32; 2],
+    pub corner_radius: f32,
+    pub border_width: f32,
+    // Already converted for the attachment, see shape_vertices
+    pub fill: [f32; 4],
+    pub border: [f32; 4],
+}
+
+vulkano::impl_vertex!(ShapeVertex, position, local, half_size, corner_radius, border_width, fill, border);
+
+// Two triangles, with the colors already run through convert (clear_color_for, for sRGB attachments)
+pub fn shape_vertices(shape: &Shape, viewport: [f32; 2], convert: impl Fn([f32; 4]) -> [f32; 4]) -> Vec<ShapeVertex> {
+    let [half_width, half_height] = shape.half_size;
+    let (fill, border) = (convert(shape.fill), convert(shape.border));
+
+    let corner = |dx: f32, dy: f32| {
+        let x = shape.center[0] + dx;
+        let y = shape.center[1] + dy;
+        ShapeVertex {
+            position: [x / viewport[0] * 2.0 - 1.0, y / viewport[1] * 2.0 - 1.0],
+            local: [dx, dy],
+            half_size: shape.half_size,
+            corner_radius: shape.corner_radius,
+            border_width: shape.border_width,
+            fill,
+            border,
+        }
+    };
+    let left = -half_width - EDGE;
+    let right = half_width + EDGE;
+    let top = -half_height - EDGE;
+    let bottom = half_height + EDGE;
+    let top_left = corner(left, top);
+    let bottom_left = corner(left, bottom);
+    let bottom_right = corner(right, bottom);
+    let top_right = corner(right, top);
+
+    vec!(top_left.clone(), bottom_left, bottom_right.clone(), bottom_right, top_right, top_left)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corner_radius_stays_inside() {
+        let shape = Shape::rounded_rect(0.0, 0.0, 40.0, 10.0, 20.0);
+        assert_eq!(shape.corner_radius, 5.0);
+        assert_eq!(shape.center, [20.0, 5.0]);
+    }
+
+    #[test]
+    fn circle_is_round() {
+        let circle = Shape::circle(50.0, 50.0, 8.0);
+        assert_eq!(circle.center, [50.0, 50.0]);
+        assert_eq!(circle.half_size, [8.0, 8.0]);
+        assert_eq!(circle.corner_radius, 8.0);
+    }
+
+    #[test]
+    fn quad_covers_the_edge() {
+        let shape = Shape::rounded_rect(10.0, 20.0, 100.0, 50.0, 4.0);
+        let vertices = shape_vertices(&shape, [200.0, 100.0], |color| color);
+        assert_eq!(vertices.len(), 6);
+
+        // One pixel past the top left corner at (10, 20)
+        assert_eq!(vertices[0].local, [-51.0, -26.0]);
+        assert_eq!(vertices[0].position, [9.0 / 200.0 * 2.0 - 1.0, 19.0 / 100.0 * 2.0 - 1.0]);
+        assert!(vertices.iter().all(|vertex| vertex.half_size == [50.0, 25.0]));
+    }
+}
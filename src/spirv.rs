@@ -0,0 +1,56 @@
+use vulkano::device::Device;
+use vulkano::pipeline::shader::ShaderModule;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::vulkan::shader::ShaderRequirements;
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+// Precompiled replacements for the baked in shaders. They have to keep the inputs, outputs and
+// descriptors of the GLSL they replace since the pipeline layout is only known at compile time
+#[derive(Default)]
+pub struct ShaderPaths {
+    pub vertex: Option<PathBuf>,
+    pub fragment: Option<PathBuf>,
+}
+
+// Anything that goes wrong here just means falling back to the baked in shader, so it only gets
+// printed instead of returned
+pub fn load_spirv(device: &Arc<Device>, path: &Path) -> Option<Arc<ShaderModule>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            println!("Couldn't read {}: {}, using the built-in shader", path.display(), e);
+            return None;
+        }
+    };
+
+    // Vulkan doesn't check any of this itself and the driver tends to just crash on garbage
+    if !bytes.len().is_multiple_of(4) || bytes.len() < 4 || u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) != SPIRV_MAGIC {
+        println!("{} is not a SPIR-V file, using the built-in shader", path.display());
+        return None;
+    }
+
+    // Anything the generated Shader::load would have checked for the built-in one
+    let words = bytes.chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect::<Vec<_>>();
+    if let Err(e) = ShaderRequirements::from_spirv(&words).check(device, &path.display().to_string()) {
+        println!("{}, using the built-in shader", e);
+        return None;
+    }
+
+    match unsafe { ShaderModule::new(device.clone(), &bytes) } {
+        Ok(module) => {
+            println!("Using shader {}", path.display());
+            Some(module)
+        }
+        Err(e) => {
+            println!("Failed to create a shader module from {}: {}, using the built-in shader", path.display(), e);
+            None
+        }
+    }
+}
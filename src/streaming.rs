@@ -0,0 +1,219 @@
+use vulkano::device::Queue;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{channel, sync_channel, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+use crate::mesh::{load_obj, BufferError, LoadError, MeshUsage, Mesh, UploadBatch};
+use crate::renderer::Scene;
+use crate::texture::{load_texture, SamplerConfig, Texture, TextureError};
+
+// How many requests can wait for the thread before request turns new ones away, unless
+// --stream-queue says otherwise
+pub const DEFAULT_QUEUE_LENGTH: usize = 4;
+
+// What the extension of a streamed file makes it
+pub enum Asset {
+    Mesh(Mesh),
+    Texture(Texture),
+}
+
+#[derive(Debug)]
+pub enum StreamError {
+    // Neither an OBJ file nor an image
+    UnknownKind(PathBuf),
+    Mesh(LoadError),
+    Upload(BufferError),
+    Texture(TextureError),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamError::UnknownKind(path) => write!(f, "{} is neither an OBJ file nor an image", path.display()),
+            StreamError::Mesh(e) => write!(f, "{}", e),
+            StreamError::Upload(e) => write!(f, "failed to upload the mesh: {}", e),
+            StreamError::Texture(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for StreamError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StreamError::UnknownKind(_) => None,
+            StreamError::Mesh(e) => Some(e),
+            StreamError::Upload(e) => Some(e),
+            StreamError::Texture(e) => Some(e),
+        }
+    }
+}
+
+impl From<LoadError> for StreamError {
+    fn from(e: LoadError) -> StreamError {
+        StreamError::Mesh(e)
+    }
+}
+
+impl From<BufferError> for StreamError {
+    fn from(e: BufferError) -> StreamError {
+        StreamError::Upload(e)
+    }
+}
+
+impl From<TextureError> for StreamError {
+    fn from(e: TextureError) -> StreamError {
+        StreamError::Texture(e)
+    }
+}
+
+// How the streamed textures get loaded, the same as --texture
+#[derive(Debug, Clone)]
+pub struct TextureOptions {
+    pub mipmaps: bool,
+    pub max_size: Option<u32>,
+    pub sampler_config: SamplerConfig,
+}
+
+// Gets called on the main thread by finish_loaded, with the scene the asset goes into
+type Callback = Box<dyn FnOnce(&mut Scene, Result<Asset, StreamError>)>;
+
+// Loads files and uploads them on a thread of its own, on the transfer queue where the device has
+// one, so the frames keep coming while that happens. Everything gets waited for on that thread,
+// by the time finish_loaded hands an asset over it's ready to draw. The buffers and images are
+// shared between all the queue families (see UploadBatch and upload_levels), so they need no
+// ownership transfer to get from the transfer queue to the graphics queue
+pub struct AssetStreamer {
+    // Bounded, None once it's being dropped so the thread sees the end of it
+    requests: Option<SyncSender<(u64, PathBuf)>>,
+    loaded: Receiver<(u64, Result<Asset, StreamError>)>,
+    callbacks: HashMap<u64, Callback>,
+    next_id: u64,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AssetStreamer {
+    pub fn new(queue: &Arc<Queue>, queue_length: usize, texture_options: TextureOptions) -> AssetStreamer {
+        let (requests, requested) = sync_channel::<(u64, PathBuf)>(queue_length);
+        let (finished, loaded) = channel();
+        let queue = queue.clone();
+
+        let thread = thread::spawn(move || {
+            // Ends when the streamer is dropped, after whatever was being loaded is done
+            for (id, path) in requested {
+                let asset = load_asset(&queue, &path, &texture_options);
+                if finished.send((id, asset)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        AssetStreamer {
+            requests: Some(requests),
+            loaded,
+            callbacks: HashMap::new(),
+            next_id: 0,
+            thread: Some(thread),
+        }
+    }
+
+    // on_loaded gets the asset (or why it couldn't be loaded) at the start of a frame, see
+    // finish_loaded. Gives the path back when queue_length requests are already waiting
+    pub fn request(&mut self, path: PathBuf, on_loaded: impl FnOnce(&mut Scene, Result<Asset, StreamError>) + 'static)
+                   -> Result<(), PathBuf> {
+        let id = self.next_id;
+        let requests = self.requests.as_ref()
+            .expect("the requests are only taken when dropping");
+        match requests.try_send((id, path)) {
+            Ok(()) => {
+                self.next_id += 1;
+                self.callbacks.insert(id, Box::new(on_loaded));
+                Ok(())
+            }
+            Err(TrySendError::Full((_, path))) | Err(TrySendError::Disconnected((_, path))) => Err(path),
+        }
+    }
+
+    // Requests that haven't been handed over yet, waiting or being loaded
+    pub fn pending(&self) -> usize {
+        self.callbacks.len()
+    }
+
+    // Between frames, so nothing that's being recorded changes underneath. Calls the callbacks of
+    // everything that finished since the last time, without waiting for anything else
+    pub fn finish_loaded(&mut self, scene: &mut Scene) {
+        for (id, asset) in self.loaded.try_iter() {
+            if let Some(callback) = self.callbacks.remove(&id) {
+                callback(scene, asset);
+            }
+        }
+    }
+}
+
+impl Drop for AssetStreamer {
+    fn drop(&mut self) {
+        self.requests = None;
+        if let Some(thread) = self.thread.take() {
+            // A panic on the thread already got printed
+            let _ = thread.join();
+        }
+    }
+}
+
+enum AssetKind {
+    Mesh,
+    Texture,
+}
+
+fn asset_kind(path: &Path) -> Option<AssetKind> {
+    let extension = path.extension()?
+        .to_str()?
+        .to_lowercase();
+    match extension.as_str() {
+        "obj" => Some(AssetKind::Mesh),
+        "png" | "jpg" | "jpeg" | "ktx2" => Some(AssetKind::Texture),
+        _ => None,
+    }
+}
+
+// On the streaming thread
+fn load_asset(queue: &Arc<Queue>, path: &Path, texture_options: &TextureOptions) -> Result<Asset, StreamError> {
+    match asset_kind(path) {
+        Some(AssetKind::Mesh) => {
+            let mut uploads = UploadBatch::new(queue)?;
+            let mesh = load_obj(path, &mut uploads, MeshUsage::Static)?;
+            uploads.finish()?;
+            Ok(Asset::Mesh(mesh))
+        }
+        Some(AssetKind::Texture) => {
+            let texture = load_texture(
+                path,
+                queue,
+                true,
+                texture_options.mipmaps,
+                texture_options.max_size,
+                &texture_options.sampler_config,
+            )?;
+            Ok(Asset::Texture(texture))
+        }
+        None => Err(StreamError::UnknownKind(path.to_path_buf())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kind_goes_by_extension() {
+        assert!(matches!(asset_kind(Path::new("models/teapot.obj")), Some(AssetKind::Mesh)));
+        assert!(matches!(asset_kind(Path::new("textures/Brick.PNG")), Some(AssetKind::Texture)));
+        assert!(matches!(asset_kind(Path::new("textures/brick.ktx2")), Some(AssetKind::Texture)));
+        assert!(asset_kind(Path::new("notes.txt")).is_none());
+        assert!(asset_kind(Path::new("no_extension")).is_none());
+    }
+}
@@ -0,0 +1,282 @@
+use vulkano::device::Queue;
+use vulkano::sampler::{Filter, MipmapMode, SamplerAddressMode};
+use image::{Rgba, RgbaImage};
+use cgmath::{Matrix4, Point3};
+
+use std::sync::Arc;
+
+use crate::mesh::Vertex;
+use crate::texture::{texture_from_pixels, SamplerConfig, Texture, TextureError};
+
+// Every glyph is 5x7 pixels, in a cell with a pixel of space to the right, above and below. The
+// cells are laid out next to each other in one row of the atlas and drawn GLYPH_SCALE times as big
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const CELL_WIDTH: u32 = 6;
+const CELL_HEIGHT: u32 = 9;
+const GLYPH_SCALE: f32 = 2.0;
+
+// The rest of each cell is a half transparent black box, so the text stays readable on any background
+const BACKGROUND: Rgba<u8> = Rgba([0, 0, 0, 160]);
+const FOREGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+// What anything missing from the font gets drawn as. Lowercase letters are drawn as uppercase ones
+const FALLBACK: char = '?';
+
+// One row per line of the glyph, the highest of the 5 bits is the leftmost pixel
+const FONT: [(char, [u8; GLYPH_HEIGHT as usize]); 58] = [
+    (' ', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    (';', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b00100, 0b01000]),
+    ('(', [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010]),
+    (')', [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000]),
+    ('[', [0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110]),
+    (']', [0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('+', [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000]),
+    ('=', [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011]),
+    ('<', [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010]),
+    ('>', [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000]),
+    ('_', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111]),
+    ('\'', [0b01100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('"', [0b01010, 0b01010, 0b01010, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('!', [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100]),
+    ('?', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100]),
+    ('*', [0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000]),
+];
+
+// Nearest filtering keeps the pixels sharp, the quads are always a whole multiple of the cell size
+pub fn create_font_atlas(queue: &Arc<Queue>) -> Result<Texture, TextureError> {
+    let mut pixels = RgbaImage::from_pixel(CELL_WIDTH * FONT.len() as u32, CELL_HEIGHT, BACKGROUND);
+    for (cell, (_, rows)) in FONT.iter().enumerate() {
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                    pixels.put_pixel(cell as u32 * CELL_WIDTH + column, row as u32 + 1, FOREGROUND);
+                }
+            }
+        }
+    }
+
+    let sampler_config = SamplerConfig {
+        mag_filter: Filter::Nearest,
+        min_filter: Filter::Nearest,
+        mipmap_mode: MipmapMode::Nearest,
+        address_mode: SamplerAddressMode::ClampToEdge,
+    };
+    texture_from_pixels(pixels, queue, true, false, &sampler_config)
+}
+
+// Two triangles per character, straight in normalized device coordinates so they can go through
+// vert.glsl with identity matrices. x and y are in pixels from the top left corner of the
+// viewport, every \n starts a new line below the first one
+pub fn text_vertices(x: f32, y: f32, text: &str, viewport: [f32; 2]) -> Vec<Vertex> {
+    text_vertices_at_depth(x, y, 0.0, text, viewport)
+}
+
+// Same as text_vertices but at depth instead of 0, for labels that can be hidden behind the meshes
+pub fn text_vertices_at_depth(x: f32, y: f32, depth: f32, text: &str, viewport: [f32; 2]) -> Vec<Vertex> {
+    let cell_width = CELL_WIDTH as f32 * GLYPH_SCALE;
+    let cell_height = CELL_HEIGHT as f32 * GLYPH_SCALE;
+    let atlas_width = (CELL_WIDTH * FONT.len() as u32) as f32;
+
+    let to_ndc = |x: f32, y: f32| [x / viewport[0] * 2.0 - 1.0, y / viewport[1] * 2.0 - 1.0, depth];
+
+    let mut vertices = vec!();
+    for (line, text) in text.lines().enumerate() {
+        let top = y + line as f32 * cell_height;
+        for (column, c) in text.chars().enumerate() {
+            let left = x + column as f32 * cell_width;
+            let cell = glyph_cell(c);
+            let u_left = (cell * CELL_WIDTH) as f32 / atlas_width;
+            let u_right = ((cell + 1) * CELL_WIDTH) as f32 / atlas_width;
+
+            let corner = |dx: f32, dy: f32, u: f32, v: f32| Vertex {
+                position: to_ndc(left + dx, top + dy),
+                uv: [u, v],
+                ..Vertex::default()
+            };
+            let top_left = corner(0.0, 0.0, u_left, 0.0);
+            let bottom_left = corner(0.0, cell_height, u_left, 1.0);
+            let bottom_right = corner(cell_width, cell_height, u_right, 1.0);
+            let top_right = corner(cell_width, 0.0, u_right, 0.0);
+
+            vertices.extend_from_slice(&[
+                top_left.clone(), bottom_left, bottom_right.clone(),
+                bottom_right, top_right, top_left,
+            ]);
+        }
+    }
+    vertices
+}
+
+// Width and height in pixels of what text_vertices draws for text
+pub fn text_size(text: &str) -> [f32; 2] {
+    let columns = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+    let lines = text.lines().count();
+    [
+        columns as f32 * CELL_WIDTH as f32 * GLYPH_SCALE,
+        lines as f32 * CELL_HEIGHT as f32 * GLYPH_SCALE,
+    ]
+}
+
+// Where text has to go so it's centered on position, as x and y in pixels from the top left plus
+// the depth position ends up at. view_proj already has to be the Vulkan one (see
+// Camera::projection). None behind the camera or past the far plane, anything off screen gets
+// moved back onto the edge of it
+pub fn label_position(position: Point3<f32>, view_proj: Matrix4<f32>, text: &str, viewport: [f32; 2])
+                      -> Option<(f32, f32, f32)> {
+    let clip = view_proj * position.to_homogeneous();
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let depth = clip.z / clip.w;
+    if depth > 1.0 {
+        return None;
+    }
+
+    let [width, height] = text_size(text);
+    let center_x = (clip.x / clip.w + 1.0) / 2.0 * viewport[0];
+    let center_y = (clip.y / clip.w + 1.0) / 2.0 * viewport[1];
+    let x = (center_x - width / 2.0).min(viewport[0] - width).max(0.0);
+    let y = (center_y - height / 2.0).min(viewport[1] - height).max(0.0);
+    // Right in front of the near plane is still in front of the camera
+    Some((x, y, depth.max(0.0)))
+}
+
+fn glyph_cell(c: char) -> u32 {
+    let c = c.to_ascii_uppercase();
+    FONT.iter()
+        .position(|(glyph, _)| *glyph == c)
+        .or_else(|| FONT.iter().position(|(glyph, _)| *glyph == FALLBACK))
+        .unwrap() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+
+    const VIEWPORT: [f32; 2] = [800.0, 600.0];
+
+    fn view_proj() -> Matrix4<f32> {
+        let camera = Camera::default();
+        camera.projection(VIEWPORT[0] / VIEWPORT[1]) * camera.view()
+    }
+
+    #[test]
+    fn text_size_uses_the_longest_line() {
+        let cell_width = CELL_WIDTH as f32 * GLYPH_SCALE;
+        let cell_height = CELL_HEIGHT as f32 * GLYPH_SCALE;
+        assert_eq!(text_size("abc\nde"), [3.0 * cell_width, 2.0 * cell_height]);
+        assert_eq!(text_size(""), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn glyph_cells_are_case_insensitive() {
+        assert_eq!(glyph_cell(' '), 0);
+        assert_eq!(glyph_cell('0'), 1);
+        assert_eq!(glyph_cell('a'), glyph_cell('A'));
+        // Anything missing is the question mark
+        let fallback = glyph_cell(FALLBACK);
+        assert_eq!(FONT[fallback as usize].0, '?');
+        assert_eq!(glyph_cell('~'), fallback);
+        assert_eq!(glyph_cell('é'), fallback);
+    }
+
+    #[test]
+    fn text_vertices_are_two_triangles_per_character() {
+        let vertices = text_vertices(0.0, 0.0, "AB\nC", VIEWPORT);
+        assert_eq!(vertices.len(), 3 * 6);
+
+        // The first character's top left corner is the top left of the viewport
+        assert_eq!(vertices[0].position, [-1.0, -1.0, 0.0]);
+        assert_eq!(vertices[0].uv, [glyph_cell('A') as f32 / FONT.len() as f32, 0.0]);
+        // The next one is a cell to the right, the next line a cell further down
+        let cell_width = CELL_WIDTH as f32 * GLYPH_SCALE;
+        let cell_height = CELL_HEIGHT as f32 * GLYPH_SCALE;
+        assert_eq!(vertices[6].position[0], cell_width / VIEWPORT[0] * 2.0 - 1.0);
+        assert_eq!(vertices[12].position[..2], [-1.0, cell_height / VIEWPORT[1] * 2.0 - 1.0]);
+        // The bottom right corner spans the whole cell of the atlas
+        assert_eq!(vertices[2].uv, [(glyph_cell('A') + 1) as f32 / FONT.len() as f32, 1.0]);
+    }
+
+    #[test]
+    fn text_vertices_keep_the_depth() {
+        let vertices = text_vertices_at_depth(10.0, 20.0, 0.25, "X", VIEWPORT);
+        assert!(vertices.iter().all(|vertex| vertex.position[2] == 0.25));
+    }
+
+    #[test]
+    fn label_in_front_of_the_camera_is_centered() {
+        let (x, y, depth) = label_position(Point3::new(0.0, 0.0, 0.0), view_proj(), "X", VIEWPORT)
+            .unwrap();
+        let [width, height] = text_size("X");
+        assert!((x + width / 2.0 - VIEWPORT[0] / 2.0).abs() < 1e-3);
+        assert!((y + height / 2.0 - VIEWPORT[1] / 2.0).abs() < 1e-3);
+        assert!(depth > 0.0 && depth < 1.0);
+    }
+
+    #[test]
+    fn label_behind_the_camera_is_skipped() {
+        // The default camera is at z = 2 looking down -z
+        assert!(label_position(Point3::new(0.0, 0.0, 3.0), view_proj(), "X", VIEWPORT).is_none());
+    }
+
+    #[test]
+    fn label_past_the_far_plane_is_skipped() {
+        assert!(label_position(Point3::new(0.0, 0.0, -200.0), view_proj(), "X", VIEWPORT).is_none());
+    }
+
+    #[test]
+    fn label_off_screen_is_clamped_to_the_edge() {
+        let [width, height] = text_size("LABEL");
+        let (x, y, _) = label_position(Point3::new(-100.0, 100.0, 0.0), view_proj(), "LABEL", VIEWPORT)
+            .unwrap();
+        assert_eq!((x, y), (0.0, 0.0));
+
+        let (x, y, _) = label_position(Point3::new(100.0, -100.0, 0.0), view_proj(), "LABEL", VIEWPORT)
+            .unwrap();
+        assert_eq!((x, y), (VIEWPORT[0] - width, VIEWPORT[1] - height));
+    }
+}
@@ -0,0 +1,502 @@
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BuildError, CommandBuffer, CommandBufferExecError,
+                              CopyBufferImageError};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::{Dimensions, ImageCreationError, ImageLayout, ImageUsage, ImmutableImage, MipmapsCount};
+use vulkano::sampler::{BorderColor, Filter, MipmapMode, Sampler, SamplerAddressMode, SamplerCreationError};
+use vulkano::sync::{FlushError, GpuFuture};
+use image::{FilterType, RgbaImage};
+
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::ktx2::{load_ktx2, Ktx2, Ktx2Error};
+use crate::vulkan::format::optimal_tiling_features;
+
+// Same as VK_LOD_CLAMP_NONE, lets the sampler go all the way down the mip chain
+const MAX_LOD: f32 = 1000.0;
+
+// Which device feature a compressed format needs on top of being supported
+#[derive(Clone, Copy, PartialEq)]
+enum Compression {
+    None,
+    Bc,
+    Etc2,
+}
+
+// The VkFormat values KTX2 files can have that we know how to upload. BC is what desktop GPUs
+// have, ETC2 what mobile ones have
+const KTX2_FORMATS: [(u32, Format, Compression); 18] = [
+    (37, Format::R8G8B8A8Unorm, Compression::None),
+    (43, Format::R8G8B8A8Srgb, Compression::None),
+    (131, Format::BC1_RGBUnormBlock, Compression::Bc),
+    (132, Format::BC1_RGBSrgbBlock, Compression::Bc),
+    (133, Format::BC1_RGBAUnormBlock, Compression::Bc),
+    (134, Format::BC1_RGBASrgbBlock, Compression::Bc),
+    (137, Format::BC3UnormBlock, Compression::Bc),
+    (138, Format::BC3SrgbBlock, Compression::Bc),
+    (139, Format::BC4UnormBlock, Compression::Bc),
+    (141, Format::BC5UnormBlock, Compression::Bc),
+    (145, Format::BC7UnormBlock, Compression::Bc),
+    (146, Format::BC7SrgbBlock, Compression::Bc),
+    (147, Format::ETC2_R8G8B8UnormBlock, Compression::Etc2),
+    (148, Format::ETC2_R8G8B8SrgbBlock, Compression::Etc2),
+    (149, Format::ETC2_R8G8B8A1UnormBlock, Compression::Etc2),
+    (150, Format::ETC2_R8G8B8A1SrgbBlock, Compression::Etc2),
+    (151, Format::ETC2_R8G8B8A8UnormBlock, Compression::Etc2),
+    (152, Format::ETC2_R8G8B8A8SrgbBlock, Compression::Etc2),
+];
+
+pub struct Texture {
+    pub image: Arc<ImmutableImage<Format>>,
+    pub sampler: Arc<Sampler>,
+}
+
+// Linear and repeat by default. Nearest for both filters gives the blocky pixel art look
+#[derive(Debug, Clone, Copy)]
+pub struct SamplerConfig {
+    // When a texel covers more than one pixel
+    pub mag_filter: Filter,
+    // When more than one texel falls into a pixel
+    pub min_filter: Filter,
+    // Between mip levels, only matters once a texture has more than one
+    pub mipmap_mode: MipmapMode,
+    // For all three coordinates, decides what's sampled outside of 0..1
+    pub address_mode: SamplerAddressMode,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> SamplerConfig {
+        SamplerConfig {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            mipmap_mode: MipmapMode::Linear,
+            address_mode: SamplerAddressMode::Repeat,
+        }
+    }
+}
+
+// "nearest" or "linear"
+pub fn filter_from_arg(arg: &str) -> Option<Filter> {
+    match arg {
+        "nearest" => Some(Filter::Nearest),
+        "linear" => Some(Filter::Linear),
+        _ => None,
+    }
+}
+
+// "repeat", "mirror", "clamp" or "border", which is opaque black
+pub fn address_mode_from_arg(arg: &str) -> Option<SamplerAddressMode> {
+    match arg {
+        "repeat" => Some(SamplerAddressMode::Repeat),
+        "mirror" => Some(SamplerAddressMode::MirroredRepeat),
+        "clamp" => Some(SamplerAddressMode::ClampToEdge),
+        "border" => Some(SamplerAddressMode::ClampToBorder(BorderColor::FloatOpaqueBlack)),
+        _ => None,
+    }
+}
+
+pub fn create_sampler(device: &Arc<Device>, config: &SamplerConfig) -> Result<Arc<Sampler>, SamplerCreationError> {
+    Sampler::new(
+        device.clone(),
+        config.mag_filter,
+        config.min_filter,
+        config.mipmap_mode,
+        config.address_mode,
+        config.address_mode,
+        config.address_mode,
+        0.0,
+        1.0,
+        0.0,
+        MAX_LOD,
+    )
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    Decode(image::ImageError),
+    ImageCreation(ImageCreationError),
+    Copy(CopyBufferImageError),
+    Build(BuildError),
+    Execute(CommandBufferExecError),
+    Upload(FlushError),
+    SamplerCreation(SamplerCreationError),
+    Ktx2(Ktx2Error),
+    // The device can't sample the KTX2 file's format and there's no PNG next to it
+    UnsupportedFormat(String),
+    // Even the smallest level of a KTX2 file is bigger than the max size, compressed data can't be
+    // scaled down
+    TooLarge { width: u32, height: u32, max_size: u32 },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Decode(e) => write!(f, "failed to decode the image: {}", e),
+            TextureError::ImageCreation(e) => write!(f, "failed to create the texture image: {}", e),
+            TextureError::Copy(e) => write!(f, "failed to record the upload: {}", e),
+            TextureError::Build(e) => write!(f, "failed to build the upload command buffer: {}", e),
+            TextureError::Execute(e) => write!(f, "failed to submit the upload: {}", e),
+            TextureError::Upload(e) => write!(f, "failed to upload the texture: {}", e),
+            TextureError::SamplerCreation(e) => write!(f, "failed to create the sampler: {}", e),
+            TextureError::Ktx2(e) => write!(f, "failed to load the KTX2 file: {}", e),
+            TextureError::UnsupportedFormat(format) => write!(f, "the device can't sample {} textures", format),
+            TextureError::TooLarge { width, height, max_size } => write!(
+                f,
+                "{}x{} is bigger than the max texture size of {} and can't be scaled down",
+                width,
+                height,
+                max_size,
+            ),
+        }
+    }
+}
+
+impl Error for TextureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TextureError::Decode(e) => Some(e),
+            TextureError::ImageCreation(e) => Some(e),
+            TextureError::Copy(e) => Some(e),
+            TextureError::Build(e) => Some(e),
+            TextureError::Execute(e) => Some(e),
+            TextureError::Upload(e) => Some(e),
+            TextureError::SamplerCreation(e) => Some(e),
+            TextureError::Ktx2(e) => Some(e),
+            TextureError::UnsupportedFormat(_) | TextureError::TooLarge { .. } => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for TextureError {
+    fn from(e: image::ImageError) -> TextureError {
+        TextureError::Decode(e)
+    }
+}
+
+impl From<ImageCreationError> for TextureError {
+    fn from(e: ImageCreationError) -> TextureError {
+        TextureError::ImageCreation(e)
+    }
+}
+
+impl From<CopyBufferImageError> for TextureError {
+    fn from(e: CopyBufferImageError) -> TextureError {
+        TextureError::Copy(e)
+    }
+}
+
+impl From<BuildError> for TextureError {
+    fn from(e: BuildError) -> TextureError {
+        TextureError::Build(e)
+    }
+}
+
+impl From<CommandBufferExecError> for TextureError {
+    fn from(e: CommandBufferExecError) -> TextureError {
+        TextureError::Execute(e)
+    }
+}
+
+impl From<FlushError> for TextureError {
+    fn from(e: FlushError) -> TextureError {
+        TextureError::Upload(e)
+    }
+}
+
+impl From<SamplerCreationError> for TextureError {
+    fn from(e: SamplerCreationError) -> TextureError {
+        TextureError::SamplerCreation(e)
+    }
+}
+
+impl From<Ktx2Error> for TextureError {
+    fn from(e: Ktx2Error) -> TextureError {
+        TextureError::Ktx2(e)
+    }
+}
+
+// Color textures are stored as sRGB and should be decoded when sampled, anything that's data
+// (normal maps, masks) wants srgb = false so the values come through untouched. Without mipmaps
+// anything with fine detail shimmers once it gets smaller than the image, e.g. a checkerboard seen
+// at an angle. KTX2 files bring their own format and mipmaps, see load_ktx2_texture. Images with a
+// side longer than max_size (or what the device can do, whichever is less) get scaled down first
+pub fn load_texture(path: &Path, queue: &Arc<Queue>, srgb: bool, mipmaps: bool, max_size: Option<u32>,
+                    sampler_config: &SamplerConfig) -> Result<Texture, TextureError> {
+    let device_max = queue.device().physical_device().limits().max_image_dimension_2d();
+    let max_size = max_size.map_or(device_max, |max_size| max_size.min(device_max));
+
+    if path.extension().is_some_and(|extension| extension == "ktx2") {
+        return load_ktx2_texture(path, queue, srgb, mipmaps, max_size, sampler_config);
+    }
+
+    // Everything gets expanded to RGBA8. Vulkan doesn't care about power of two sizes and RGBA8
+    // rows are always 4 byte aligned, so odd sizes need no special handling
+    let mut pixels = image::open(path)?.to_rgba();
+    let (width, height) = pixels.dimensions();
+    let (new_width, new_height) = downscaled_size(width, height, max_size);
+    if (new_width, new_height) != (width, height) {
+        println!(
+            "Scaling {} down from {}x{} to {}x{} to fit the max texture size of {}",
+            path.display(),
+            width,
+            height,
+            new_width,
+            new_height,
+            max_size,
+        );
+        pixels = image::imageops::resize(&pixels, new_width, new_height, FilterType::Triangle);
+    }
+    texture_from_pixels(pixels, queue, srgb, mipmaps, sampler_config)
+}
+
+// The longer side shrinks to max_size and the other one along with it, neither goes below 1
+fn downscaled_size(width: u32, height: u32, max_size: u32) -> (u32, u32) {
+    let longest = width.max(height);
+    if longest <= max_size {
+        return (width, height);
+    }
+
+    let scale = |side: u32| ((side as u64 * max_size as u64 / longest as u64) as u32).max(1);
+    (scale(width), scale(height))
+}
+
+// For images that don't come from a file, e.g. the font atlas
+pub fn texture_from_pixels(pixels: RgbaImage, queue: &Arc<Queue>, srgb: bool, mipmaps: bool,
+                           sampler_config: &SamplerConfig) -> Result<Texture, TextureError> {
+    let (width, height) = pixels.dimensions();
+
+    let format = if srgb {
+        Format::R8G8B8A8Srgb
+    } else {
+        Format::R8G8B8A8Unorm
+    };
+
+    let levels = if mipmaps {
+        mip_chain(pixels)
+    } else {
+        vec!(pixels)
+    };
+    let levels = levels.into_iter()
+        .map(|pixels| pixels.into_raw())
+        .collect();
+
+    upload_levels(queue, format, width, height, levels, sampler_config)
+}
+
+// The levels get uploaded as they are, compressed ones without ever being decompressed. The file
+// decides about sRGB, so srgb only matters for the PNG fallback. Files without mipmaps stay
+// without, they can't be generated from compressed data on the CPU. Too large ones start at the
+// first level that fits in max_size instead
+fn load_ktx2_texture(path: &Path, queue: &Arc<Queue>, srgb: bool, mipmaps: bool, max_size: u32,
+                     sampler_config: &SamplerConfig) -> Result<Texture, TextureError> {
+    let Ktx2 { vk_format, mut width, mut height, mut levels } = load_ktx2(path)?;
+
+    let format = match usable_ktx2_format(queue.device(), vk_format) {
+        Ok(format) => format,
+        Err(format_name) => {
+            let fallback = path.with_extension("png");
+            if !fallback.exists() {
+                return Err(TextureError::UnsupportedFormat(format_name));
+            }
+            println!("The device can't sample {} textures, loading {} instead", format_name, fallback.display());
+            return load_texture(&fallback, queue, srgb, mipmaps, Some(max_size), sampler_config);
+        }
+    };
+
+    let skipped = first_fitting_level(width, height, levels.len(), max_size)
+        .ok_or(TextureError::TooLarge { width, height, max_size })?;
+    if skipped > 0 {
+        println!(
+            "Skipping the first {} levels of {} to fit the max texture size of {}",
+            skipped,
+            path.display(),
+            max_size,
+        );
+        levels.drain(..skipped);
+        width = (width >> skipped).max(1);
+        height = (height >> skipped).max(1);
+    }
+
+    if !mipmaps {
+        levels.truncate(1);
+    }
+
+    // vulkano checks the length too but panics instead of returning an error
+    for (level, data) in levels.iter().enumerate() {
+        let expected = level_len(format, (width >> level).max(1), (height >> level).max(1));
+        if data.len() != expected {
+            return Err(Ktx2Error::Unsupported("a level with the wrong size for its format").into());
+        }
+    }
+
+    upload_levels(queue, format, width, height, levels, sampler_config)
+}
+
+// The first of level_count levels, each half the size of the one before, with no side longer than
+// max_size. None if not even the last one fits
+fn first_fitting_level(width: u32, height: u32, level_count: usize, max_size: u32) -> Option<usize> {
+    (0..level_count).find(|&level| (width >> level).max(height >> level) <= max_size)
+}
+
+// The format, or its name for the error if the device can't sample it. Compressed formats need
+// their device feature, see DeviceConfig::compressed_textures
+fn usable_ktx2_format(device: &Arc<Device>, vk_format: u32) -> Result<Format, String> {
+    let (format, compression) = KTX2_FORMATS.iter()
+        .find(|(number, _, _)| *number == vk_format)
+        .map(|&(_, format, compression)| (format, compression))
+        .ok_or_else(|| format!("VkFormat {}", vk_format))?;
+
+    let features = device.enabled_features();
+    let enabled = match compression {
+        Compression::None => true,
+        Compression::Bc => features.texture_compression_bc,
+        Compression::Etc2 => features.texture_compression_etc2,
+    };
+    let sampled = optimal_tiling_features(device.physical_device(), format)
+        .is_some_and(|features| features.sampled_image);
+
+    if enabled && sampled {
+        Ok(format)
+    } else {
+        Err(format!("{:?}", format))
+    }
+}
+
+// Bytes of one level of a format, compressed ones are stored in whole blocks
+fn level_len(format: Format, width: u32, height: u32) -> usize {
+    let (block_width, block_height) = format.block_dimensions();
+    let blocks_x = width.div_ceil(block_width);
+    let blocks_y = height.div_ceil(block_height);
+    format.size().unwrap_or(0) * blocks_x as usize * blocks_y as usize
+}
+
+// All levels of the image, the full size one first, each in the layout of the format
+fn upload_levels(queue: &Arc<Queue>, format: Format, width: u32, height: u32, levels: Vec<Vec<u8>>,
+                 sampler_config: &SamplerConfig) -> Result<Texture, TextureError> {
+    let device = queue.device();
+    let (image, init) = ImmutableImage::uninitialized(
+        device.clone(),
+        Dimensions::Dim2d { width, height },
+        format,
+        MipmapsCount::Specific(levels.len() as u32),
+        ImageUsage { transfer_destination: true, sampled: true, ..ImageUsage::none() },
+        ImageLayout::ShaderReadOnlyOptimal,
+        device.active_queue_families(),
+    )?;
+    // Every level gets its own copy, all in the same command buffer
+    let init = Arc::new(init);
+
+    let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family())
+        .map_err(ImageCreationError::from)?;
+    for (level, data) in levels.into_iter().enumerate() {
+        let level_width = (width >> level).max(1);
+        let level_height = (height >> level).max(1);
+        let staging = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::transfer_source(),
+            false,
+            data.into_iter(),
+        )
+            .map_err(ImageCreationError::from)?;
+
+        builder = builder
+            .copy_buffer_to_image_dimensions(
+                staging,
+                init.clone(),
+                [0, 0, 0],
+                [level_width, level_height, 1],
+                0,
+                1,
+                level as u32,
+            )?;
+    }
+
+    // Wait for the upload here so callers get a ready texture
+    builder.build()?
+        .execute(queue.clone())?
+        .then_signal_fence_and_flush()?
+        .wait(None)?;
+
+    let sampler = create_sampler(device, sampler_config)?;
+
+    Ok(Texture { image, sampler })
+}
+
+// Each level half the size of the one before, rounded down, until both sides are 1. Generated on
+// the CPU: blitting between the levels on the GPU would need the image as both the source and the
+// destination of one command, which vulkano's command buffers refuse. The filtering happens on
+// the sRGB values as they are, slightly darker than a proper linear downsample
+fn mip_chain(base: RgbaImage) -> Vec<RgbaImage> {
+    let mut levels = vec!(base);
+    loop {
+        let (width, height) = levels.last()
+            .unwrap()
+            .dimensions();
+        if width == 1 && height == 1 {
+            return levels;
+        }
+
+        // Always from the full image so the errors of the smaller levels don't add up
+        let level = image::imageops::resize(&levels[0], (width / 2).max(1), (height / 2).max(1), FilterType::Triangle);
+        levels.push(level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downscaling_keeps_the_aspect_ratio() {
+        assert_eq!(downscaled_size(4096, 2048, 1024), (1024, 512));
+        assert_eq!(downscaled_size(1000, 3000, 1500), (500, 1500));
+        // Thin images keep at least a pixel
+        assert_eq!(downscaled_size(8192, 2, 1024), (1024, 1));
+    }
+
+    #[test]
+    fn small_enough_images_stay() {
+        assert_eq!(downscaled_size(1024, 1024, 1024), (1024, 1024));
+        assert_eq!(downscaled_size(3, 5, 16384), (3, 5));
+    }
+
+    #[test]
+    fn ktx2_starts_at_the_first_level_that_fits() {
+        assert_eq!(first_fitting_level(4096, 1024, 13, 1024), Some(2));
+        assert_eq!(first_fitting_level(512, 512, 10, 1024), Some(0));
+        // Without mipmaps there's nothing smaller to go to
+        assert_eq!(first_fitting_level(4096, 4096, 1, 1024), None);
+    }
+
+    fn level_sizes(width: u32, height: u32) -> Vec<(u32, u32)> {
+        mip_chain(RgbaImage::new(width, height)).iter()
+            .map(|level| level.dimensions())
+            .collect()
+    }
+
+    #[test]
+    fn mip_chain_halves_down_to_one() {
+        assert_eq!(level_sizes(8, 8), vec!((8, 8), (4, 4), (2, 2), (1, 1)));
+        assert_eq!(level_sizes(1, 1), vec!((1, 1)));
+    }
+
+    #[test]
+    fn mip_chain_keeps_the_short_side_at_one() {
+        assert_eq!(level_sizes(16, 4), vec!((16, 4), (8, 2), (4, 1), (2, 1), (1, 1)));
+        assert_eq!(level_sizes(1, 4), vec!((1, 4), (1, 2), (1, 1)));
+    }
+
+    #[test]
+    fn mip_chain_rounds_odd_sizes_down() {
+        // Same as Vulkan's floor(size / 2^level), which upload_levels copies with
+        let sizes = level_sizes(13, 7);
+        assert_eq!(sizes, vec!((13, 7), (6, 3), (3, 1), (1, 1)));
+        for (level, &(width, height)) in sizes.iter().enumerate() {
+            assert_eq!((width, height), ((13 >> level).max(1), (7 >> level).max(1)));
+        }
+    }
+}
@@ -1,15 +1,37 @@
 #version 460
 
-layout(location = 0) in vec2 position;
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 uv;
+layout(location = 3) in vec3 color;
+// With AFFINE the UVs get interpolated straight across the screen instead of in 3D, for
+// Scene::affine_uvs. The fragment shader has to say the same
+#ifdef AFFINE
+layout(location = 0) noperspective out vec2 v_uv;
+#else
+layout(location = 0) out vec2 v_uv;
+#endif
+layout(location = 1) out vec3 v_color;
+layout(location = 2) out vec3 v_normal;
 layout(set = 0, binding = 0) uniform Data {
-    mat4 rotation;
+    mat4 view;
+    mat4 proj;
+    // std140 aligns a vec3 to 16 bytes like a vec4, so each one is followed by a float to fill
+    // the gap instead of padding. light_direction points towards the light, in world space
+    vec3 light_direction;
+    float time;
+    vec3 light_color;
+    // How bright faces turned away from the light still are
+    float ambient;
 } uni;
+layout(push_constant) uniform PushConstants {
+    mat4 rotation;
+} push;
 
 void main() {
-    mat4 size;
-    size[0] = vec4(0.5, 0.0, 0.0, 0.0);
-    size[1] = vec4(0.0, 0.5, 0.0, 0.0);
-    size[2] = vec4(0.0, 0.0, 1.0, 0.0);
-    size[3] = vec4(0.0, 0.0, 0.0, 1.0);
-    gl_Position = size * vec4(position, 0.0, 1.0) * uni.rotation;
-}
\ No newline at end of file
+    v_uv = uv;
+    v_color = color;
+    // The model matrix is just a rotation, no scale that would need the inverse transpose
+    v_normal = mat3(push.rotation) * normal;
+    gl_Position = uni.proj * uni.view * push.rotation * vec4(position, 1.0);
+}
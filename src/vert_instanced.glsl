@@ -0,0 +1,36 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 uv;
+layout(location = 3) in vec3 color;
+// Per instance
+layout(location = 4) in vec2 offset;
+layout(location = 5) in float scale;
+layout(location = 0) out vec2 v_uv;
+layout(location = 1) out vec3 v_color;
+layout(location = 2) out vec3 v_normal;
+layout(set = 0, binding = 0) uniform Data {
+    mat4 view;
+    mat4 proj;
+    // std140 aligns a vec3 to 16 bytes like a vec4, so each one is followed by a float to fill
+    // the gap instead of padding. light_direction points towards the light, in world space
+    vec3 light_direction;
+    float time;
+    vec3 light_color;
+    // How bright faces turned away from the light still are
+    float ambient;
+} uni;
+layout(push_constant) uniform PushConstants {
+    mat4 rotation;
+} push;
+
+void main() {
+    v_uv = uv;
+    v_color = color;
+    // The rotation has no scale in it, so it can turn normals as well
+    v_normal = mat3(push.rotation) * normal;
+    // Offset after rotating so every copy spins in place
+    vec4 world_position = push.rotation * vec4(position * scale, 1.0) + vec4(offset, 0.0, 0.0);
+    gl_Position = uni.proj * uni.view * world_position;
+}
@@ -0,0 +1,28 @@
+#version 460
+
+// ShapeVertex, already in normalized device coordinates. Everything but the position is the same
+// for the whole shape
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 local;
+layout(location = 2) in vec2 half_size;
+layout(location = 3) in float corner_radius;
+layout(location = 4) in float border_width;
+layout(location = 5) in vec4 fill;
+layout(location = 6) in vec4 border;
+
+layout(location = 0) out vec2 v_local;
+layout(location = 1) flat out vec2 v_half_size;
+layout(location = 2) flat out float v_corner_radius;
+layout(location = 3) flat out float v_border_width;
+layout(location = 4) flat out vec4 v_fill;
+layout(location = 5) flat out vec4 v_border;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+    v_local = local;
+    v_half_size = half_size;
+    v_corner_radius = corner_radius;
+    v_border_width = border_width;
+    v_fill = fill;
+    v_border = border;
+}
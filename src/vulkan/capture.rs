@@ -0,0 +1,31 @@
+use renderdoc::{RenderDoc, V110};
+
+// RenderDoc has to be loaded before the vulkan instance is created, otherwise its layer
+// never gets the chance to hook into anything
+pub struct FrameCapture {
+    renderdoc: Option<RenderDoc<V110>>,
+}
+
+impl FrameCapture {
+    pub fn detect() -> FrameCapture {
+        let renderdoc = match RenderDoc::new() {
+            Ok(renderdoc) => {
+                println!("RenderDoc detected, press F9 to capture a frame");
+                Some(renderdoc)
+            }
+            Err(e) => {
+                println!("RenderDoc not available, frame capture disabled: {}", e);
+                None
+            }
+        };
+
+        FrameCapture { renderdoc }
+    }
+
+    pub fn trigger(&mut self) {
+        if let Some(renderdoc) = self.renderdoc.as_mut() {
+            renderdoc.trigger_capture();
+            println!("Triggered RenderDoc capture of the next frame");
+        }
+    }
+}
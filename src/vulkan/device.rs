@@ -0,0 +1,234 @@
+use vulkano::instance::{Instance, PhysicalDevice, PhysicalDeviceType, QueueFamily};
+use vulkano::swapchain::Surface;
+use winit::window::Window;
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum DeviceSelector {
+    First,
+    PreferDiscrete,
+    PreferIntegrated,
+    ByName(String),
+}
+
+impl DeviceSelector {
+    // "first", "discrete" and "integrated" pick a strategy, anything else is matched against
+    // the device names
+    pub fn from_arg(arg: &str) -> DeviceSelector {
+        match arg {
+            "first" => DeviceSelector::First,
+            "discrete" => DeviceSelector::PreferDiscrete,
+            "integrated" => DeviceSelector::PreferIntegrated,
+            name => DeviceSelector::ByName(name.to_string()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DeviceSelectionError {
+    EmptyEnumeration,
+    NoSuitableDevice,
+    NoDeviceNamed(String, Vec<String>),
+}
+
+impl fmt::Display for DeviceSelectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeviceSelectionError::EmptyEnumeration =>
+                write!(f, "no vulkan physical devices found, is a driver installed?"),
+            DeviceSelectionError::NoSuitableDevice =>
+                write!(f, "no physical device has a queue family supporting graphics and one that can present"),
+            DeviceSelectionError::NoDeviceNamed(name, available) =>
+                write!(f, "no suitable physical device matches \"{}\" (available: {})", name, available.join(", ")),
+        }
+    }
+}
+
+impl Error for DeviceSelectionError {}
+
+// What a queue family can do, so callers don't have to go back to the physical device to find out
+#[derive(Debug, Clone, Copy)]
+pub struct QueueCapabilities {
+    pub family_id: u32,
+    pub graphics: bool,
+    pub compute: bool,
+    // Graphics and compute families can always transfer, even when they don't say so
+    pub transfer: bool,
+    pub queue_count: usize,
+}
+
+impl QueueCapabilities {
+    pub fn of(family: QueueFamily) -> QueueCapabilities {
+        QueueCapabilities {
+            family_id: family.id(),
+            graphics: family.supports_graphics(),
+            compute: family.supports_compute(),
+            transfer: family.explicitly_supports_transfers() || family.supports_graphics() || family.supports_compute(),
+            queue_count: family.queues_count(),
+        }
+    }
+}
+
+impl fmt::Display for QueueCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut operations = vec!();
+        if self.graphics {
+            operations.push("graphics");
+        }
+        if self.compute {
+            operations.push("compute");
+        }
+        if self.transfer {
+            operations.push("transfer");
+        }
+
+        write!(f, "queue family {}: {} queue(s), {}", self.family_id, self.queue_count, operations.join(" + "))
+    }
+}
+
+// Without a surface (headless) any graphics family will do. With one, a family that can present as
+// well is preferred so everything runs on one queue. Otherwise it's any graphics family as long as
+// some other family can present, see find_present_queue_family
+pub fn find_queue_family<'a>(physical: PhysicalDevice<'a>, surface: Option<&Arc<Surface<Arc<Window>>>>) -> Option<QueueFamily<'a>> {
+    physical.queue_families()
+        .find(|&q| {
+            q.supports_graphics() && surface.map(|surface| surface.is_supported(q).unwrap_or(false)).unwrap_or(true)
+        })
+        .or_else(|| {
+            let graphics = physical.queue_families().find(|q| q.supports_graphics())?;
+            find_present_queue_family(physical, surface?, graphics).map(|_| graphics)
+        })
+}
+
+// The graphics family itself whenever it can present
+pub fn find_present_queue_family<'a>(physical: PhysicalDevice<'a>, surface: &Arc<Surface<Arc<Window>>>, graphics: QueueFamily<'a>)
+                                     -> Option<QueueFamily<'a>> {
+    if surface.is_supported(graphics).unwrap_or(false) {
+        return Some(graphics);
+    }
+
+    physical.queue_families()
+        .find(|&q| surface.is_supported(q).unwrap_or(false))
+}
+
+// Prefers a transfer-only family (the DMA engine on discrete cards), then any other family that can
+// do transfers. None means the graphics family is all there is
+pub fn find_transfer_queue_family<'a>(physical: PhysicalDevice<'a>, graphics: QueueFamily<'a>) -> Option<QueueFamily<'a>> {
+    let others = || physical.queue_families()
+        .filter(move |q| q.id() != graphics.id() && q.explicitly_supports_transfers());
+
+    others()
+        .find(|q| !q.supports_graphics() && !q.supports_compute())
+        .or_else(|| others().next())
+}
+
+pub fn select_physical_device<'a>(
+    instance: &'a Arc<Instance>,
+    surface: Option<&Arc<Surface<Arc<Window>>>>,
+    selector: &DeviceSelector,
+) -> Result<PhysicalDevice<'a>, DeviceSelectionError> {
+    // Happens on machines without a GPU (or driver), worth telling apart from "none of them is good enough"
+    if PhysicalDevice::enumerate(instance).next().is_none() {
+        return Err(DeviceSelectionError::EmptyEnumeration);
+    }
+
+    let candidates: Vec<PhysicalDevice> = PhysicalDevice::enumerate(instance)
+        .filter(|&physical| find_queue_family(physical, surface).is_some())
+        .collect();
+
+    let selected = match selector {
+        DeviceSelector::First => candidates.first().cloned(),
+        DeviceSelector::PreferDiscrete => candidates.iter()
+            .cloned()
+            .min_by_key(|physical| type_rank(physical.ty(), PhysicalDeviceType::DiscreteGpu)),
+        DeviceSelector::PreferIntegrated => candidates.iter()
+            .cloned()
+            .min_by_key(|physical| type_rank(physical.ty(), PhysicalDeviceType::IntegratedGpu)),
+        DeviceSelector::ByName(name) => {
+            let lowercase_name = name.to_lowercase();
+            let found = candidates.iter()
+                .cloned()
+                .find(|physical| physical.name().to_lowercase().contains(&lowercase_name));
+
+            if found.is_none() && !candidates.is_empty() {
+                let available = candidates.iter()
+                    .map(|physical| physical.name())
+                    .collect();
+                return Err(DeviceSelectionError::NoDeviceNamed(name.clone(), available));
+            }
+            found
+        }
+    };
+
+    selected.ok_or(DeviceSelectionError::NoSuitableDevice)
+}
+
+// Lower is better. The preferred type always wins, after that real GPUs beat virtual ones and
+// software rasterizers come last
+fn type_rank(ty: PhysicalDeviceType, preferred: PhysicalDeviceType) -> u32 {
+    if ty == preferred {
+        return 0;
+    }
+
+    match ty {
+        PhysicalDeviceType::DiscreteGpu | PhysicalDeviceType::IntegratedGpu => 1,
+        PhysicalDeviceType::VirtualGpu => 2,
+        PhysicalDeviceType::Cpu => 3,
+        PhysicalDeviceType::Other => 4,
+    }
+}
+
+// One line with everything a bug report needs about the device, e.g.
+// "NVIDIA GeForce GTX 1070 (type: DiscreteGpu, vulkan 1.2.133, driver 440.82.0, vendor 0x10de, device 0x1b81)"
+pub fn describe_device(physical: PhysicalDevice) -> String {
+    format!(
+        "{} (type: {:?}, vulkan {}, driver {}, vendor {:#06x}, device {:#06x})",
+        physical.name(),
+        physical.ty(),
+        physical.api_version(),
+        driver_version(physical.pci_vendor_id(), physical.driver_version()),
+        physical.pci_vendor_id(),
+        physical.pci_device_id(),
+    )
+}
+
+// The driver version is packed however the vendor likes. NVIDIA and Intel on Windows have their own
+// layouts, everyone else uses the same one as the API version
+fn driver_version(vendor_id: u32, version: u32) -> String {
+    const NVIDIA: u32 = 0x10de;
+    const INTEL: u32 = 0x8086;
+
+    match vendor_id {
+        NVIDIA => format!("{}.{}.{}", version >> 22, (version >> 14) & 0xff, (version >> 6) & 0xff),
+        INTEL if cfg!(target_os = "windows") => format!("{}.{}", version >> 14, version & 0x3fff),
+        _ => format!("{}.{}.{}", version >> 22, (version >> 12) & 0x3ff, version & 0xfff),
+    }
+}
+
+// For --list-devices and bug reports. Lists everything, not just the devices select_physical_device
+// would pick from
+pub fn list_devices(instance: &Arc<Instance>) {
+    for physical in PhysicalDevice::enumerate(instance) {
+        println!("{}: {}", physical.index(), describe_device(physical));
+
+        for family in physical.queue_families() {
+            println!("    {}", QueueCapabilities::of(family));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selector_from_arg() {
+        assert!(matches!(DeviceSelector::from_arg("first"), DeviceSelector::First));
+        assert!(matches!(DeviceSelector::from_arg("discrete"), DeviceSelector::PreferDiscrete));
+        assert!(matches!(DeviceSelector::from_arg("integrated"), DeviceSelector::PreferIntegrated));
+        assert!(matches!(DeviceSelector::from_arg("GeForce"), DeviceSelector::ByName(name) if name == "GeForce"));
+    }
+}
@@ -0,0 +1,146 @@
+use vulkano::device::DeviceCreationError;
+use vulkano::framebuffer::RenderPassCreationError;
+use vulkano::image::ImageCreationError;
+use vulkano::instance::{InstanceCreationError, InstanceExtensions, LoadingError};
+use vulkano::swapchain::{CapabilitiesError, SwapchainCreationError};
+use vulkano_win::CreationError as SurfaceCreationError;
+
+use std::error::Error;
+use std::fmt;
+
+use super::device::DeviceSelectionError;
+use super::pipeline::PipelineError;
+use super::shader::ShaderLoadError;
+
+#[derive(Debug)]
+pub enum InitError {
+    Loading(LoadingError),
+    MissingInstanceExtensions(InstanceExtensions),
+    InstanceCreation(InstanceCreationError),
+    SurfaceCreation(SurfaceCreationError),
+    DeviceSelection(DeviceSelectionError),
+    NoQueueFamily,
+    NoPresentSupport,
+    DeviceCreation(DeviceCreationError),
+    Capabilities(CapabilitiesError),
+    MissingSwapchainUsage(Vec<&'static str>),
+    NoSurfaceFormat,
+    NoCompositeAlpha,
+    SwapchainCreation(SwapchainCreationError),
+    RenderPassCreation(RenderPassCreationError),
+    ImageCreation(ImageCreationError),
+    ShaderLoad(ShaderLoadError),
+    Pipeline(PipelineError),
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InitError::Loading(e) =>
+                write!(f, "failed to load the vulkan library, is a vulkan driver installed? ({})", e),
+            InitError::MissingInstanceExtensions(missing) =>
+                write!(f, "missing required instance extensions: {:?}", missing),
+            InitError::InstanceCreation(e) => write!(f, "failed to create the vulkan instance: {}", e),
+            InitError::SurfaceCreation(e) => write!(f, "failed to create the window surface: {}", e),
+            InitError::DeviceSelection(e) => write!(f, "failed to select a physical device: {}", e),
+            InitError::NoQueueFamily => write!(f, "the selected device has no graphics queue family, or none of its families can present"),
+            InitError::NoPresentSupport => write!(f, "the present queue family can't present to the new surface"),
+            InitError::DeviceCreation(e) => write!(f, "failed to create the logical device: {}", e),
+            InitError::Capabilities(e) => write!(f, "failed to query the surface capabilities: {}", e),
+            InitError::MissingSwapchainUsage(missing) =>
+                write!(f, "swapchain images don't support the required usages: {}", missing.join(", ")),
+            InitError::NoSurfaceFormat => write!(f, "the surface doesn't support any formats"),
+            InitError::NoCompositeAlpha => write!(f, "the surface doesn't support any composite alpha mode"),
+            InitError::SwapchainCreation(e) => write!(f, "failed to create the swapchain: {}", e),
+            InitError::RenderPassCreation(e) => write!(f, "failed to create the render pass: {}", e),
+            InitError::ImageCreation(e) => write!(f, "failed to create the offscreen image: {}", e),
+            InitError::ShaderLoad(e) => write!(f, "failed to load a shader: {}", e),
+            InitError::Pipeline(e) => write!(f, "post pass: {}", e),
+        }
+    }
+}
+
+impl Error for InitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InitError::Loading(e) => Some(e),
+            InitError::InstanceCreation(e) => Some(e),
+            InitError::SurfaceCreation(e) => Some(e),
+            InitError::DeviceSelection(e) => Some(e),
+            InitError::DeviceCreation(e) => Some(e),
+            InitError::Capabilities(e) => Some(e),
+            InitError::SwapchainCreation(e) => Some(e),
+            InitError::RenderPassCreation(e) => Some(e),
+            InitError::ImageCreation(e) => Some(e),
+            InitError::ShaderLoad(e) => Some(e),
+            InitError::Pipeline(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<LoadingError> for InitError {
+    fn from(e: LoadingError) -> InitError {
+        InitError::Loading(e)
+    }
+}
+
+impl From<InstanceCreationError> for InitError {
+    fn from(e: InstanceCreationError) -> InitError {
+        InitError::InstanceCreation(e)
+    }
+}
+
+impl From<SurfaceCreationError> for InitError {
+    fn from(e: SurfaceCreationError) -> InitError {
+        InitError::SurfaceCreation(e)
+    }
+}
+
+impl From<DeviceSelectionError> for InitError {
+    fn from(e: DeviceSelectionError) -> InitError {
+        InitError::DeviceSelection(e)
+    }
+}
+
+impl From<DeviceCreationError> for InitError {
+    fn from(e: DeviceCreationError) -> InitError {
+        InitError::DeviceCreation(e)
+    }
+}
+
+impl From<CapabilitiesError> for InitError {
+    fn from(e: CapabilitiesError) -> InitError {
+        InitError::Capabilities(e)
+    }
+}
+
+impl From<SwapchainCreationError> for InitError {
+    fn from(e: SwapchainCreationError) -> InitError {
+        InitError::SwapchainCreation(e)
+    }
+}
+
+impl From<RenderPassCreationError> for InitError {
+    fn from(e: RenderPassCreationError) -> InitError {
+        InitError::RenderPassCreation(e)
+    }
+}
+
+impl From<ImageCreationError> for InitError {
+    fn from(e: ImageCreationError) -> InitError {
+        InitError::ImageCreation(e)
+    }
+}
+
+impl From<ShaderLoadError> for InitError {
+    fn from(e: ShaderLoadError) -> InitError {
+        InitError::ShaderLoad(e)
+    }
+}
+
+impl From<PipelineError> for InitError {
+    fn from(e: PipelineError) -> InitError {
+        InitError::Pipeline(e)
+    }
+}
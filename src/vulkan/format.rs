@@ -0,0 +1,49 @@
+use vulkano::VulkanObject;
+use vulkano::format::Format;
+use vulkano::instance::PhysicalDevice;
+
+use std::mem;
+
+use super::loader::instance_function;
+
+// vulkano 0.18 can't ask what a format can be used for, so this goes through
+// vkGetPhysicalDeviceFormatProperties by hand. Mirrors VkFormatProperties
+#[repr(C)]
+struct FormatProperties {
+    linear_tiling_features: u32,
+    optimal_tiling_features: u32,
+    buffer_features: u32,
+}
+
+type GetFormatProperties = extern "system" fn(usize, u32, *mut FormatProperties);
+
+const FORMAT_FEATURE_SAMPLED_IMAGE: u32 = 0x1;
+const FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT: u32 = 0x200;
+
+// The bits we care about, for images with optimal tiling (which is all vulkano creates)
+pub struct FormatFeatures {
+    pub sampled_image: bool,
+    pub depth_stencil_attachment: bool,
+}
+
+// None if the function couldn't be loaded, which shouldn't happen since it's core Vulkan 1.0
+pub fn optimal_tiling_features(physical: PhysicalDevice, format: Format) -> Option<FormatFeatures> {
+    let function = instance_function(physical.instance(), "vkGetPhysicalDeviceFormatProperties")?;
+    let get_format_properties = unsafe {
+        mem::transmute::<extern "system" fn(), GetFormatProperties>(function)
+    };
+
+    let mut properties = FormatProperties {
+        linear_tiling_features: 0,
+        optimal_tiling_features: 0,
+        buffer_features: 0,
+    };
+    // Format's discriminants are the VkFormat values
+    get_format_properties(physical.internal_object(), format as u32, &mut properties);
+
+    let features = properties.optimal_tiling_features;
+    Some(FormatFeatures {
+        sampled_image: features & FORMAT_FEATURE_SAMPLED_IMAGE != 0,
+        depth_stencil_attachment: features & FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT != 0,
+    })
+}
@@ -1,155 +1,977 @@
 use vulkano::command_buffer::{DynamicState};
-use vulkano::device::{Device, DeviceExtensions, Queue};
-use vulkano::format::Format;
-use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract};
-use vulkano::image::SwapchainImage;
-use vulkano::instance::Instance;
-use vulkano::instance::PhysicalDevice;
+use vulkano::device::{Device, DeviceExtensions, Features, Queue};
+use vulkano::format::{Format, FormatTy};
+use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, LoadOp, RenderPassAbstract, StoreOp};
+use vulkano::image::{AttachmentImage, ImageUsage, SwapchainImage};
+use vulkano::instance::{Instance, InstanceExtensions};
+use vulkano::instance::{PhysicalDevice, QueueFamily};
+use vulkano::instance::debug::DebugCallback;
 use vulkano::pipeline::viewport::Viewport;
-use vulkano::swapchain::{PresentMode, Surface, SurfaceTransform, Swapchain, ColorSpace, FullscreenExclusive};
+use vulkano::sync::SharingMode;
+use vulkano::swapchain::{Capabilities, PresentMode, Surface, SurfaceTransform, Swapchain, ColorSpace, FullscreenExclusive,
+                         SupportedPresentModes};
 
-use vulkano_win::VkSurfaceBuild;
+use vulkano_win::CreationError;
+use winit::dpi::LogicalSize;
 use winit::window::{WindowBuilder, Window};
-use winit::event_loop::{EventLoop};
+use winit::event_loop::{EventLoop, EventLoopWindowTarget};
 
 use std::sync::Arc;
 
-pub fn vulkan_init() -> (Arc<Device>, Arc<dyn RenderPassAbstract + Send + Sync>, Vec<Arc<SwapchainImage<Window>>>, EventLoop<()>, Arc<Surface<Window>>, Arc<Swapchain<Window>>, Arc<Queue>) {
-    let required_extensions = vulkano_win::required_extensions();
-    let instance = Instance::new(None, &required_extensions, None)
-        .unwrap();
-    let physical = PhysicalDevice::enumerate(&instance)
-        .next()
-        .unwrap();
-    println!("Using device: {} (type: {:?})", physical.name(), physical.ty());
+use super::device::{describe_device, DeviceSelector, QueueCapabilities, find_present_queue_family, find_queue_family,
+                    find_transfer_queue_family, select_physical_device};
+use super::error::InitError;
+use super::format::optimal_tiling_features;
+use super::render_pass::{is_input_attachment, with_attachment_ops, with_decal_subpass, AttachmentOps};
+use super::validation::{validation_layers, create_debug_callback};
+
+pub struct VulkanContext {
+    pub device: Arc<Device>,
+    // The first window, create_window makes more
+    pub window: WindowTarget,
+    pub queue: Arc<Queue>,
+    pub queue_capabilities: QueueCapabilities,
+    // A queue from a separate transfer family if the device has one and DeviceConfig::transfer_queue
+    // asked for it, otherwise just another handle to queue
+    pub transfer_queue: Arc<Queue>,
+    // Only separate from queue when the graphics family can't present to the first window
+    pub present_queue: Arc<Queue>,
+    // Only set with the validation feature, messages stop once this is dropped
+    pub debug_callback: Option<DebugCallback>,
+    // What SwapchainConfig::samples ended up as after checking the device limits
+    pub samples: u32,
+}
+
+// A window and everything needed to render into and present to it
+pub struct WindowTarget {
+    pub surface: Arc<Surface<Arc<Window>>>,
+    pub swapchain: Arc<Swapchain<Arc<Window>>>,
+    pub swapchain_images: Vec<Arc<SwapchainImage<Arc<Window>>>>,
+    // For the swapchain's format, which can differ between windows
+    pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+}
+
+#[derive(Clone)]
+pub struct DeviceConfig {
+    pub selector: DeviceSelector,
+    // Always enabled, creating the device fails if it doesn't have them. For shaders that need more
+    // than the built-in ones
+    pub required_features: Features,
+    // fill_mode_non_solid for wireframe mode and wide_lines for the axes, where the device has
+    // them. Both modes keep working without, just filled and 1 pixel wide
+    pub debug_features: bool,
+    // texture_compression_bc and texture_compression_etc2 where the device has them, for KTX2
+    // textures. Without, those get loaded from a PNG next to them instead
+    pub compressed_textures: bool,
+    // A queue from a family of its own for the uploads where the device has one. Without (or
+    // without such a family) VulkanContext::transfer_queue is just the graphics queue
+    pub transfer_queue: bool,
+}
+
+#[derive(Clone)]
+pub struct SwapchainConfig {
+    // Falls back to Fifo, the only mode every driver has to support. Mailbox tries Immediate
+    // first, neither of them waits for vblank
+    pub present_mode: PresentMode,
+    // MSAA sample count, clamped down to what the device supports. 1 turns it off
+    pub samples: u32,
+    // None takes the surface's minimum (usually 2). min + 1 gets triple buffering, which paces
+    // better with Fifo. Clamped to what the surface supports
+    pub image_count: Option<u32>,
+    // Lets the swapchain take over the display while the window is fullscreen. Needs
+    // VK_EXT_full_screen_exclusive, which only Windows drivers have, otherwise fullscreen stays borderless
+    pub exclusive_fullscreen: bool,
+    // Draws the first mesh into the depth buffer in a subpass of its own before the color pass,
+    // which then only shades the fragments that end up visible. Worth it with lots of overdraw
+    pub depth_prepass: bool,
+    // Cleared and stored by default, see AttachmentOps
+    pub color_ops: AttachmentOps,
+    // Adds a subpass for Scene's decals after the color pass, see with_decal_subpass. Its depth
+    // buffer has no stencil, which turns the stencil mask off
+    pub decals: bool,
+    // Width over height to keep, letterboxed or pillarboxed to fit the window. None fills the window
+    pub letterbox: Option<f32>,
+    // What the bars get cleared to, in sRGB like Scene::clear_color
+    pub letterbox_color: [f32; 4],
+}
+
+#[derive(Clone)]
+pub struct WindowConfig {
+    pub title: String,
+    // In logical pixels, so it comes out the same physical size on high DPI screens
+    pub width: u32,
+    pub height: u32,
+    pub resizable: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> WindowConfig {
+        WindowConfig {
+            title: "vulkan-sandbox".to_string(),
+            width: 800,
+            height: 600,
+            resizable: true,
+        }
+    }
+}
+
+impl Default for DeviceConfig {
+    fn default() -> DeviceConfig {
+        DeviceConfig {
+            selector: DeviceSelector::First,
+            required_features: Features::none(),
+            debug_features: true,
+            compressed_textures: false,
+            transfer_queue: true,
+        }
+    }
+}
+
+impl Default for SwapchainConfig {
+    fn default() -> SwapchainConfig {
+        SwapchainConfig {
+            present_mode: PresentMode::Fifo,
+            samples: 1,
+            image_count: None,
+            exclusive_fullscreen: false,
+            depth_prepass: false,
+            color_ops: AttachmentOps::default(),
+            decals: false,
+            letterbox: None,
+            letterbox_color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+// Renders into an offscreen image instead of a window, for screenshot tests and the like
+pub struct HeadlessContext {
+    pub device: Arc<Device>,
+    pub queue: Arc<Queue>,
+    pub queue_capabilities: QueueCapabilities,
+    pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    pub image: Arc<AttachmentImage>,
+    pub framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    pub dimensions: [u32; 2],
+    pub format: Format,
+    // Only held so validation messages keep coming for as long as the context is alive
+    _debug_callback: Option<DebugCallback>,
+}
+
+// The depth buffer has a stencil part for Scene::stencil_mask. Every implementation supports one of
+// the first two. The decals need one without, D16 has to be supported so it's always the last resort
+const DEPTH_FORMATS: [Format; 4] = [
+    Format::D24Unorm_S8Uint,
+    Format::D32Sfloat_S8Uint,
+    Format::D32Sfloat,
+    Format::D16Unorm,
+];
 
+// RGBA so the readback can be saved as is, sRGB so it looks the same as it would in the window
+const HEADLESS_FORMAT: Format = Format::R8G8B8A8Srgb;
+
+type SwapchainWithImages = (Arc<Swapchain<Arc<Window>>>, Vec<Arc<SwapchainImage<Arc<Window>>>>);
+// The device, its graphics queue, its transfer queue and its present queue
+type DeviceWithQueues = (Arc<Device>, Arc<Queue>, Arc<Queue>, Arc<Queue>);
+
+// The event loop is handed back separately, run needs it and create_window makes more windows with it
+pub fn vulkan_init(device_config: &DeviceConfig, window_config: &WindowConfig, swapchain_config: &SwapchainConfig)
+                   -> Result<(VulkanContext, EventLoop<()>), InitError> {
     let event_loop = EventLoop::new();
-    let surface = WindowBuilder::new()
-        .build_vk_surface(&event_loop, instance.clone())
-        .unwrap();
+    let context = vulkan_init_with_event_loop(&event_loop, device_config, window_config, swapchain_config)?;
+    Ok((context, event_loop))
+}
 
-    let (device, queue) = create_device_and_queue(physical.clone(), &surface);
+// For apps that already have an event loop, there can only be one per process on some platforms.
+// The first window gets built on it, like everything passed to create_window later
+pub fn vulkan_init_with_event_loop<T>(event_loop: &EventLoopWindowTarget<T>, device_config: &DeviceConfig,
+                                      window_config: &WindowConfig, swapchain_config: &SwapchainConfig)
+                                      -> Result<VulkanContext, InitError> {
+    let mut required_extensions = vulkano_win::required_extensions();
+    if swapchain_config.exclusive_fullscreen {
+        // VK_EXT_full_screen_exclusive needs these two on the instance
+        let supported = InstanceExtensions::supported_by_core()
+            .unwrap_or_else(|_| InstanceExtensions::none());
+        required_extensions.khr_get_physical_device_properties2 = supported.khr_get_physical_device_properties2;
+        required_extensions.khr_get_surface_capabilities2 = supported.khr_get_surface_capabilities2;
+    }
 
-    // i3wm reports min and max image extents that are identical. This is a sort of workaround for me
-    // Use surface.window().inner_size().into() if it doesn't panic for you
-    let dimensions: [u32; 2] = surface.capabilities(device.physical_device())
-        .unwrap()
-        .min_image_extent;
+    let (instance, debug_callback) = create_instance(required_extensions)?;
+
+    // The surface has to exist before picking a device since we only want devices that can present to it
+    let surface = build_window(event_loop, &instance, window_config)?;
+
+    let physical = select_physical_device(&instance, Some(&surface), &device_config.selector)?;
+    println!("Using device: {}", describe_device(physical));
+
+    let (device, queue, transfer_queue, present_queue) = create_device_and_queues(
+        physical,
+        Some(&surface),
+        device_config,
+        swapchain_config.exclusive_fullscreen,
+    )?;
+
+    let samples = supported_samples(physical, swapchain_config.samples);
+    let window = create_window_target(&queue, &present_queue, surface, samples, swapchain_config)?;
+    Ok(VulkanContext {
+        device,
+        window,
+        queue_capabilities: QueueCapabilities::of(queue.family()),
+        queue,
+        transfer_queue,
+        present_queue,
+        debug_callback,
+        samples,
+    })
+}
+
+// Another window on the same device and queue. It gets its own swapchain, and its own render pass
+// since its surface might want a different format. event_loop is the one the context was created
+// with, or the target a running loop passes to its handler
+pub fn create_window<T>(context: &VulkanContext, event_loop: &EventLoopWindowTarget<T>, window_config: &WindowConfig,
+                        swapchain_config: &SwapchainConfig) -> Result<WindowTarget, InitError> {
+    let surface = build_window(event_loop, context.device.instance(), window_config)?;
+
+    // The device was picked for the first window. With more than one GPU the new one might end up
+    // on a monitor the device can't reach
+    if !surface.is_supported(context.present_queue.family())? {
+        return Err(InitError::NoPresentSupport);
+    }
+
+    create_window_target(&context.queue, &context.present_queue, surface, context.samples, swapchain_config)
+}
+
+// The surface only gets a shared handle to the window so a lost surface can be replaced by a new
+// one for the same window, see recreate_surface
+fn build_window<T>(event_loop: &EventLoopWindowTarget<T>, instance: &Arc<Instance>, config: &WindowConfig)
+                -> Result<Arc<Surface<Arc<Window>>>, InitError> {
+    let window = WindowBuilder::new()
+        .with_title(&config.title)
+        .with_inner_size(LogicalSize::new(config.width, config.height))
+        .with_resizable(config.resizable)
+        .build(event_loop)
+        .map_err(CreationError::from)?;
+    let surface = vulkano_win::create_vk_surface(Arc::new(window), instance.clone())
+        .map_err(CreationError::from)?;
+    Ok(surface)
+}
+
+// After the surface got lost, e.g. when the display server restarted or the GPU got reset. Every
+// swapchain on the old surface is useless, the new surface needs one made with create_swapchain
+pub fn recreate_surface(old_surface: &Surface<Arc<Window>>, present_queue: &Arc<Queue>)
+                        -> Result<Arc<Surface<Arc<Window>>>, InitError> {
+    let surface = vulkano_win::create_vk_surface(old_surface.window().clone(), old_surface.instance().clone())
+        .map_err(CreationError::from)?;
+    if !surface.is_supported(present_queue.family())? {
+        return Err(InitError::NoPresentSupport);
+    }
+    Ok(surface)
+}
+
+fn create_window_target(queue: &Arc<Queue>, present_queue: &Arc<Queue>, surface: Arc<Surface<Arc<Window>>>, samples: u32,
+                        config: &SwapchainConfig) -> Result<WindowTarget, InitError> {
+    let device = queue.device();
+    let caps = surface.capabilities(device.physical_device())?;
+    let dimensions = current_dimensions(&surface, &caps);
 
-    let (swapchain, images) = create_swapchain(
-        &queue,
-        &surface,
-        &device,
+    let (swapchain, swapchain_images) = create_swapchain(queue, present_queue, &surface, dimensions, config)?;
+    let render_pass = create_render_pass(device, swapchain.format(), samples, config.depth_prepass, config.color_ops,
+                                         config.decals)?;
+
+    Ok(WindowTarget { surface, swapchain, swapchain_images, render_pass })
+}
+
+// When the surface reports a fixed extent the swapchain has to match it exactly. Otherwise (Wayland,
+// and i3wm which reports identical min and max extents) the window size is what we want, clamped to
+// what the surface accepts so Swapchain::new doesn't reject it
+pub fn current_dimensions(surface: &Surface<Arc<Window>>, caps: &Capabilities) -> [u32; 2] {
+    surface_dimensions(caps, surface.window().inner_size().into())
+}
+
+// Same as current_dimensions for a window size we already know about but the window might not
+// report yet
+pub fn surface_dimensions(caps: &Capabilities, window_size: [u32; 2]) -> [u32; 2] {
+    if let Some(extent) = caps.current_extent {
+        return extent;
+    }
+
+    [
+        window_size[0].max(caps.min_image_extent[0]).min(caps.max_image_extent[0]),
+        window_size[1].max(caps.min_image_extent[1]).min(caps.max_image_extent[1]),
+    ]
+}
+
+// Same device selection and render pass as vulkan_init, but no surface, swapchain or extensions
+pub fn vulkan_init_headless(device_config: &DeviceConfig, width: u32, height: u32) -> Result<HeadlessContext, InitError> {
+    let (instance, debug_callback) = create_instance(InstanceExtensions::none())?;
+
+    let physical = select_physical_device(&instance, None, &device_config.selector)?;
+    println!("Using device: {}", describe_device(physical));
+
+    let (device, queue, _, _) = create_device_and_queues(physical, None, device_config, false)?;
+
+    let dimensions = [width, height];
+    let image = AttachmentImage::with_usage(
+        device.clone(),
         dimensions,
-    );
+        HEADLESS_FORMAT,
+        ImageUsage {
+            color_attachment: true,
+            transfer_source: true,
+            ..ImageUsage::none()
+        },
+    )?;
 
-    let render_pass = create_render_pass(&device, swapchain.format());
-    (device, render_pass, images, event_loop, surface, swapchain, queue)
+    // Single sampled so the readback can copy straight out of the render target
+    let render_pass = create_render_pass(&device, HEADLESS_FORMAT, 1, false, AttachmentOps::default(), false)?;
+    let depth_image = AttachmentImage::transient(device.clone(), dimensions, depth_attachment_format(&render_pass))?;
+    let framebuffer = Arc::new(
+        Framebuffer::start(render_pass.clone())
+            .add(image.clone())
+            .unwrap()
+            .add(depth_image)
+            .unwrap()
+            .build()
+            .unwrap()
+    ) as Arc<dyn FramebufferAbstract + Send + Sync>;
+
+    Ok(HeadlessContext {
+        device,
+        queue_capabilities: QueueCapabilities::of(queue.family()),
+        queue,
+        render_pass,
+        image,
+        framebuffer,
+        dimensions,
+        format: HEADLESS_FORMAT,
+        _debug_callback: debug_callback,
+    })
+}
+
+pub fn full_viewport(dimensions: [u32; 2]) -> Viewport {
+    Viewport {
+        origin: [0.0, 0.0],
+        dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+        depth_range: 0.0..1.0,
+    }
 }
 
+// With a scene_image (a post pass) every framebuffer renders into that instead of its swapchain image.
+// There's still one per swapchain image so they can be indexed the same way. They all share the
+// depth image that comes back with them, for the decals to read
 pub fn window_size_dependent_setup(
-    images: &[Arc<SwapchainImage<Window>>],
+    device: &Arc<Device>,
+    images: &[Arc<SwapchainImage<Arc<Window>>>],
     render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
     dynamic_state: &mut DynamicState,
-) -> Vec<Arc<dyn FramebufferAbstract + Send + Sync>> {
+    aspect_ratio: Option<f32>,
+    samples: u32,
+    scene_image: Option<Arc<AttachmentImage>>,
+) -> (Vec<Arc<dyn FramebufferAbstract + Send + Sync>>, Arc<AttachmentImage>) {
     let dimensions = images[0].dimensions();
 
-    let viewport = Viewport {
-        origin: [0.0, 0.0],
-        dimensions: [dimensions[0] as f32, dimensions[1] as f32],
-        depth_range: 0.0..1.0,
+    let viewport = match aspect_ratio {
+        Some(aspect_ratio) => letterboxed_viewport(dimensions, aspect_ratio),
+        None => full_viewport(dimensions),
     };
 
     dynamic_state.viewports = Some(vec!(viewport));
 
-    images.iter()
+    // Only used within a frame, so like the multisampled image one is enough for all of them. A new
+    // one every time since it has to match the new size, the old one goes away with the old
+    // framebuffers once the frames that used them have finished (Renderer::recreate drops their
+    // fences first)
+    let depth_attachment = render_pass.num_attachments() - 1;
+    let depth_image = AttachmentImage::multisampled_with_usage(
+        device.clone(),
+        dimensions,
+        samples,
+        depth_attachment_format(&render_pass),
+        ImageUsage {
+            depth_stencil_attachment: true,
+            transient_attachment: true,
+            input_attachment: is_input_attachment(&render_pass, depth_attachment),
+            ..ImageUsage::none()
+        },
+    )
+        .unwrap();
+
+    // Not imported, the trait's dimensions() would shadow the swapchain image's one above
+    let target = |image: &Arc<SwapchainImage<Arc<Window>>>| match &scene_image {
+        Some(scene_image) => scene_image.clone() as Arc<dyn vulkano::image::ImageViewAccess + Send + Sync>,
+        None => image.clone() as Arc<dyn vulkano::image::ImageViewAccess + Send + Sync>,
+    };
+
+    if samples == 1 {
+        let framebuffers = images.iter()
+            .map(|image| {
+                Arc::new(
+                    Framebuffer::start(render_pass.clone())
+                        .add(target(image))
+                        .unwrap()
+                        .add(depth_image.clone())
+                        .unwrap()
+                        .build()
+                        .unwrap()
+                ) as Arc<dyn FramebufferAbstract + Send + Sync>
+            })
+            .collect::<Vec<_>>();
+        return (framebuffers, depth_image);
+    }
+
+    // Frames are chained one after the other so they can all share one multisampled image. It only
+    // has to stay around between them when it's loaded at the start of the next one
+    let format = images[0].swapchain().format();
+    let intermediary = if render_pass.attachment_desc(0).unwrap().load == LoadOp::Load {
+        AttachmentImage::multisampled(device.clone(), dimensions, samples, format)
+    } else {
+        AttachmentImage::transient_multisampled(device.clone(), dimensions, samples, format)
+    }
+        .unwrap();
+
+    let framebuffers = images.iter()
         .map(|image| {
             Arc::new(
                 Framebuffer::start(render_pass.clone())
-                    .add(image.clone())
+                    .add(intermediary.clone())
+                    .unwrap()
+                    .add(target(image))
+                    .unwrap()
+                    .add(depth_image.clone())
                     .unwrap()
                     .build()
                     .unwrap()
             ) as Arc<dyn FramebufferAbstract + Send + Sync>
         })
-        .collect::<Vec<_>>()
-}
-
-fn create_render_pass(device: &Arc<Device>, format: Format) -> Arc<dyn RenderPassAbstract + Send + Sync> {
-    Arc::new(
-        vulkano::single_pass_renderpass!(
-            device.clone(),
-            attachments: {
-                color: {
-                    load: Clear,
-                    store: Store,
-                    format: format,
-                    samples: 1,
+        .collect::<Vec<_>>();
+    (framebuffers, depth_image)
+}
+
+// Centers a viewport with the given aspect ratio inside the image. The bars around it are never
+// drawn to, so they just keep the render pass clear color
+fn letterboxed_viewport(dimensions: [u32; 2], aspect_ratio: f32) -> Viewport {
+    let width = dimensions[0] as f32;
+    let height = dimensions[1] as f32;
+
+    let viewport_dimensions = if width / height > aspect_ratio {
+        [height * aspect_ratio, height]
+    } else {
+        [width, width / aspect_ratio]
+    };
+
+    Viewport {
+        origin: [
+            (width - viewport_dimensions[0]) / 2.0,
+            (height - viewport_dimensions[1]) / 2.0
+        ],
+        dimensions: viewport_dimensions,
+        depth_range: 0.0..1.0,
+    }
+}
+
+// Turns on validation as well when the feature is enabled and the layer is around
+pub fn create_instance(required_extensions: InstanceExtensions) -> Result<(Arc<Instance>, Option<DebugCallback>), InitError> {
+    check_instance_extensions(&required_extensions)?;
+
+    let supported = InstanceExtensions::supported_by_core()?;
+    let layers = if cfg!(feature = "validation") {
+        validation_layers(&supported)
+    } else {
+        vec!()
+    };
+
+    let extensions = InstanceExtensions {
+        ext_debug_utils: !layers.is_empty(),
+        // Only needed for the memory budget in log_memory_heaps, which goes without it
+        khr_get_physical_device_properties2: required_extensions.khr_get_physical_device_properties2
+            || supported.khr_get_physical_device_properties2,
+        ..required_extensions
+    };
+    let instance = Instance::new(None, &extensions, layers.iter().cloned())?;
+
+    let debug_callback = if layers.is_empty() {
+        None
+    } else {
+        create_debug_callback(&instance)
+    };
+
+    Ok((instance, debug_callback))
+}
+
+// Instance::new only says "extension not present" without telling which one, which is not
+// very helpful over SSH or in WSL where the platform surface extension tends to be missing
+fn check_instance_extensions(required: &InstanceExtensions) -> Result<(), InitError> {
+    let supported = InstanceExtensions::supported_by_core()?;
+
+    let missing = required.difference(&supported);
+    if missing != InstanceExtensions::none() {
+        return Err(InitError::MissingInstanceExtensions(missing));
+    }
+
+    Ok(())
+}
+
+// Sample counts are powers of two and the limits are a bitmask of them, so this walks up until the
+// next one isn't supported anymore
+fn supported_samples(physical: PhysicalDevice, requested: u32) -> u32 {
+    // The depth buffer (and its stencil) gets the same sample count as the color attachment
+    let supported = physical.limits().framebuffer_color_sample_counts()
+        & physical.limits().framebuffer_depth_sample_counts()
+        & physical.limits().framebuffer_stencil_sample_counts();
+
+    let mut samples = 1;
+    while samples * 2 <= requested && supported & (samples * 2) != 0 {
+        samples *= 2;
+    }
+
+    if samples != requested {
+        println!("{}x MSAA is not supported, using {}x", requested, samples);
+    }
+    samples
+}
+
+// Attachments are always the color target(s) followed by depth. With depth_prepass there's a depth
+// only subpass first and the color pass is subpass 1, otherwise there's just the color pass
+// color_ops is for the image that ends up on screen. With MSAA its load op goes to the multisampled
+// image instead, which then has to be kept around between frames for Load to see anything. decals
+// adds the decal subpass after the color pass
+pub fn create_render_pass(device: &Arc<Device>, format: Format, samples: u32, depth_prepass: bool,
+                          color_ops: AttachmentOps, decals: bool)
+                          -> Result<Arc<dyn RenderPassAbstract + Send + Sync>, InitError> {
+    let depth_format = depth_format(device, !decals);
+
+    // Render into the multisampled image and resolve it into the swapchain image at the end
+    let render_pass: Arc<dyn RenderPassAbstract + Send + Sync> = match (samples > 1, depth_prepass) {
+        (true, false) => Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    intermediary: {
+                        load: Clear,
+                        store: DontCare,
+                        format: format,
+                        samples: samples,
+                    },
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: format,
+                        samples: 1,
+                    },
+                    depth: {
+                        load: Clear,
+                        store: DontCare,
+                        format: depth_format,
+                        samples: samples,
+                    }
+                },
+                pass: {
+                    color: [intermediary],
+                    depth_stencil: {depth},
+                    resolve: [color],
                 }
-            },
-            pass: {
-                color: [color],
-                depth_stencil: {}
-            }
-        )
-            .unwrap()
-    )
+            )?
+        ),
+        (true, true) => Arc::new(
+            vulkano::ordered_passes_renderpass!(
+                device.clone(),
+                attachments: {
+                    intermediary: {
+                        load: Clear,
+                        store: DontCare,
+                        format: format,
+                        samples: samples,
+                    },
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: format,
+                        samples: 1,
+                    },
+                    depth: {
+                        load: Clear,
+                        store: DontCare,
+                        format: depth_format,
+                        samples: samples,
+                    }
+                },
+                passes: [
+                    {
+                        color: [],
+                        depth_stencil: {depth},
+                        input: []
+                    },
+                    {
+                        color: [intermediary],
+                        depth_stencil: {depth},
+                        input: [],
+                        resolve: [color]
+                    }
+                ]
+            )?
+        ),
+        (false, false) => Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: Clear,
+                        store: Store,
+                        format: format,
+                        samples: 1,
+                    },
+                    depth: {
+                        load: Clear,
+                        store: DontCare,
+                        format: depth_format,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {depth}
+                }
+            )?
+        ),
+        (false, true) => Arc::new(
+            vulkano::ordered_passes_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: Clear,
+                        store: Store,
+                        format: format,
+                        samples: 1,
+                    },
+                    depth: {
+                        load: Clear,
+                        store: DontCare,
+                        format: depth_format,
+                        samples: 1,
+                    }
+                },
+                passes: [
+                    {
+                        color: [],
+                        depth_stencil: {depth},
+                        input: []
+                    },
+                    {
+                        color: [color],
+                        depth_stencil: {depth},
+                        input: []
+                    }
+                ]
+            )?
+        ),
+    };
+
+    let render_pass = if color_ops == AttachmentOps::default() {
+        render_pass
+    } else {
+        let ops = if samples > 1 {
+            let intermediary = AttachmentOps {
+                load: color_ops.load,
+                store: if color_ops.load == LoadOp::Load { StoreOp::Store } else { StoreOp::DontCare },
+            };
+            vec!((0, intermediary), (1, AttachmentOps { load: LoadOp::DontCare, store: color_ops.store }))
+        } else {
+            vec!((0, color_ops))
+        };
+        with_attachment_ops(device, &render_pass, &ops)?
+    };
+
+    if decals {
+        Ok(with_decal_subpass(device, &render_pass)?)
+    } else {
+        Ok(render_pass)
+    }
 }
 
-fn create_device_and_queue(physical: PhysicalDevice, surface: &Arc<Surface<Window>>)
-                           -> (Arc<Device>, Arc<Queue>) {
-    let queue_family = physical.queue_families()
-        .find(|&q| {
-            q.supports_graphics() && surface.is_supported(q).unwrap_or(false)
+// The first of DEPTH_FORMATS the device can render depth into, without stencil only the depth only
+// formats count. Every device has to support D16Unorm
+fn depth_format(device: &Arc<Device>, stencil: bool) -> Format {
+    DEPTH_FORMATS.iter()
+        .cloned()
+        .filter(|format| stencil || format.ty() == FormatTy::Depth)
+        .find(|&format| {
+            optimal_tiling_features(device.physical_device(), format)
+                .is_some_and(|features| features.depth_stencil_attachment)
         })
-        .unwrap();
+        .unwrap_or(Format::D16Unorm)
+}
+
+// create_render_pass always puts the depth attachment last
+fn depth_attachment_format(render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>) -> Format {
+    render_pass.attachment_desc(render_pass.num_attachments() - 1)
+        .unwrap()
+        .format
+}
+
+fn create_device_and_queues(physical: PhysicalDevice, surface: Option<&Arc<Surface<Arc<Window>>>>, config: &DeviceConfig,
+                            exclusive_fullscreen: bool) -> Result<DeviceWithQueues, InitError> {
+    let queue_family = find_queue_family(physical, surface)
+        .ok_or(InitError::NoQueueFamily)?;
+
+    let mut queue_families = vec!((queue_family, 0.5));
+    let transfer_family = if config.transfer_queue {
+        find_transfer_queue_family(physical, queue_family)
+    } else {
+        None
+    };
+    if let Some(transfer_family) = transfer_family {
+        queue_families.push((transfer_family, 0.5));
+    }
+
+    // Usually the graphics family, where it isn't presenting needs a queue of its own. That can
+    // happen to be the transfer family's queue
+    let present_family = surface.and_then(|surface| find_present_queue_family(physical, surface, queue_family));
+    if let Some(present_family) = present_family {
+        if queue_families.iter().all(|(family, _)| family.id() != present_family.id()) {
+            queue_families.push((present_family, 0.5));
+        }
+    }
 
     let device_extensions = DeviceExtensions {
-        khr_swapchain: true,
+        khr_swapchain: surface.is_some(),
+        ext_full_screen_exclusive: exclusive_fullscreen
+            && DeviceExtensions::supported_by_device(physical).ext_full_screen_exclusive,
         ..DeviceExtensions::none()
     };
 
-    let (device, mut queues) = Device::new(
+    // Only what's asked for, every extra feature can make the driver pick slower paths
+    let mut features = config.required_features.clone();
+    if config.debug_features {
+        let supported = physical.supported_features();
+        features.fill_mode_non_solid |= supported.fill_mode_non_solid;
+        features.wide_lines |= supported.wide_lines;
+    }
+    if config.compressed_textures {
+        let supported = physical.supported_features();
+        features.texture_compression_bc |= supported.texture_compression_bc;
+        features.texture_compression_etc2 |= supported.texture_compression_etc2;
+    }
+
+    let (device, queues) = Device::new(
         physical,
-        physical.supported_features(),
+        &features,
         &device_extensions,
-        [(queue_family, 0.5)].iter()
+        queue_families.iter()
             .cloned(),
-    )
-        .unwrap();
+    )?;
 
-    let queue = queues.next()
-        .unwrap();
-    (device, queue)
+    // We asked for at least the graphics queue so this can't be empty
+    let queues = queues.collect::<Vec<_>>();
+    let queue = queues[0].clone();
+    let queue_of = |family: Option<QueueFamily>| family
+        .and_then(|family| queues.iter().find(|queue| queue.family().id() == family.id()))
+        .cloned()
+        .unwrap_or_else(|| queue.clone());
+    let transfer_queue = queue_of(transfer_family);
+    let present_queue = queue_of(present_family);
+    Ok((device, queue, transfer_queue, present_queue))
 }
 
-fn create_swapchain(queue: &Arc<Queue>, surface: &Arc<Surface<Window>>, device: &Arc<Device>, dimensions: [u32; 2])
-                    -> (Arc<Swapchain<Window>>, Vec<Arc<SwapchainImage<Window>>>) {
+// recreate_with_dimensions keeps the old present mode, so switching it needs a whole new swapchain
+pub fn recreate_swapchain_with_config(old_swapchain: &Arc<Swapchain<Arc<Window>>>, queue: &Arc<Queue>, present_queue: &Arc<Queue>,
+                                      dimensions: [u32; 2], config: &SwapchainConfig)
+                                      -> Result<SwapchainWithImages, InitError> {
+    let surface = old_swapchain.surface();
+    let caps = surface.capabilities(queue.device().physical_device())?;
+    let usage = swapchain_image_usage(caps.supported_usage_flags)?;
+    let (format, color_space) = choose_surface_format(&caps.supported_formats)?;
+    let present_mode = choose_present_mode(caps.present_modes, config.present_mode);
+    let image_count = choose_image_count(caps.min_image_count, caps.max_image_count, config.image_count);
+
+    let swapchain = Swapchain::with_old_swapchain(
+        queue.device().clone(),
+        surface.clone(),
+        image_count,
+        format,
+        dimensions,
+        1,
+        usage,
+        swapchain_sharing(queue, present_queue),
+        old_swapchain.transform(),
+        old_swapchain.composite_alpha(),
+        present_mode,
+        old_swapchain.fullscreen_exclusive(),
+        old_swapchain.clipped(),
+        color_space,
+        old_swapchain.clone(),
+    )?;
+
+    Ok(swapchain)
+}
+
+// The images get rendered to on one queue and presented on the other. Sharing them between the two
+// families saves transferring their ownership back and forth every frame
+fn swapchain_sharing(queue: &Arc<Queue>, present_queue: &Arc<Queue>) -> SharingMode {
+    if queue.family().id() == present_queue.family().id() {
+        SharingMode::from(queue)
+    } else {
+        SharingMode::from(&[queue, present_queue][..])
+    }
+}
+
+pub fn create_swapchain(queue: &Arc<Queue>, present_queue: &Arc<Queue>, surface: &Arc<Surface<Arc<Window>>>, dimensions: [u32; 2],
+                    config: &SwapchainConfig) -> Result<SwapchainWithImages, InitError> {
+    let device = queue.device();
     let dev = device.clone();
-    let caps = surface.capabilities(dev.physical_device().clone())
-        .unwrap();
-    let usage = caps.supported_usage_flags;
+    let caps = surface.capabilities(dev.physical_device())?;
+    let usage = swapchain_image_usage(caps.supported_usage_flags)?;
     let alpha = caps.supported_composite_alpha
         .iter()
         .next()
-        .unwrap();
-    let format = caps.supported_formats[0].0;
+        .ok_or(InitError::NoCompositeAlpha)?;
+    let (format, color_space) = choose_surface_format(&caps.supported_formats)?;
+    let present_mode = choose_present_mode(caps.present_modes, config.present_mode);
+    let image_count = choose_image_count(caps.min_image_count, caps.max_image_count, config.image_count);
+    let fullscreen_exclusive = choose_fullscreen_exclusive(device, config);
 
-    Swapchain::new(
+    let swapchain = Swapchain::new(
         dev,
         surface.clone(),
-        caps.min_image_count,
+        image_count,
         format,
         dimensions,
         1,
         usage,
-        queue,
+        swapchain_sharing(queue, present_queue),
         SurfaceTransform::Identity,
         alpha,
-        PresentMode::Fifo,
-        FullscreenExclusive::Default,
+        present_mode,
+        fullscreen_exclusive,
         true,
-        ColorSpace::SrgbNonLinear,
-    )
-        .unwrap()
-}
\ No newline at end of file
+        color_space,
+    )?;
+
+    Ok(swapchain)
+}
+
+// The first format isn't necessarily sRGB on every driver, and a UNORM swapchain makes everything
+// look washed out
+// A surface has to have at least one format, but a broken driver could still report none
+fn choose_surface_format(supported: &[(Format, ColorSpace)]) -> Result<(Format, ColorSpace), InitError> {
+    let preferred = [Format::B8G8R8A8Srgb, Format::R8G8B8A8Srgb];
+
+    let chosen = supported.iter()
+        .find(|(format, color_space)| {
+            preferred.contains(format) && *color_space == ColorSpace::SrgbNonLinear
+        })
+        .or_else(|| supported.first())
+        .ok_or(InitError::NoSurfaceFormat)?;
+
+    println!("Using surface format: {:?} ({:?})", chosen.0, chosen.1);
+    Ok(*chosen)
+}
+
+// The surface's limits, without a max there's no limit
+fn choose_image_count(min_image_count: u32, max_image_count: Option<u32>, requested: Option<u32>) -> u32 {
+    let image_count = requested
+        .unwrap_or(min_image_count)
+        .clamp(min_image_count, max_image_count.unwrap_or(u32::MAX));
+
+    println!("Using {} swapchain images", image_count);
+    image_count
+}
+
+fn choose_fullscreen_exclusive(device: &Device, config: &SwapchainConfig) -> FullscreenExclusive {
+    if !config.exclusive_fullscreen {
+        return FullscreenExclusive::Default;
+    }
+
+    // Swapchain::new silently ignores the mode without all three
+    let instance_extensions = device.instance().loaded_extensions();
+    if device.loaded_extensions().ext_full_screen_exclusive
+        && instance_extensions.khr_get_physical_device_properties2
+        && instance_extensions.khr_get_surface_capabilities2 {
+        FullscreenExclusive::AppControlled
+    } else {
+        println!("Exclusive fullscreen is not supported, fullscreen will be borderless");
+        FullscreenExclusive::Default
+    }
+}
+
+fn choose_present_mode(supported: SupportedPresentModes, desired: PresentMode) -> PresentMode {
+    let present_mode = if supported.supports(desired) {
+        desired
+    } else if desired == PresentMode::Mailbox && supported.supports(PresentMode::Immediate) {
+        println!("Present mode Mailbox is not supported, falling back to Immediate");
+        PresentMode::Immediate
+    } else {
+        println!("Present mode {:?} is not supported, falling back to Fifo", desired);
+        PresentMode::Fifo
+    };
+
+    println!("Using present mode: {:?}", present_mode);
+    present_mode
+}
+
+// Readback copies out of the swapchain images and the resolution blit copies into them, so we ask
+// for exactly those usages and bail out loudly instead of assuming the driver hands them out
+fn swapchain_image_usage(supported: ImageUsage) -> Result<ImageUsage, InitError> {
+    let required = ImageUsage {
+        color_attachment: true,
+        transfer_source: true,
+        transfer_destination: true,
+        ..ImageUsage::none()
+    };
+
+    let mut missing = vec!();
+    if !supported.color_attachment {
+        missing.push("color_attachment");
+    }
+    if !supported.transfer_source {
+        missing.push("transfer_source");
+    }
+    if !supported.transfer_destination {
+        missing.push("transfer_destination");
+    }
+
+    if !missing.is_empty() {
+        return Err(InitError::MissingSwapchainUsage(missing));
+    }
+
+    Ok(required)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surface_format_prefers_srgb() {
+        let supported = [
+            (Format::B8G8R8A8Unorm, ColorSpace::SrgbNonLinear),
+            (Format::B8G8R8A8Srgb, ColorSpace::SrgbNonLinear),
+        ];
+        assert_eq!(choose_surface_format(&supported).unwrap(), supported[1]);
+        assert_eq!(choose_surface_format(&supported[..1]).unwrap(), supported[0]);
+        assert!(matches!(choose_surface_format(&[]), Err(InitError::NoSurfaceFormat)));
+    }
+
+    #[test]
+    fn image_count_stays_in_the_limits() {
+        assert_eq!(choose_image_count(2, Some(8), None), 2);
+        assert_eq!(choose_image_count(2, Some(8), Some(3)), 3);
+        assert_eq!(choose_image_count(2, Some(3), Some(5)), 3);
+        assert_eq!(choose_image_count(2, Some(8), Some(1)), 2);
+        assert_eq!(choose_image_count(2, None, Some(16)), 16);
+    }
+
+    #[test]
+    fn letterbox_bars_go_on_the_longer_side() {
+        // Wider than 16:9, bars left and right
+        let viewport = letterboxed_viewport([2000, 900], 16.0 / 9.0);
+        assert_eq!(viewport.dimensions, [1600.0, 900.0]);
+        assert_eq!(viewport.origin, [200.0, 0.0]);
+
+        // Taller, bars above and below
+        let viewport = letterboxed_viewport([1600, 1000], 16.0 / 9.0);
+        assert_eq!(viewport.dimensions, [1600.0, 900.0]);
+        assert_eq!(viewport.origin, [0.0, 50.0]);
+
+        let viewport = letterboxed_viewport([1600, 900], 16.0 / 9.0);
+        assert_eq!(viewport.origin, [0.0, 0.0]);
+    }
+}
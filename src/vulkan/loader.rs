@@ -0,0 +1,27 @@
+use vulkano::VulkanObject;
+use vulkano::instance::Instance;
+use vulkano::instance::loader::auto_loader;
+
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::c_char;
+
+// The same function vulkano calls, but with the null it returns for unknown names as None
+type GetInstanceProcAddr = extern "system" fn(usize, *const c_char) -> Option<extern "system" fn()>;
+
+// For the functions vulkano 0.18 doesn't expose. Device level ones come back as the loader's
+// trampolines, which work for every device of the instance. The caller has to transmute it to the
+// right signature
+pub fn instance_function(instance: &Instance, name: &str) -> Option<extern "system" fn()> {
+    let loader = auto_loader().ok()?;
+    let name = CString::new(name).unwrap();
+    unsafe {
+        // The loader always knows vkGetInstanceProcAddr itself, so this one is never null. Its
+        // return type in vulkano can't be null either, which isn't true for every other name
+        let own_name = CString::new("vkGetInstanceProcAddr").unwrap();
+        let get_instance_proc_addr = mem::transmute::<extern "system" fn(), GetInstanceProcAddr>(
+            loader.get_instance_proc_addr(instance.internal_object(), own_name.as_ptr())
+        );
+        get_instance_proc_addr(instance.internal_object(), name.as_ptr())
+    }
+}
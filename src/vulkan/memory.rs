@@ -0,0 +1,134 @@
+use vulkano::VulkanObject;
+use vulkano::device::RawDeviceExtensions;
+use vulkano::instance::PhysicalDevice;
+
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+use super::loader::instance_function;
+
+// vulkano 0.18 only hands out the heap sizes. How much of a heap is in use (by us and everyone else)
+// and how much we can use comes from VK_EXT_memory_budget, which vulkano doesn't know about, so
+// that gets queried by hand through vkGetPhysicalDeviceMemoryProperties2KHR. These mirror the
+// structs in vulkan_core.h
+const MAX_MEMORY_TYPES: usize = 32;
+const MAX_MEMORY_HEAPS: usize = 16;
+
+const STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_PROPERTIES_2: u32 = 1000059006;
+const STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_BUDGET_PROPERTIES_EXT: u32 = 1000237000;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MemoryType {
+    property_flags: u32,
+    heap_index: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MemoryHeap {
+    size: u64,
+    flags: u32,
+}
+
+#[repr(C)]
+struct MemoryProperties2 {
+    s_type: u32,
+    p_next: *mut c_void,
+    memory_type_count: u32,
+    memory_types: [MemoryType; MAX_MEMORY_TYPES],
+    memory_heap_count: u32,
+    memory_heaps: [MemoryHeap; MAX_MEMORY_HEAPS],
+}
+
+#[repr(C)]
+struct MemoryBudgetProperties {
+    s_type: u32,
+    p_next: *mut c_void,
+    heap_budget: [u64; MAX_MEMORY_HEAPS],
+    heap_usage: [u64; MAX_MEMORY_HEAPS],
+}
+
+type GetMemoryProperties2 = extern "system" fn(usize, *mut MemoryProperties2);
+
+// Budget and usage per heap, indexed like PhysicalDevice::memory_heaps
+struct HeapBudgets {
+    budget: Vec<u64>,
+    usage: Vec<u64>,
+}
+
+// Prints every heap with its size, plus its usage and budget when the driver can tell. At startup
+// and on M in the demo
+pub fn log_memory_heaps(physical: PhysicalDevice) {
+    let budgets = query_budgets(physical);
+    if budgets.is_none() {
+        println!("VK_EXT_memory_budget is not supported, only showing heap sizes");
+    }
+
+    for heap in physical.memory_heaps() {
+        let kind = if heap.is_device_local() { "device local" } else { "host" };
+        let index = heap.id() as usize;
+        match &budgets {
+            Some(budgets) => println!(
+                "Memory heap {} ({}): {} used of {} budget, {} total",
+                index,
+                kind,
+                format_bytes(budgets.usage[index]),
+                format_bytes(budgets.budget[index]),
+                format_bytes(heap.size() as u64),
+            ),
+            None => println!("Memory heap {} ({}): {} total", index, kind, format_bytes(heap.size() as u64)),
+        }
+    }
+}
+
+fn query_budgets(physical: PhysicalDevice) -> Option<HeapBudgets> {
+    // vulkano creates the instance without an application info, which makes it a Vulkan 1.0
+    // instance no matter what the device supports. The core vkGetPhysicalDeviceMemoryProperties2
+    // is off limits then, only the KHR one from the instance extension can be used
+    let instance = physical.instance();
+    if !instance.loaded_extensions().khr_get_physical_device_properties2 {
+        return None;
+    }
+
+    // vulkano 0.18 doesn't know VK_EXT_memory_budget, so it only shows up in the raw list. The
+    // query only needs the device to support it, not to have it enabled
+    let budget_extension = CString::new("VK_EXT_memory_budget").unwrap();
+    if !RawDeviceExtensions::supported_by_device(physical).iter().any(|name| *name == budget_extension) {
+        return None;
+    }
+
+    let function = instance_function(instance, "vkGetPhysicalDeviceMemoryProperties2KHR")?;
+    let get_memory_properties = unsafe {
+        mem::transmute::<extern "system" fn(), GetMemoryProperties2>(function)
+    };
+
+    unsafe {
+        let mut budget: MemoryBudgetProperties = mem::zeroed();
+        budget.s_type = STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_BUDGET_PROPERTIES_EXT;
+        budget.p_next = ptr::null_mut();
+
+        let mut properties: MemoryProperties2 = mem::zeroed();
+        properties.s_type = STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_PROPERTIES_2;
+        properties.p_next = &mut budget as *mut MemoryBudgetProperties as *mut c_void;
+
+        get_memory_properties(physical.internal_object(), &mut properties);
+
+        let heap_count = properties.memory_heap_count as usize;
+        Some(HeapBudgets {
+            budget: budget.heap_budget[..heap_count].to_vec(),
+            usage: budget.heap_usage[..heap_count].to_vec(),
+        })
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const MIB: f64 = 1024.0 * 1024.0;
+    if bytes >= 1024 * 1024 * 1024 {
+        format!("{:.2} GiB", bytes as f64 / (MIB * 1024.0))
+    } else {
+        format!("{:.1} MiB", bytes as f64 / MIB)
+    }
+}
@@ -1 +1,12 @@
-pub mod initialization;
\ No newline at end of file
+pub mod initialization;
+pub mod device;
+pub mod loader;
+pub mod memory;
+pub mod error;
+pub mod format;
+pub mod pipeline;
+pub mod render_pass;
+pub mod shader;
+pub mod validation;
+#[cfg(feature = "renderdoc")]
+pub mod capture;
\ No newline at end of file
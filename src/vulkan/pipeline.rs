@@ -0,0 +1,174 @@
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::device::Device;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineCreationError};
+use vulkano::pipeline::blend::AttachmentBlend;
+use vulkano::pipeline::depth_stencil::{Compare, DepthStencil, Stencil};
+use vulkano::pipeline::input_assembly::PrimitiveTopology;
+use vulkano::pipeline::raster::PolygonMode;
+use vulkano::pipeline::shader::{GraphicsEntryPointAbstract, ShaderInterfaceDefMatch};
+use vulkano::pipeline::vertex::VertexDefinition;
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+pub type BuiltPipeline<Vdef> = Arc<GraphicsPipeline<
+    Vdef,
+    Box<dyn PipelineLayoutAbstract + Send + Sync>,
+    Arc<dyn RenderPassAbstract + Send + Sync>,
+>>;
+
+#[derive(Debug)]
+pub enum PipelineError {
+    // The render pass has fewer subpasses than PipelineBuilder::subpass asked for
+    MissingSubpass(u32),
+    Creation(GraphicsPipelineCreationError),
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PipelineError::MissingSubpass(subpass) => write!(f, "the render pass has no subpass {}", subpass),
+            PipelineError::Creation(e) => write!(f, "failed to create the pipeline: {}", e),
+        }
+    }
+}
+
+impl Error for PipelineError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PipelineError::Creation(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<GraphicsPipelineCreationError> for PipelineError {
+    fn from(e: GraphicsPipelineCreationError) -> PipelineError {
+        PipelineError::Creation(e)
+    }
+}
+
+// Everything about a pipeline except its vertex input and shaders, which are what makes the
+// pipeline types differ. Cheap to clone, so variants (wireframe, lines) start from the same base.
+// Viewports are always dynamic since they change with the window. Nothing culls, the triangles
+// are seen from both sides when they spin
+#[derive(Clone)]
+pub struct PipelineBuilder {
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    subpass: u32,
+    topology: PrimitiveTopology,
+    polygon_mode: PolygonMode,
+    depth_stencil: DepthStencil,
+    blend: AttachmentBlend,
+    line_width_dynamic: bool,
+}
+
+impl PipelineBuilder {
+    // Filled triangle lists without culling, depth testing or blending, drawing into the first subpass
+    pub fn new(render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>) -> PipelineBuilder {
+        PipelineBuilder {
+            render_pass: render_pass.clone(),
+            subpass: 0,
+            topology: PrimitiveTopology::TriangleList,
+            polygon_mode: PolygonMode::Fill,
+            depth_stencil: DepthStencil::disabled(),
+            blend: AttachmentBlend::pass_through(),
+            line_width_dynamic: false,
+        }
+    }
+
+    pub fn subpass(mut self, subpass: u32) -> PipelineBuilder {
+        self.subpass = subpass;
+        self
+    }
+
+    pub fn topology(mut self, topology: PrimitiveTopology) -> PipelineBuilder {
+        self.topology = topology;
+        self
+    }
+
+    // Anything but Fill needs fill_mode_non_solid
+    pub fn polygon_mode(mut self, polygon_mode: PolygonMode) -> PipelineBuilder {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    // Lines don't end up with exactly the depth of the filled triangles they outline, so an equal
+    // test against a depth prepass would drop most of them. They get a plain depth test instead
+    // and the prepass isn't drawn in wireframe mode. Needs fill_mode_non_solid like polygon_mode
+    pub fn wireframe(&self) -> PipelineBuilder {
+        let mut builder = self.clone().polygon_mode(PolygonMode::Line);
+        if builder.depth_stencil.depth_compare == Compare::Equal {
+            builder.depth_stencil.depth_compare = Compare::Less;
+            builder.depth_stencil.depth_write = true;
+        }
+        builder
+    }
+
+    // A plain less than test that writes depth
+    pub fn depth_test(mut self, depth_test: bool) -> PipelineBuilder {
+        self.depth_stencil = if depth_test { DepthStencil::simple_depth_test() } else { DepthStencil::disabled() };
+        self
+    }
+
+    // For anything depth_test doesn't cover, like only testing for what a prepass left behind
+    pub fn depth_stencil(mut self, depth_stencil: DepthStencil) -> PipelineBuilder {
+        self.depth_stencil = depth_stencil;
+        self
+    }
+
+    // Same test and ops for both faces, on top of whatever depth test is set. Nothing in the
+    // Stencil (reference, masks) that's None here has to be set in the DynamicState instead
+    pub fn stencil(mut self, stencil: Stencil) -> PipelineBuilder {
+        self.depth_stencil.stencil_front = stencil;
+        self.depth_stencil.stencil_back = stencil;
+        self
+    }
+
+    // Blended draws have to come after the opaque ones, without a depth buffer nothing sorts them
+    pub fn blend(mut self, blend: AttachmentBlend) -> PipelineBuilder {
+        self.blend = blend;
+        self
+    }
+
+    // The draws then need a DynamicState with line_width set, and every other pipeline one without
+    pub fn line_width_dynamic(mut self) -> PipelineBuilder {
+        self.line_width_dynamic = true;
+        self
+    }
+
+    pub fn build<Vdef, Vs, Fs>(&self, device: &Arc<Device>, vertex_input: Vdef, vertex_shader: Vs, fragment_shader: Fs)
+                               -> Result<BuiltPipeline<Vdef>, PipelineError>
+        where Vdef: VertexDefinition<Vs::InputDefinition>,
+              Vs: GraphicsEntryPointAbstract<SpecializationConstants = ()>,
+              Fs: GraphicsEntryPointAbstract<SpecializationConstants = ()>,
+              Vs::PipelineLayout: Clone + Send + Sync + 'static,
+              Fs::PipelineLayout: Clone + Send + Sync + 'static,
+              Fs::InputDefinition: ShaderInterfaceDefMatch<Vs::OutputDefinition>
+    {
+        let subpass = Subpass::from(self.render_pass.clone(), self.subpass)
+            .ok_or(PipelineError::MissingSubpass(self.subpass))?;
+        let builder = GraphicsPipeline::start()
+            .vertex_input(vertex_input)
+            .vertex_shader(vertex_shader, ())
+            .primitive_topology(self.topology)
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(fragment_shader, ())
+            .depth_stencil(self.depth_stencil.clone())
+            .blend_collective(self.blend.clone())
+            .cull_mode_disabled()
+            .render_pass(subpass);
+
+        // The builder only has one method per mode
+        let builder = match self.polygon_mode {
+            PolygonMode::Fill => builder.polygon_mode_fill(),
+            PolygonMode::Line => builder.polygon_mode_line(),
+            PolygonMode::Point => builder.polygon_mode_point(),
+        };
+        let builder = if self.line_width_dynamic { builder.line_width_dynamic() } else { builder };
+
+        Ok(Arc::new(builder.build(device.clone())?))
+    }
+}
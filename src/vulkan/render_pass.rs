@@ -0,0 +1,189 @@
+use vulkano::device::Device;
+use vulkano::format::ClearValue;
+use vulkano::framebuffer::{AttachmentDescription, LoadOp, PassDependencyDescription, PassDescription, RenderPass,
+                           RenderPassAbstract, RenderPassCreationError, RenderPassDesc, RenderPassDescClearValues,
+                           StoreOp};
+use vulkano::image::ImageLayout;
+use vulkano::sync::{AccessFlagBits, PipelineStages};
+
+use std::sync::Arc;
+
+// What happens to the color attachment at the start and the end of the render pass. Load keeps
+// what the last frame left in the image (for accumulation and overlays), DontCare skips the clear
+// when every pixel gets drawn anyway. Nothing extra is needed on the swapchain images for Load,
+// color_attachment usage covers it and vulkano transitions them into ColorAttachmentOptimal
+// without throwing away their contents
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttachmentOps {
+    pub load: LoadOp,
+    pub store: StoreOp,
+}
+
+impl Default for AttachmentOps {
+    fn default() -> AttachmentOps {
+        AttachmentOps {
+            load: LoadOp::Clear,
+            store: StoreOp::Store,
+        }
+    }
+}
+
+pub fn load_op_from_arg(arg: &str) -> Option<LoadOp> {
+    match arg {
+        "clear" => Some(LoadOp::Clear),
+        "load" => Some(LoadOp::Load),
+        "dont-care" => Some(LoadOp::DontCare),
+        _ => None,
+    }
+}
+
+pub fn store_op_from_arg(arg: &str) -> Option<StoreOp> {
+    match arg {
+        "store" => Some(StoreOp::Store),
+        "dont-care" => Some(StoreOp::DontCare),
+        _ => None,
+    }
+}
+
+// The render pass macros only take the ops as identifiers, so they can't be picked at runtime.
+// This copies a render pass built by one of them, swaps in the given ops for some of its
+// attachments and builds that instead
+pub fn with_attachment_ops(
+    device: &Arc<Device>,
+    render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+    ops: &[(usize, AttachmentOps)],
+) -> Result<Arc<dyn RenderPassAbstract + Send + Sync>, RenderPassCreationError> {
+    let mut desc = ListedRenderPassDesc::from(render_pass);
+    for &(id, AttachmentOps { load, store }) in ops {
+        desc.attachments[id].load = load;
+        desc.attachments[id].store = store;
+    }
+
+    Ok(Arc::new(RenderPass::new(device.clone(), desc)?))
+}
+
+// Adds a subpass at the end that draws into the same color attachment as the last one and reads
+// the depth attachment (always the last one) as an input attachment, for the decals. With MSAA the
+// resolve moves into it, otherwise the decals would miss the image that ends up on screen. The
+// depth format can't have stencil, vulkano's views of those include both aspects and an input
+// attachment can only have one
+pub fn with_decal_subpass(
+    device: &Arc<Device>,
+    render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+) -> Result<Arc<dyn RenderPassAbstract + Send + Sync>, RenderPassCreationError> {
+    let mut desc = ListedRenderPassDesc::from(render_pass);
+    let depth = desc.attachments.len() - 1;
+    let last = desc.subpasses.len() - 1;
+
+    let resolve_attachments = std::mem::take(&mut desc.subpasses[last].resolve_attachments);
+    desc.subpasses.push(PassDescription {
+        color_attachments: desc.subpasses[last].color_attachments.clone(),
+        depth_stencil: None,
+        // The layout vulkano's AttachmentImage gives input attachment descriptors
+        input_attachments: vec!((depth, ImageLayout::ShaderReadOnlyOptimal)),
+        resolve_attachments,
+        preserve_attachments: vec!(),
+    });
+
+    // Everything the color pass wrote has to be done before the decals read the depth or blend over it
+    desc.dependencies.push(PassDependencyDescription {
+        source_subpass: last,
+        destination_subpass: last + 1,
+        source_stages: PipelineStages {
+            color_attachment_output: true,
+            late_fragment_tests: true,
+            ..PipelineStages::none()
+        },
+        destination_stages: PipelineStages {
+            fragment_shader: true,
+            color_attachment_output: true,
+            ..PipelineStages::none()
+        },
+        source_access: AccessFlagBits {
+            color_attachment_write: true,
+            depth_stencil_attachment_write: true,
+            ..AccessFlagBits::none()
+        },
+        destination_access: AccessFlagBits {
+            input_attachment_read: true,
+            color_attachment_read: true,
+            color_attachment_write: true,
+            ..AccessFlagBits::none()
+        },
+        by_region: true,
+    });
+
+    Ok(Arc::new(RenderPass::new(device.clone(), desc)?))
+}
+
+// Whether a subpass of render_pass reads attachment as an input attachment, which the image for
+// it needs the usage for
+pub fn is_input_attachment(render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>, attachment: usize) -> bool {
+    (0..render_pass.num_subpasses())
+        .filter_map(|id| render_pass.subpass_desc(id))
+        .any(|subpass| subpass.input_attachments.iter().any(|&(id, _)| id == attachment))
+}
+
+// The subpass with_decal_subpass added, if render_pass has one
+pub fn decal_subpass(render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>) -> Option<u32> {
+    let last = render_pass.num_subpasses() - 1;
+    render_pass.subpass_desc(last)
+        .filter(|subpass| !subpass.input_attachments.is_empty())
+        .map(|_| last as u32)
+}
+
+struct ListedRenderPassDesc {
+    attachments: Vec<AttachmentDescription>,
+    subpasses: Vec<PassDescription>,
+    dependencies: Vec<PassDependencyDescription>,
+}
+
+impl ListedRenderPassDesc {
+    // Everything render_pass has, to change and build again
+    fn from(render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>) -> ListedRenderPassDesc {
+        ListedRenderPassDesc {
+            attachments: (0..render_pass.num_attachments())
+                .map(|id| render_pass.attachment_desc(id).unwrap())
+                .collect(),
+            subpasses: (0..render_pass.num_subpasses())
+                .map(|id| render_pass.subpass_desc(id).unwrap())
+                .collect(),
+            dependencies: (0..render_pass.num_dependencies())
+                .map(|id| render_pass.dependency_desc(id).unwrap())
+                .collect(),
+        }
+    }
+}
+
+unsafe impl RenderPassDesc for ListedRenderPassDesc {
+    fn num_attachments(&self) -> usize {
+        self.attachments.len()
+    }
+
+    fn attachment_desc(&self, id: usize) -> Option<AttachmentDescription> {
+        self.attachments.get(id).cloned()
+    }
+
+    fn num_subpasses(&self) -> usize {
+        self.subpasses.len()
+    }
+
+    fn subpass_desc(&self, id: usize) -> Option<PassDescription> {
+        self.subpasses.get(id).cloned()
+    }
+
+    fn num_dependencies(&self) -> usize {
+        self.dependencies.len()
+    }
+
+    fn dependency_desc(&self, id: usize) -> Option<PassDependencyDescription> {
+        self.dependencies.get(id).cloned()
+    }
+}
+
+// Same as the macros, one value per attachment in order
+unsafe impl RenderPassDescClearValues<Vec<ClearValue>> for ListedRenderPassDesc {
+    fn convert_clear_values(&self, values: Vec<ClearValue>) -> Box<dyn Iterator<Item = ClearValue>> {
+        Box::new(values.into_iter())
+    }
+}
@@ -0,0 +1,218 @@
+use vulkano::OomError;
+use vulkano::device::{Device, DeviceExtensions, Features};
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+// SPIR-V starts with a 5 word header, after that every instruction's first word has its length
+// in words in the high half and the opcode in the low half
+const SPIRV_HEADER_WORDS: usize = 5;
+const OP_CAPABILITY: u32 = 17;
+
+#[derive(Debug)]
+pub enum ShaderLoadError {
+    // The SPIR-V uses device features or extensions that aren't enabled. Features can be asked
+    // for with DeviceConfig::required_features
+    MissingRequirement { shader: String, features: Features, extensions: DeviceExtensions },
+    Oom { shader: String, error: OomError },
+}
+
+impl fmt::Display for ShaderLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderLoadError::MissingRequirement { shader, features, extensions } => write!(
+                f,
+                "{} can't be used on this device, it needs features [{}] and extensions {:?} that aren't enabled",
+                shader,
+                feature_names(features).join(", "),
+                extensions,
+            ),
+            ShaderLoadError::Oom { shader, error } => write!(f, "failed to load {}: {}", shader, error),
+        }
+    }
+}
+
+impl Error for ShaderLoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ShaderLoadError::Oom { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+// What a shader needs beyond plain Vulkan 1.0. The Shader::load generated by shader! checks the
+// same thing by panicking and doesn't hand out its SPIR-V, so the built-in shaders declare theirs
+// where they're loaded. SPIR-V loaded at runtime goes through from_spirv instead
+#[derive(Clone)]
+pub struct ShaderRequirements {
+    pub features: Features,
+    pub extensions: DeviceExtensions,
+}
+
+impl ShaderRequirements {
+    // Only the Shader capability, which every device has
+    pub fn none() -> ShaderRequirements {
+        ShaderRequirements {
+            features: Features::none(),
+            extensions: DeviceExtensions::none(),
+        }
+    }
+
+    // From the OpCapability instructions, which all come right after the header. Same table as
+    // vulkano-shaders uses for its checks, capabilities it doesn't know about are left to the driver
+    pub fn from_spirv(words: &[u32]) -> ShaderRequirements {
+        let mut requirements = ShaderRequirements::none();
+        let features = &mut requirements.features;
+        let extensions = &mut requirements.extensions;
+
+        let mut offset = SPIRV_HEADER_WORDS;
+        while let Some(&first) = words.get(offset) {
+            let length = (first >> 16) as usize;
+            if length == 0 {
+                break;
+            }
+
+            if first & 0xffff == OP_CAPABILITY {
+                match words.get(offset + 1) {
+                    Some(2) => features.geometry_shader = true,
+                    Some(3) => features.tessellation_shader = true,
+                    Some(10) => features.shader_f3264 = true,
+                    Some(11) => features.shader_int64 = true,
+                    Some(22) => features.shader_int16 = true,
+                    Some(23) | Some(24) => features.shader_tessellation_and_geometry_point_size = true,
+                    Some(25) => features.shader_image_gather_extended = true,
+                    Some(27) | Some(48) => features.shader_storage_image_multisample = true,
+                    Some(28) => features.shader_uniform_buffer_array_dynamic_indexing = true,
+                    Some(29) => features.shader_sampled_image_array_dynamic_indexing = true,
+                    Some(30) => features.shader_storage_buffer_array_dynamic_indexing = true,
+                    Some(31) => features.shader_storage_image_array_dynamic_indexing = true,
+                    Some(32) => features.shader_clip_distance = true,
+                    Some(33) => features.shader_cull_distance = true,
+                    Some(34) | Some(45) => features.image_cube_array = true,
+                    Some(35) | Some(52) => features.sample_rate_shading = true,
+                    Some(41) => features.shader_resource_residency = true,
+                    Some(42) => features.shader_resource_min_lod = true,
+                    Some(49) => features.shader_storage_image_extended_formats = true,
+                    Some(55) => features.shader_storage_image_read_without_format = true,
+                    Some(56) => features.shader_storage_image_write_without_format = true,
+                    Some(57) => features.multi_viewport = true,
+                    Some(4433..=4436) => extensions.khr_16bit_storage = true,
+                    _ => (),
+                }
+            }
+            offset += length;
+        }
+
+        requirements
+    }
+
+    // Everything from here that the device doesn't have enabled, as a MissingRequirement
+    pub fn check(&self, device: &Arc<Device>, shader: &str) -> Result<(), ShaderLoadError> {
+        let features = self.features.difference(device.enabled_features());
+        let extensions = self.extensions.difference(device.loaded_extensions());
+        if features != Features::none() || extensions != DeviceExtensions::none() {
+            return Err(ShaderLoadError::MissingRequirement { shader: shader.to_string(), features, extensions });
+        }
+        Ok(())
+    }
+}
+
+// For the Shader::load generated by vulkano_shaders::shader!, e.g.
+// load_shader(&device, "vert.glsl", ShaderRequirements::none(), || vs::Shader::load(device.clone()))
+pub fn load_shader<S>(device: &Arc<Device>, shader: &str, requirements: ShaderRequirements,
+                      load: impl FnOnce() -> Result<S, OomError>) -> Result<S, ShaderLoadError> {
+    requirements.check(device, shader)?;
+    load().map_err(|error| ShaderLoadError::Oom { shader: shader.to_string(), error })
+}
+
+// Only the features from_spirv can ask for
+fn feature_names(features: &Features) -> Vec<&'static str> {
+    let names = [
+        (features.geometry_shader, "geometry_shader"),
+        (features.tessellation_shader, "tessellation_shader"),
+        (features.shader_f3264, "shader_f3264"),
+        (features.shader_int64, "shader_int64"),
+        (features.shader_int16, "shader_int16"),
+        (features.shader_tessellation_and_geometry_point_size, "shader_tessellation_and_geometry_point_size"),
+        (features.shader_image_gather_extended, "shader_image_gather_extended"),
+        (features.shader_storage_image_multisample, "shader_storage_image_multisample"),
+        (features.shader_uniform_buffer_array_dynamic_indexing, "shader_uniform_buffer_array_dynamic_indexing"),
+        (features.shader_sampled_image_array_dynamic_indexing, "shader_sampled_image_array_dynamic_indexing"),
+        (features.shader_storage_buffer_array_dynamic_indexing, "shader_storage_buffer_array_dynamic_indexing"),
+        (features.shader_storage_image_array_dynamic_indexing, "shader_storage_image_array_dynamic_indexing"),
+        (features.shader_clip_distance, "shader_clip_distance"),
+        (features.shader_cull_distance, "shader_cull_distance"),
+        (features.image_cube_array, "image_cube_array"),
+        (features.sample_rate_shading, "sample_rate_shading"),
+        (features.shader_resource_residency, "shader_resource_residency"),
+        (features.shader_resource_min_lod, "shader_resource_min_lod"),
+        (features.shader_storage_image_extended_formats, "shader_storage_image_extended_formats"),
+        (features.shader_storage_image_read_without_format, "shader_storage_image_read_without_format"),
+        (features.shader_storage_image_write_without_format, "shader_storage_image_write_without_format"),
+        (features.multi_viewport, "multi_viewport"),
+    ];
+    names.iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Vec<u32> {
+        vec!(0x0723_0203, 0x0001_0000, 0, 1, 0)
+    }
+
+    fn capability(capability: u32) -> [u32; 2] {
+        [(2 << 16) | OP_CAPABILITY, capability]
+    }
+
+    #[test]
+    fn plain_shader_needs_nothing() {
+        let mut words = header();
+        words.extend_from_slice(&capability(1));
+        let requirements = ShaderRequirements::from_spirv(&words);
+        assert_eq!(requirements.features, Features::none());
+        assert_eq!(requirements.extensions, DeviceExtensions::none());
+    }
+
+    #[test]
+    fn capabilities_map_to_features() {
+        let mut words = header();
+        words.extend_from_slice(&capability(1));
+        words.extend_from_slice(&capability(10));
+        words.extend_from_slice(&capability(32));
+        words.extend_from_slice(&capability(4433));
+        let requirements = ShaderRequirements::from_spirv(&words);
+        assert!(requirements.features.shader_f3264);
+        assert!(requirements.features.shader_clip_distance);
+        assert!(!requirements.features.geometry_shader);
+        assert!(requirements.extensions.khr_16bit_storage);
+        assert_eq!(feature_names(&requirements.features), vec!("shader_f3264", "shader_clip_distance"));
+    }
+
+    #[test]
+    fn other_instructions_are_skipped() {
+        let mut words = header();
+        // OpExtInstImport with a 3 word name, whose words would read as capability 35 otherwise
+        words.extend_from_slice(&[(5 << 16) | 11, 1, 35, 35, 0]);
+        words.extend_from_slice(&capability(2));
+        let requirements = ShaderRequirements::from_spirv(&words);
+        assert!(requirements.features.geometry_shader);
+        assert!(!requirements.features.sample_rate_shading);
+    }
+
+    #[test]
+    fn truncated_spirv_stops() {
+        let mut words = header();
+        // A length of 0 would never advance
+        words.push(OP_CAPABILITY);
+        words.extend_from_slice(&capability(2));
+        assert_eq!(ShaderRequirements::from_spirv(&words).features, Features::none());
+        assert_eq!(ShaderRequirements::from_spirv(&[1, 2]).features, Features::none());
+    }
+}
@@ -0,0 +1,78 @@
+use vulkano::instance::{Instance, InstanceExtensions, layers_list};
+use vulkano::instance::debug::{DebugCallback, Message, MessageSeverity, MessageType};
+
+use std::sync::Arc;
+
+const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+// Missing validation shouldn't keep the sandbox from starting, so this warns and hands back no
+// layers when either the layer or the debug utils extension isn't installed
+pub fn validation_layers(supported: &InstanceExtensions) -> Vec<&'static str> {
+    let layer_available = match layers_list() {
+        Ok(mut layers) => layers.any(|layer| layer.name() == VALIDATION_LAYER),
+        Err(_) => false,
+    };
+
+    if !layer_available {
+        println!("Warning: {} is not available, continuing without validation", VALIDATION_LAYER);
+        return vec!();
+    }
+
+    if !supported.ext_debug_utils {
+        println!("Warning: VK_EXT_debug_utils is not available, continuing without validation");
+        return vec!();
+    }
+
+    vec!(VALIDATION_LAYER)
+}
+
+// Everything the loader found, which is the first thing to look at when validation doesn't load
+pub fn list_layers() {
+    let layers = match layers_list() {
+        Ok(layers) => layers.collect::<Vec<_>>(),
+        Err(e) => {
+            println!("Failed to list the instance layers: {}", e);
+            return;
+        }
+    };
+
+    if layers.is_empty() {
+        println!("No instance layers installed");
+    }
+    for layer in layers {
+        println!("{} (vulkan {}): {}", layer.name(), layer.vulkan_version(), layer.description());
+    }
+}
+
+pub fn create_debug_callback(instance: &Arc<Instance>) -> Option<DebugCallback> {
+    let severity = MessageSeverity {
+        error: true,
+        warning: true,
+        information: true,
+        verbose: false,
+    };
+
+    let callback = DebugCallback::new(instance, severity, MessageType::all(), |message| {
+        println!("[{}] {}: {}", severity_label(message), message.layer_prefix, message.description);
+    });
+
+    match callback {
+        Ok(callback) => Some(callback),
+        Err(e) => {
+            println!("Warning: failed to register the debug callback: {}", e);
+            None
+        }
+    }
+}
+
+fn severity_label(message: &Message) -> &'static str {
+    if message.severity.error {
+        "error"
+    } else if message.severity.warning {
+        "warning"
+    } else if message.severity.information {
+        "info"
+    } else {
+        "verbose"
+    }
+}